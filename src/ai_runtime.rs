@@ -2,6 +2,9 @@
 //! Gemini API等を使用してAI処理を実行する
 
 use std::env;
+use std::time::Duration;
+
+use async_trait::async_trait;
 
 /// AIランタイムエラー
 #[derive(Debug)]
@@ -9,6 +12,26 @@ pub enum AiError {
     ApiKeyNotSet,
     RequestFailed(String),
     ParseError(String),
+    /// 制限時間内に応答がなかった
+    Timeout,
+    /// 一時的な失敗 (通信エラー・429・5xx)。再試行で回復しうる
+    Transient(String),
+}
+
+impl AiError {
+    /// HTTP ステータスから失敗の種類を決める
+    pub fn from_status(status: u16, body: String) -> Self {
+        if status == 429 || (500..600).contains(&status) {
+            AiError::Transient(format!("HTTP {}: {}", status, body))
+        } else {
+            AiError::RequestFailed(body)
+        }
+    }
+
+    /// 再試行する価値のある失敗か
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, AiError::Transient(_) | AiError::Timeout)
+    }
 }
 
 impl std::fmt::Display for AiError {
@@ -17,6 +40,113 @@ impl std::fmt::Display for AiError {
             AiError::ApiKeyNotSet => write!(f, "GEMINI_API_KEY environment variable not set"),
             AiError::RequestFailed(e) => write!(f, "API request failed: {}", e),
             AiError::ParseError(e) => write!(f, "Failed to parse response: {}", e),
+            AiError::Timeout => write!(f, "timeout"),
+            AiError::Transient(e) => write!(f, "Transient failure: {}", e),
+        }
+    }
+}
+
+/// AI動詞に渡すオプション
+/// `... を {言語: "en", 長さ: "短い"} に 翻訳する` のレコードを型付きの項目に振り分けたもの
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AiOptions {
+    /// 翻訳先の言語
+    pub language: Option<String>,
+    /// 出力の長さ (例: "短い")
+    pub length: Option<String>,
+    /// 1回の呼び出しの制限時間 (未指定なら DEFAULT_AI_TIMEOUT)
+    pub timeout: Option<Duration>,
+    /// 既知の項目以外 (キー順を保持)
+    pub extra: Vec<(String, String)>,
+}
+
+/// AI呼び出しの既定の制限時間
+pub const DEFAULT_AI_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl AiOptions {
+    /// 単一の値は従来どおり言語として扱う
+    pub fn language(language: impl Into<String>) -> Self {
+        Self { language: Some(language.into()), ..Self::default() }
+    }
+
+    /// レコードの各項目を対応するフィールドへ振り分ける
+    pub fn from_fields(fields: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut options = Self::default();
+        for (key, value) in fields {
+            match key.as_str() {
+                "言語" | "language" => options.language = Some(value),
+                "長さ" | "length" => options.length = Some(value),
+                "タイムアウト" | "timeout" => match parse_timeout(&value) {
+                    Some(timeout) => options.timeout = Some(timeout),
+                    None => log::warn!("[AI] Invalid timeout '{}', using default", value),
+                },
+                _ => options.extra.push((key, value)),
+            }
+        }
+        options
+    }
+
+    /// この呼び出しに適用する制限時間
+    pub fn effective_timeout(&self) -> Duration {
+        self.timeout.unwrap_or(DEFAULT_AI_TIMEOUT)
+    }
+}
+
+/// "5" / "5秒" / "500ミリ秒" / "500ms" を制限時間として解釈する (単位なしは秒)
+fn parse_timeout(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (number, millis) = if let Some(n) = value.strip_suffix("ミリ秒").or_else(|| value.strip_suffix("ms")) {
+        (n, true)
+    } else {
+        (value.strip_suffix('秒').or_else(|| value.strip_suffix('s')).unwrap_or(value), false)
+    };
+    let number: f64 = number.trim().parse().ok().filter(|n: &f64| n.is_finite() && *n >= 0.0)?;
+    Some(Duration::from_secs_f64(if millis { number / 1000.0 } else { number }))
+}
+
+/// 想像する (画像生成) に使うモデル
+const IMAGE_MODEL: &str = "gemini-2.0-flash-preview-image-generation";
+
+/// 一時的な失敗に対する再試行の設定 (既定では再試行しない)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// 最初の試行に加えて再試行する回数
+    pub max_retries: u32,
+    /// 1回目の再試行までの待ち時間
+    pub initial_backoff: Duration,
+    /// 再試行ごとに待ち時間を掛ける倍率
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 0, initial_backoff: Duration::from_millis(500), multiplier: 2.0 }
+    }
+}
+
+impl RetryPolicy {
+    pub fn exponential(max_retries: u32, initial_backoff: Duration) -> Self {
+        Self { max_retries, initial_backoff, ..Self::default() }
+    }
+
+    /// 再試行可能な失敗の間は待ち時間を伸ばしながら attempt を繰り返す
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> Result<T, AiError>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<T, AiError>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut retries = 0;
+        loop {
+            match attempt().await {
+                Err(e) if e.is_retryable() && retries < self.max_retries => {
+                    retries += 1;
+                    log::warn!("[AI] {} (retry {}/{} in {:?})", e, retries, self.max_retries, backoff);
+                    crate::utils::sleep(backoff.as_millis() as u64).await;
+                    backoff = backoff.mul_f64(self.multiplier);
+                }
+                result => return result,
+            }
         }
     }
 }
@@ -26,6 +156,7 @@ pub struct AiRuntime {
     api_key: Option<String>,
     model: String,
     enabled: bool,
+    retry: RetryPolicy,
 }
 
 impl AiRuntime {
@@ -44,23 +175,36 @@ impl AiRuntime {
             enabled,
             api_key,
             model: "gemini-2.0-flash".to_string(),
+            retry: RetryPolicy::default(),
         }
     }
 
+    /// 一時的な失敗を再試行する
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
 
     /// 要約を実行
     pub async fn summarize(&self, text: &str) -> Result<String, AiError> {
+        self.summarize_with_length(text, None).await
+    }
+
+    /// 長さを指定して要約を実行
+    pub async fn summarize_with_length(&self, text: &str, length: Option<&str>) -> Result<String, AiError> {
         if !self.enabled {
             // APIキーがない場合はプレースホルダーを返す
             return Ok(format!("[要約: {}...]", &text.chars().take(20).collect::<String>()));
         }
 
+        let length_hint = length.map(|l| format!("長さは「{}」にしてください。", l)).unwrap_or_default();
         let prompt = format!(
-            "以下のテキストを簡潔に要約してください。要約のみを回答し、他の説明は不要です。\n\n{}",
-            text
+            "以下のテキストを簡潔に要約してください。{}要約のみを回答し、他の説明は不要です。\n\n{}",
+            length_hint, text
         );
         
         self.call_gemini(&prompt).await
@@ -94,6 +238,17 @@ impl AiRuntime {
         self.call_gemini(&prompt).await
     }
 
+    /// 画像を生成 (想像する)
+    /// 画像の URL またはデータ URL を返す
+    pub async fn generate_image(&self, prompt: &str) -> Result<String, AiError> {
+        if !self.enabled {
+            return Ok(format!("[画像: {}]", &prompt.chars().take(20).collect::<String>()));
+        }
+
+        let prompt = format!("次の内容の画像を1枚生成してください。\n\n{}", prompt);
+        self.call_gemini_image(&prompt).await
+    }
+
     /// Gemini APIを呼び出し
     /// Gemini APIを呼び出し (Native)
     #[cfg(not(target_arch = "wasm32"))]
@@ -120,11 +275,12 @@ impl AiRuntime {
             .json(&body)
             .send()
             .await
-            .map_err(|e| AiError::RequestFailed(e.to_string()))?;
+            .map_err(|e| AiError::Transient(e.to_string()))?;
 
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
-            return Err(AiError::RequestFailed(error_text));
+            return Err(AiError::from_status(status.as_u16(), error_text));
         }
 
         let json: serde_json::Value = response
@@ -140,6 +296,61 @@ impl AiRuntime {
         Ok(text.trim().to_string())
     }
 
+    /// Gemini の画像生成モデルを呼び出し、データ URL を返す (Native)
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn call_gemini_image(&self, prompt: &str) -> Result<String, AiError> {
+        let api_key = self.api_key.as_ref().ok_or(AiError::ApiKeyNotSet)?;
+
+        let url = format!(
+            "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent?key={}",
+            IMAGE_MODEL, api_key
+        );
+
+        let body = serde_json::json!({
+            "contents": [{
+                "parts": [{
+                    "text": prompt
+                }]
+            }],
+            "generationConfig": {
+                "responseModalities": ["TEXT", "IMAGE"]
+            }
+        });
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| AiError::Transient(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(AiError::from_status(status.as_u16(), error_text));
+        }
+
+        let json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| AiError::ParseError(e.to_string()))?;
+
+        // 画像は parts のいずれかに inlineData として入っている
+        let parts = json["candidates"][0]["content"]["parts"]
+            .as_array()
+            .ok_or_else(|| AiError::ParseError("No parts in response".to_string()))?;
+        parts
+            .iter()
+            .find_map(|part| {
+                let data = part["inlineData"]["data"].as_str()?;
+                let mime = part["inlineData"]["mimeType"].as_str().unwrap_or("image/png");
+                Some(format!("data:{};base64,{}", mime, data))
+            })
+            .ok_or_else(|| AiError::ParseError("No image in response".to_string()))
+    }
+
     /// Gemini APIを呼び出し (Wasm: Proxy to /api/gemini)
     #[cfg(target_arch = "wasm32")]
     async fn call_gemini(&self, prompt: &str) -> Result<String, AiError> {
@@ -161,13 +372,14 @@ impl AiRuntime {
             .json(&body)
             .send()
             .await
-            .map_err(|e| AiError::RequestFailed(e.to_string()))?;
+            .map_err(|e| AiError::Transient(e.to_string()))?;
 
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
              // For text() on wasm, we might need await? yes.
              // But on some error response it might be text.
             let error_text = response.text().await.unwrap_or_default();
-            return Err(AiError::RequestFailed(error_text));
+            return Err(AiError::from_status(status.as_u16(), error_text));
         }
 
         let json: serde_json::Value = response
@@ -183,20 +395,98 @@ impl AiRuntime {
         Ok(text.trim().to_string())
     }
 
+    /// 画像生成 (Wasm: Proxy to /api/gemini)
+    /// Proxy expected response: { "image": "<URL or data URL>" }
+    #[cfg(target_arch = "wasm32")]
+    async fn call_gemini_image(&self, prompt: &str) -> Result<String, AiError> {
+        let origin = web_sys::window()
+            .and_then(|w| w.location().origin().ok())
+            .unwrap_or_else(|| "http://localhost:3003".to_string());
+        let url = format!("{}/api/gemini", origin);
+
+        let body = serde_json::json!({
+            "prompt": prompt,
+            "model": IMAGE_MODEL,
+            "modality": "image"
+        });
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(url)
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| AiError::Transient(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(AiError::from_status(status.as_u16(), error_text));
+        }
+
+        let json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| AiError::ParseError(e.to_string()))?;
+
+        json["image"]
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| AiError::ParseError("No image in response".to_string()))
+    }
+
     /// AI動詞を実行
-    pub async fn execute_verb(&self, verb: &str, input: &str, option: Option<String>) -> Result<String, AiError> {
+    pub async fn execute_verb(&self, verb: &str, input: &str, options: &AiOptions) -> Result<String, AiError> {
+        self.retry.run(|| self.execute_verb_once(verb, input, options)).await
+    }
+
+    async fn execute_verb_once(&self, verb: &str, input: &str, options: &AiOptions) -> Result<String, AiError> {
         match verb {
-            "要約する" | "summarize" => self.summarize(input).await,
+            "要約する" | "summarize" => self.summarize_with_length(input, options.length.as_deref()).await,
             "翻訳する" | "translate" => {
-                 let target = option.unwrap_or_else(|| "英語".to_string());
-                 self.translate(input, &target).await
+                 let target = options.language.as_deref().unwrap_or("英語");
+                 self.translate(input, target).await
             },
-            "想像する" | "imagine" => self.generate_story(input).await,
+            // 画像は generate_image だけが扱う (文字列の結果を返さない)
+            v if is_image_verb(v) => Err(AiError::RequestFailed(format!(
+                "AI verb '{}' produces an image; use generate_image", v
+            ))),
             _ => Err(AiError::RequestFailed(format!("Unknown AI verb: {}", verb))),
         }
     }
 }
 
+/// AI動詞の実行バックエンド
+/// インタプリタは生成済みのバックエンドを Arc で共有し、呼び出しごとに再初期化しない
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait AiBackend: Send + Sync {
+    async fn execute_verb(&self, verb: &str, input: &str, options: &AiOptions) -> Result<String, AiError>;
+
+    /// 画像を生成し、その URL (データ URL 可) を返す
+    async fn generate_image(&self, _prompt: &str, _options: &AiOptions) -> Result<String, AiError> {
+        Err(AiError::RequestFailed("Image generation is not supported by this backend".to_string()))
+    }
+}
+
+/// 画像を生成する AI動詞か (結果を Value::Image として扱う)
+pub fn is_image_verb(verb: &str) -> bool {
+    matches!(verb, "想像する" | "imagine")
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl AiBackend for AiRuntime {
+    async fn execute_verb(&self, verb: &str, input: &str, options: &AiOptions) -> Result<String, AiError> {
+        AiRuntime::execute_verb(self, verb, input, options).await
+    }
+
+    async fn generate_image(&self, prompt: &str, _options: &AiOptions) -> Result<String, AiError> {
+        self.retry.run(|| AiRuntime::generate_image(self, prompt)).await
+    }
+}
+
 impl Default for AiRuntime {
     fn default() -> Self {
         Self::new()
@@ -232,10 +522,70 @@ mod tests {
             api_key: None,
             model: "gemini-2.0-flash".to_string(),
             enabled: false,
+            retry: RetryPolicy::default(),
         };
         
         let result = runtime.summarize("これはテストテキストです").await;
         assert!(result.is_ok());
         assert!(result.unwrap().contains("[要約:"));
     }
+
+    #[tokio::test]
+    async fn test_image_verb_is_not_a_text_verb() {
+        let runtime = AiRuntime {
+            api_key: None,
+            model: "gemini-2.0-flash".to_string(),
+            enabled: false,
+            retry: RetryPolicy::default(),
+        };
+
+        let err = runtime.execute_verb("想像する", "猫", &AiOptions::default()).await.unwrap_err();
+        assert!(err.to_string().contains("generate_image"));
+        assert!(runtime.generate_image("猫").await.unwrap().contains("[画像:"));
+    }
+
+    /// 指定回数だけ一時的な失敗を返すバックエンド
+    struct FlakyBackend {
+        failures: u32,
+        calls: std::sync::atomic::AtomicU32,
+    }
+
+    #[async_trait]
+    impl AiBackend for FlakyBackend {
+        async fn execute_verb(&self, verb: &str, input: &str, _options: &AiOptions) -> Result<String, AiError> {
+            let call = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            if call < self.failures {
+                Err(AiError::from_status(503, "unavailable".to_string()))
+            } else {
+                Ok(format!("{}:{}", verb, input))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_recovers_from_transient_failures() {
+        let backend = FlakyBackend { failures: 2, calls: std::sync::atomic::AtomicU32::new(0) };
+        let options = AiOptions::default();
+        let call = || backend.execute_verb("要約する", "文", &options);
+
+        // 既定では再試行しない
+        assert!(matches!(RetryPolicy::default().run(call).await, Err(AiError::Transient(_))));
+
+        backend.calls.store(0, std::sync::atomic::Ordering::SeqCst);
+        let policy = RetryPolicy::exponential(3, Duration::from_millis(1));
+        assert_eq!(policy.run(call).await.unwrap(), "要約する:文");
+        assert_eq!(backend.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        // 恒久的な失敗は再試行しない
+        assert!(!AiError::from_status(400, "bad request".to_string()).is_retryable());
+    }
+
+    #[test]
+    fn test_timeout_option_parsing() {
+        let options = AiOptions::from_fields(vec![("タイムアウト".to_string(), "500ミリ秒".to_string())]);
+        assert_eq!(options.effective_timeout(), Duration::from_millis(500));
+        assert_eq!(parse_timeout("2秒"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_timeout("-1"), None);
+        assert_eq!(AiOptions::default().effective_timeout(), DEFAULT_AI_TIMEOUT);
+    }
 }
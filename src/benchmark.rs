@@ -0,0 +1,328 @@
+//! AGN Benchmark - バックエンド比較ベンチマーク
+//! 同じプログラムをインタプリタ・ネイティブ・Wasmで実行し、実行時間を比較する
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::bridge::std_bridge::{StdP2PBridge, StdUIManager};
+use crate::compiler::{Compiler, Target};
+use crate::interpreter::Interpreter;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+/// 実行バックエンド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Interpreter,
+    Native,
+    Wasm,
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Interpreter => "Interpreter",
+            Backend::Native => "Native",
+            Backend::Wasm => "Wasm",
+        }
+    }
+}
+
+/// 複数回実行した実行時間の統計
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimingStats {
+    pub mean: Duration,
+    pub median: Duration,
+    pub stddev: Duration,
+    pub samples: usize,
+}
+
+impl TimingStats {
+    /// 計測値から統計を計算 (空なら None)
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let secs: Vec<f64> = samples.iter().map(|d| d.as_secs_f64()).collect();
+        let n = secs.len() as f64;
+        let mean = secs.iter().sum::<f64>() / n;
+        let variance = secs.iter().map(|s| (s - mean).powi(2)).sum::<f64>() / n;
+
+        let mut sorted = secs.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let mid = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        };
+
+        Some(Self {
+            mean: Duration::from_secs_f64(mean),
+            median: Duration::from_secs_f64(median),
+            stddev: Duration::from_secs_f64(variance.sqrt()),
+            samples: samples.len(),
+        })
+    }
+}
+
+/// バックエンドごとの計測結果
+#[derive(Debug, Clone)]
+pub enum BackendOutcome {
+    /// 実行時間の統計
+    Timed(TimingStats),
+    /// ビルドまたは実行できなかった理由
+    Skipped(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct BackendResult {
+    pub backend: Backend,
+    pub outcome: BackendOutcome,
+}
+
+/// ベンチマーク結果
+#[derive(Debug, Clone, Default)]
+pub struct BenchmarkResult {
+    pub entries: Vec<BackendResult>,
+}
+
+impl BenchmarkResult {
+    /// 計測できたバックエンドの統計
+    pub fn stats(&self, backend: Backend) -> Option<&TimingStats> {
+        self.entries.iter().find(|e| e.backend == backend).and_then(|e| match &e.outcome {
+            BackendOutcome::Timed(stats) => Some(stats),
+            BackendOutcome::Skipped(_) => None,
+        })
+    }
+
+    /// 計測できたバックエンドの平均実行時間
+    pub fn duration(&self, backend: Backend) -> Option<Duration> {
+        self.stats(backend).map(|s| s.mean)
+    }
+
+    /// 計測できたバックエンド一覧
+    pub fn available_backends(&self) -> Vec<Backend> {
+        self.entries
+            .iter()
+            .filter(|e| matches!(e.outcome, BackendOutcome::Timed(_)))
+            .map(|e| e.backend)
+            .collect()
+    }
+
+    /// インタプリタ比の速度倍率 (2.0 = インタプリタの2倍速い)
+    pub fn speedup(&self, backend: Backend) -> Option<f64> {
+        let base = self.duration(Backend::Interpreter)?;
+        let target = self.duration(backend)?;
+        if target.is_zero() {
+            return None;
+        }
+        Some(base.as_secs_f64() / target.as_secs_f64())
+    }
+
+    /// 結果を表形式の文字列にする
+    pub fn to_table(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!(
+            "  {:<12} {:>14} {:>14} {:>14} {:>10}\n",
+            "Backend", "Mean", "Median", "Stddev", "Speedup"
+        ));
+        for entry in &self.entries {
+            match &entry.outcome {
+                BackendOutcome::Timed(stats) => {
+                    let speedup = self
+                        .speedup(entry.backend)
+                        .map(|s| format!("{:.2}x", s))
+                        .unwrap_or_else(|| "-".to_string());
+                    out.push_str(&format!(
+                        "  {:<12} {:>14} {:>14} {:>14} {:>10}\n",
+                        entry.backend.name(),
+                        format!("{:?}", stats.mean),
+                        format!("{:?}", stats.median),
+                        format!("{:?}", stats.stddev),
+                        speedup
+                    ));
+                }
+                BackendOutcome::Skipped(reason) => {
+                    out.push_str(&format!("  {:<12} skipped ({})\n", entry.backend.name(), reason));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// ベンチマーク実行器
+pub struct Benchmark {
+    output_dir: PathBuf,
+    output_name: String,
+    /// 計測回数 (別途ウォームアップを1回行う)
+    iterations: usize,
+}
+
+impl Benchmark {
+    pub fn new<P: AsRef<Path>>(output_dir: P, output_name: &str) -> Self {
+        Self {
+            output_dir: output_dir.as_ref().to_path_buf(),
+            output_name: output_name.to_string(),
+            iterations: 1,
+        }
+    }
+
+    pub fn set_iterations(&mut self, iterations: usize) {
+        self.iterations = iterations.max(1);
+    }
+
+    /// ウォームアップ後に iterations 回計測する
+    fn measure<F>(&self, mut run_once: F) -> Result<TimingStats, String>
+    where
+        F: FnMut() -> Result<Duration, String>,
+    {
+        run_once()?; // warm-up
+        let mut samples = Vec::with_capacity(self.iterations);
+        for _ in 0..self.iterations {
+            samples.push(run_once()?);
+        }
+        TimingStats::from_samples(&samples).ok_or_else(|| "no samples".to_string())
+    }
+
+    /// 全バックエンドで計測する (ビルドできないバックエンドはスキップ)
+    pub async fn run(&self, source: &str) -> BenchmarkResult {
+        let entries = vec![
+            BackendResult { backend: Backend::Interpreter, outcome: self.time_interpreter(source).await },
+            BackendResult { backend: Backend::Native, outcome: self.time_native(source) },
+            BackendResult { backend: Backend::Wasm, outcome: self.time_wasm(source) },
+        ];
+        BenchmarkResult { entries }
+    }
+
+    async fn time_interpreter(&self, source: &str) -> BackendOutcome {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize();
+        let program = match Parser::new(tokens).parse() {
+            Ok(program) => program,
+            Err(e) => return BackendOutcome::Skipped(format!("parse error: {}", e)),
+        };
+
+        // 非同期実行のため measure は使わずに同じ手順を踏む
+        let mut samples = Vec::with_capacity(self.iterations);
+        for i in 0..=self.iterations {
+            let interpreter = Interpreter::with_bridges(Arc::new(StdP2PBridge), Arc::new(StdUIManager));
+            let start = Instant::now();
+            interpreter.execute(&program).await;
+            if i > 0 {
+                samples.push(start.elapsed()); // 1回目はウォームアップ
+            }
+        }
+        match TimingStats::from_samples(&samples) {
+            Some(stats) => BackendOutcome::Timed(stats),
+            None => BackendOutcome::Skipped("no samples".to_string()),
+        }
+    }
+
+    fn time_native(&self, source: &str) -> BackendOutcome {
+        let compiler = Compiler::new(&self.output_dir);
+        let result = match compiler.compile(source, &self.output_name) {
+            Ok(result) => result,
+            Err(e) => return BackendOutcome::Skipped(e.to_string()),
+        };
+
+        let outcome = self.measure(|| {
+            let start = Instant::now();
+            result.run().map_err(|e| format!("execution error: {}", e))?;
+            Ok(start.elapsed())
+        });
+        match outcome {
+            Ok(stats) => BackendOutcome::Timed(stats),
+            Err(e) => BackendOutcome::Skipped(e),
+        }
+    }
+
+    fn time_wasm(&self, source: &str) -> BackendOutcome {
+        // Wasmランタイムがなければビルドせずにスキップ
+        if Command::new("wasmtime").arg("--version").output().is_err() {
+            return BackendOutcome::Skipped("wasmtime not found in PATH".to_string());
+        }
+
+        let mut compiler = Compiler::new(&self.output_dir);
+        compiler.set_target(Target::Wasm);
+        let result = match compiler.compile(source, &self.output_name) {
+            Ok(result) => result,
+            Err(e) => return BackendOutcome::Skipped(e.to_string()),
+        };
+
+        let outcome = self.measure(|| {
+            let start = Instant::now();
+            match Command::new("wasmtime").arg("run").arg(&result.binary_path).output() {
+                Ok(output) if output.status.success() => Ok(start.elapsed()),
+                Ok(output) => Err(format!(
+                    "wasmtime failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )),
+                Err(e) => Err(format!("wasmtime error: {}", e)),
+            }
+        });
+        match outcome {
+            Ok(stats) => BackendOutcome::Timed(stats),
+            Err(e) => BackendOutcome::Skipped(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[tokio::test]
+    async fn test_benchmark_has_entry_per_backend() {
+        let output_dir = temp_dir().join("agn_bench_test");
+        let mut benchmark = Benchmark::new(&output_dir, "bench");
+        benchmark.set_iterations(3);
+        let result = benchmark.run("X は 10 だ\nX に 5 を 足す").await;
+
+        let backends: Vec<Backend> = result.entries.iter().map(|e| e.backend).collect();
+        assert_eq!(backends, vec![Backend::Interpreter, Backend::Native, Backend::Wasm]);
+        // インタプリタは常に計測できる
+        assert!(result.available_backends().contains(&Backend::Interpreter));
+        assert_eq!(result.stats(Backend::Interpreter).unwrap().samples, 3);
+        assert!(result.to_table().contains("Interpreter"));
+
+        let _ = std::fs::remove_dir_all(&output_dir);
+    }
+
+    fn timed(ms: u64) -> BackendOutcome {
+        BackendOutcome::Timed(TimingStats::from_samples(&[Duration::from_millis(ms)]).unwrap())
+    }
+
+    #[test]
+    fn test_timing_stats() {
+        let samples: Vec<Duration> = [10, 20, 30, 40].iter().map(|ms| Duration::from_millis(*ms)).collect();
+        let stats = TimingStats::from_samples(&samples).unwrap();
+        assert_eq!(stats.samples, 4);
+        assert_eq!(stats.mean, Duration::from_millis(25));
+        assert_eq!(stats.median, Duration::from_millis(25));
+        // 母標準偏差: sqrt(125) ≒ 11.18ms
+        assert!((stats.stddev.as_secs_f64() - 0.011180).abs() < 1e-5);
+
+        let odd: Vec<Duration> = [5, 1, 3].iter().map(|ms| Duration::from_millis(*ms)).collect();
+        assert_eq!(TimingStats::from_samples(&odd).unwrap().median, Duration::from_millis(3));
+        assert!(TimingStats::from_samples(&[]).is_none());
+    }
+
+    #[test]
+    fn test_speedup_relative_to_interpreter() {
+        let result = BenchmarkResult {
+            entries: vec![
+                BackendResult { backend: Backend::Interpreter, outcome: timed(100) },
+                BackendResult { backend: Backend::Native, outcome: timed(25) },
+                BackendResult { backend: Backend::Wasm, outcome: BackendOutcome::Skipped("no runtime".to_string()) },
+            ],
+        };
+        assert_eq!(result.speedup(Backend::Native), Some(4.0));
+        assert_eq!(result.speedup(Backend::Wasm), None);
+        assert_eq!(result.available_backends(), vec![Backend::Interpreter, Backend::Native]);
+    }
+}
@@ -1,31 +1,52 @@
 use crate::bridge::{P2PBridge, UIManager};
 use crate::p2p::{DetectedPeer, SocialTokuEvent, Relationship};
+use crate::parser::SpatialFilter;
 use crate::interpreter::RuntimeMessage;
 use async_trait::async_trait;
 use std::sync::Arc;
 
 pub struct MockP2PBridge {
     pub toku_scores: std::sync::Mutex<std::collections::HashMap<String, u32>>,
+    /// 初期値を除いて得た徳の累計
+    pub toku_earned: std::sync::Mutex<std::collections::HashMap<String, u32>>,
     pub bonds: std::sync::Mutex<std::collections::HashMap<(String, String), Relationship>>,
     pub events: std::sync::Mutex<std::collections::HashMap<String, SocialTokuEvent>>,
+    /// 発信されたビーコンの記録 (種類, 発信時間)
+    pub broadcasts: std::sync::Mutex<Vec<(String, Option<u64>)>>,
+    /// 検出済みとして扱うピア
+    pub peers: std::sync::Mutex<Vec<DetectedPeer>>,
 }
 
 impl MockP2PBridge {
     pub fn new() -> Self {
         Self {
             toku_scores: std::sync::Mutex::new(std::collections::HashMap::new()),
+            toku_earned: std::sync::Mutex::new(std::collections::HashMap::new()),
             bonds: std::sync::Mutex::new(std::collections::HashMap::new()),
             events: std::sync::Mutex::new(std::collections::HashMap::new()),
+            broadcasts: std::sync::Mutex::new(Vec::new()),
+            peers: std::sync::Mutex::new(Vec::new()),
         }
     }
 }
 
 #[async_trait]
 impl P2PBridge for MockP2PBridge {
-    async fn broadcast_beacon(&self, _beacon_type: &str, _duration: Option<u64>) {}
-    async fn get_nearby_peers(&self, _max_distance: f64) -> Vec<DetectedPeer> { Vec::new() }
-    async fn spatial_search(&self, _max_distance: f64, _filters: &[(String, String)]) -> Vec<DetectedPeer> { Vec::new() }
+    async fn broadcast_beacon(&self, beacon_type: &str, duration: Option<u64>) {
+        self.broadcasts.lock().unwrap().push((beacon_type.to_string(), duration));
+    }
+    async fn get_nearby_peers(&self, max_distance: f64) -> Vec<DetectedPeer> {
+        self.peers.lock().unwrap().iter().filter(|p| p.estimated_distance <= max_distance).cloned().collect()
+    }
+    async fn spatial_search(&self, max_distance: f64, filters: &[SpatialFilter]) -> Vec<DetectedPeer> {
+        let mut peers = self.get_nearby_peers(max_distance).await;
+        peers.retain(|p| filters.iter().all(|f| crate::p2p::peer_matches_filter(p, f)));
+        peers
+    }
     async fn notify_peer(&self, _peer_id: &str, _message: &str) -> Result<(), String> { Ok(()) }
+    async fn find_peer(&self, peer_id: &str) -> Option<DetectedPeer> {
+        self.peers.lock().unwrap().iter().find(|p| p.peer_id == peer_id).cloned()
+    }
     
     fn get_toku(&self, user_id: &str) -> u32 {
         *self.toku_scores.lock().unwrap().get(user_id).unwrap_or(&100)
@@ -34,12 +55,36 @@ impl P2PBridge for MockP2PBridge {
         let mut scores = self.toku_scores.lock().unwrap();
         let score = scores.entry(user_id.to_string()).or_insert(100);
         *score += amount;
+        *self.toku_earned.lock().unwrap().entry(user_id.to_string()).or_insert(0) += amount;
     }
     fn subtract_toku(&self, user_id: &str, amount: u32) {
         let mut scores = self.toku_scores.lock().unwrap();
         let score = scores.entry(user_id.to_string()).or_insert(100);
         *score = score.saturating_sub(amount);
     }
+    fn transfer_toku(&self, from: &str, to: &str, amount: u32) -> Result<bool, String> {
+        {
+            let mut scores = self.toku_scores.lock().unwrap();
+            let sender = *scores.get(from).unwrap_or(&100);
+            if sender < amount {
+                return Err(format!("徳が足りません: {} の徳 {} < {}", from, sender, amount));
+            }
+            scores.insert(from.to_string(), sender - amount);
+            *scores.entry(to.to_string()).or_insert(100) += amount;
+            *self.toku_earned.lock().unwrap().entry(to.to_string()).or_insert(0) += amount;
+        }
+        Ok(self.deepen_bond(from, to, 1))
+    }
+    fn toku_earned(&self, user_id: &str) -> u32 {
+        100 + self.toku_earned.lock().unwrap().get(user_id).copied().unwrap_or(0)
+    }
+    fn top_toku(&self, n: usize) -> Vec<(String, u32)> {
+        let scores = self.toku_scores.lock().unwrap();
+        let mut ranking: Vec<(String, u32)> = scores.iter().map(|(id, s)| (id.clone(), *s)).collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranking.truncate(n);
+        ranking
+    }
     
     fn get_bond(&self, from: &str, to: &str) -> Relationship {
         self.bonds.lock().unwrap().get(&(from.to_string(), to.to_string())).cloned().unwrap_or_else(|| {
@@ -53,8 +98,9 @@ impl P2PBridge for MockP2PBridge {
             }
         })
     }
-    fn deepen_bond(&self, from: &str, to: &str, amount: u32) {
+    fn deepen_bond(&self, from: &str, to: &str, amount: u32) -> bool {
         let mut bonds = self.bonds.lock().unwrap();
+        let first_met = !bonds.contains_key(&(from.to_string(), to.to_string()));
         let bond = bonds.entry((from.to_string(), to.to_string())).or_insert(Relationship {
             strength: 10,
             level: 1,
@@ -64,11 +110,20 @@ impl P2PBridge for MockP2PBridge {
             tags: Vec::new(),
         });
         bond.strength += amount;
+        first_met
     }
     fn has_bond(&self, from: &str, to: &str) -> bool {
         self.bonds.lock().unwrap().contains_key(&(from.to_string(), to.to_string()))
     }
     fn set_bond_status(&self, _from: &str, _to: &str, _status: &str) {}
+    fn bonds_for(&self, user_id: &str) -> Vec<(String, Relationship)> {
+        let mut bonds: Vec<(String, Relationship)> = self.bonds.lock().unwrap().iter()
+            .filter(|((from, _), _)| from == user_id)
+            .map(|((_, to), rel)| (to.clone(), rel.clone()))
+            .collect();
+        bonds.sort_by(|a, b| a.0.cmp(&b.0));
+        bonds
+    }
     
     async fn get_all_feed_events(&self) -> Vec<SocialTokuEvent> {
         self.events.lock().unwrap().values().cloned().collect()
@@ -87,4 +142,43 @@ impl UIManager for MockUIManager {
     fn update_feed(&self, _events: Vec<SocialTokuEvent>) {}
     fn notify(&self, _message: &str) {}
     fn send_runtime_message(&self, _msg: RuntimeMessage) {}
+    fn print(&self, _message: &str) {}
+    fn error(&self, _message: &str) {}
+    fn log(&self, _channel: &str, _message: &str) {}
+}
+
+/// 出力を記録するUIManager (テスト・埋め込み用)
+#[derive(Default)]
+pub struct CapturingUIManager {
+    pub outputs: std::sync::Mutex<Vec<String>>,
+    pub errors: std::sync::Mutex<Vec<String>>,
+    pub notifications: std::sync::Mutex<Vec<String>>,
+    pub runtime_messages: std::sync::Mutex<Vec<RuntimeMessage>>,
+    /// (チャンネル, 内容)
+    pub logs: std::sync::Mutex<Vec<(String, String)>>,
+}
+
+impl CapturingUIManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl UIManager for CapturingUIManager {
+    fn update_feed(&self, _events: Vec<SocialTokuEvent>) {}
+    fn notify(&self, message: &str) {
+        self.notifications.lock().unwrap().push(message.to_string());
+    }
+    fn send_runtime_message(&self, msg: RuntimeMessage) {
+        self.runtime_messages.lock().unwrap().push(msg);
+    }
+    fn print(&self, message: &str) {
+        self.outputs.lock().unwrap().push(message.to_string());
+    }
+    fn error(&self, message: &str) {
+        self.errors.lock().unwrap().push(message.to_string());
+    }
+    fn log(&self, channel: &str, message: &str) {
+        self.logs.lock().unwrap().push((channel.to_string(), message.to_string()));
+    }
 }
@@ -1,4 +1,5 @@
 use crate::p2p::{DetectedPeer, Relationship, SocialTokuEvent};
+use crate::parser::SpatialFilter;
 use async_trait::async_trait;
 
 #[async_trait]
@@ -6,19 +7,31 @@ pub trait P2PBridge: Send + Sync {
     // Beacon / Search
     async fn broadcast_beacon(&self, beacon_type: &str, duration: Option<u64>);
     async fn get_nearby_peers(&self, max_distance: f64) -> Vec<DetectedPeer>;
-    async fn spatial_search(&self, max_distance: f64, filters: &[(String, String)]) -> Vec<DetectedPeer>;
+    /// 空間検索 (フィルターの比較値は評価済みのリテラル)
+    async fn spatial_search(&self, max_distance: f64, filters: &[SpatialFilter]) -> Vec<DetectedPeer>;
     async fn notify_peer(&self, peer_id: &str, message: &str) -> Result<(), String>;
+    /// 検出済みのピアをIDで取得
+    async fn find_peer(&self, peer_id: &str) -> Option<DetectedPeer>;
     
     // Toku Management
     fn get_toku(&self, user_id: &str) -> u32;
     fn add_toku(&self, user_id: &str, amount: u32);
     fn subtract_toku(&self, user_id: &str, amount: u32);
+    fn top_toku(&self, n: usize) -> Vec<(String, u32)>;
+    /// 徳を送る (一括で反映し、絆を深める)。送り手の徳が足りなければ何もせずエラー
+    /// この送付で初めて絆ができた (初対面) ときは Ok(true)
+    fn transfer_toku(&self, from: &str, to: &str, amount: u32) -> Result<bool, String>;
+    /// これまでに得た徳の累計 (使っても減らない)
+    fn toku_earned(&self, user_id: &str) -> u32;
     
     // Bond Management
     fn get_bond(&self, from: &str, to: &str) -> Relationship;
-    fn deepen_bond(&self, from: &str, to: &str, amount: u32);
+    /// 絆を深める (なければ作る)。初めて絆ができた (初対面) ときは true
+    fn deepen_bond(&self, from: &str, to: &str, amount: u32) -> bool;
     fn has_bond(&self, from: &str, to: &str) -> bool;
     fn set_bond_status(&self, from: &str, to: &str, status: &str);
+    /// ユーザーが起点の絆一覧 (相手のID, 関係性)
+    fn bonds_for(&self, user_id: &str) -> Vec<(String, Relationship)>;
     
     // Social Feed
     async fn get_all_feed_events(&self) -> Vec<SocialTokuEvent>;
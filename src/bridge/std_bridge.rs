@@ -1,9 +1,12 @@
 use crate::bridge::{P2PBridge, UIManager};
 use crate::p2p::{DetectedPeer, Relationship, SocialTokuEvent};
+use crate::parser::SpatialFilter;
 use crate::interpreter::RuntimeMessage;
 use async_trait::async_trait;
 use std::sync::Arc;
 
+/// グローバルの TOKU_MANAGER / P2P_MANAGER に委譲するブリッジ
+/// (WASM API の agn_* / eeyo_* 関数と同じ状態を共有する)
 pub struct StdP2PBridge;
 
 #[async_trait]
@@ -14,12 +17,15 @@ impl P2PBridge for StdP2PBridge {
     async fn get_nearby_peers(&self, max_distance: f64) -> Vec<DetectedPeer> {
         crate::p2p::agn_spatial_search(max_distance, &[]).await
     }
-    async fn spatial_search(&self, max_distance: f64, filters: &[(String, String)]) -> Vec<DetectedPeer> {
+    async fn spatial_search(&self, max_distance: f64, filters: &[SpatialFilter]) -> Vec<DetectedPeer> {
         crate::p2p::agn_spatial_search(max_distance, filters).await
     }
     async fn notify_peer(&self, peer_id: &str, message: &str) -> Result<(), String> {
         crate::p2p::agn_notify_peer(peer_id, message).await
     }
+    async fn find_peer(&self, peer_id: &str) -> Option<DetectedPeer> {
+        crate::p2p::agn_find_peer(peer_id)
+    }
     
     // Toku
     fn get_toku(&self, user_id: &str) -> u32 {
@@ -31,13 +37,22 @@ impl P2PBridge for StdP2PBridge {
     fn subtract_toku(&self, user_id: &str, amount: u32) {
         crate::p2p::agn_subtract_toku(user_id, amount);
     }
+    fn top_toku(&self, n: usize) -> Vec<(String, u32)> {
+        crate::p2p::agn_top_toku(n)
+    }
+    fn transfer_toku(&self, from: &str, to: &str, amount: u32) -> Result<bool, String> {
+        crate::p2p::agn_transfer_toku(from, to, amount)
+    }
+    fn toku_earned(&self, user_id: &str) -> u32 {
+        crate::p2p::agn_toku_earned(user_id)
+    }
     
     // Bond
     fn get_bond(&self, from: &str, to: &str) -> Relationship {
         crate::p2p::agn_get_bond(from, to)
     }
-    fn deepen_bond(&self, from: &str, to: &str, amount: u32) {
-        crate::p2p::agn_deepen_bond(from, to, amount);
+    fn deepen_bond(&self, from: &str, to: &str, amount: u32) -> bool {
+        crate::p2p::agn_deepen_bond(from, to, amount)
     }
     fn has_bond(&self, from: &str, to: &str) -> bool {
         crate::p2p::agn_has_bond(from, to)
@@ -45,6 +60,9 @@ impl P2PBridge for StdP2PBridge {
     fn set_bond_status(&self, _from: &str, _to: &str, _status: &str) {
         // Future: crate::p2p::agn_set_bond_status(from, to, status);
     }
+    fn bonds_for(&self, user_id: &str) -> Vec<(String, Relationship)> {
+        crate::p2p::agn_bonds_for(user_id)
+    }
     
     // Feed
     async fn get_all_feed_events(&self) -> Vec<SocialTokuEvent> {
@@ -71,8 +89,54 @@ impl UIManager for StdUIManager {
         self.send_runtime_message(RuntimeMessage::String(message.to_string()));
     }
     fn send_runtime_message(&self, msg: RuntimeMessage) {
+        // 位置付きの表示はコンソールにも出す
+        if let RuntimeMessage::Display(text, _) = &msg {
+            println!("{}", text);
+        }
         if let Some(sender) = crate::interpreter::SCREEN_CHANNEL.lock().unwrap().as_ref() {
             sender.send(msg).ok();
         }
     }
+    fn print(&self, message: &str) {
+        println!("{}", message);
+        // ネイティブウィンドウが接続されていれば画面にも出す
+        self.send_runtime_message(RuntimeMessage::String(message.to_string()));
+    }
+    fn error(&self, message: &str) {
+        eprintln!("{}", message);
+    }
+    fn log(&self, channel: &str, message: &str) {
+        // 診断出力はユーザー向けの出力と混ざらないよう標準エラーへ
+        eprintln!("[{}] {}", channel, message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_bridge_toku_visible_via_globals() {
+        let bridge = StdP2PBridge;
+        let before = crate::p2p::agn_get_toku("std_bridge_user");
+        bridge.add_toku("std_bridge_user", 15);
+        assert_eq!(crate::p2p::agn_get_toku("std_bridge_user"), before + 15);
+
+        bridge.deepen_bond("std_bridge_user", "std_bridge_peer", 5);
+        assert!(crate::p2p::agn_has_bond("std_bridge_user", "std_bridge_peer"));
+    }
+
+    #[tokio::test]
+    async fn test_script_toku_visible_via_globals() {
+        let interpreter = Interpreter::with_bridges(Arc::new(StdP2PBridge), Arc::new(StdUIManager));
+        let code = "Member は \"StdScriptUser\" だ\nMember.徳 に 20 を 増やす";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let before = crate::p2p::agn_get_toku("StdScriptUser");
+        interpreter.execute(&program).await;
+        assert_eq!(crate::p2p::agn_get_toku("StdScriptUser"), before + 20);
+    }
 }
@@ -5,4 +5,10 @@ pub trait UIManager: Send + Sync {
     fn update_feed(&self, events: Vec<SocialTokuEvent>);
     fn notify(&self, message: &str);
     fn send_runtime_message(&self, msg: RuntimeMessage);
+    /// ユーザー向けの出力 (表示する・画面出力・システムメッセージ)
+    fn print(&self, message: &str);
+    /// ユーザー向けのエラー出力
+    fn error(&self, message: &str);
+    /// 診断用の記録 (記録する)。channel でホスト側が出力先を振り分ける
+    fn log(&self, channel: &str, message: &str);
 }
@@ -123,6 +123,7 @@ impl CodeGenerator {
             Statement::ReturnStatement { .. } => {
                 String::from("    ; Return statement\n")
             }
+            Statement::Spanned { inner, .. } => self.emit_statement(inner),
             _ => String::from("    ; Unsupported statement\n"),
         }
     }
@@ -160,8 +161,18 @@ impl CodeGenerator {
                 let _const_name = self.add_string_constant(&s);
                 ir.push_str(&format!("    ; Distance/Duration assignment: {} = \"{}\"\n", name, s));
             }
+            // 真偽値は 1.0 / 0.0 として格納
+            Expr::Bool(b) => {
+                ir.push_str(&format!("    store double {}, double* %{}, align 8\n",
+                    if *b { "1.0" } else { "0.0" }, name));
+            }
+            // 百分率は分数として格納
+            Expr::Percent { value } => {
+                ir.push_str(&format!("    store double {:?}, double* %{}, align 8\n",
+                    value / 100.0, name));
+            }
             // AGN 2.0
-            Expr::PropertyAccess { .. } | Expr::Bond(_, _) | Expr::Call { .. } => {
+            Expr::PropertyAccess { .. } | Expr::Bond(_, _) | Expr::Call { .. } | Expr::Spread(_) | Expr::Await(_) | Expr::Record(_) => {
                  ir.push_str(&format!("    ; Complex expression not implemented in codegen: {:?}\n", value));
             }
         }
@@ -201,11 +212,13 @@ impl CodeGenerator {
             Expr::Distance { value, .. } | Expr::Duration { value, .. } => {
                 format!("{:.1}", value)
             }
+            Expr::Percent { value } => format!("{:?}", value / 100.0),
+            Expr::Bool(b) => if *b { "1.0" } else { "0.0" }.to_string(),
             // AGN 2.0
             Expr::PropertyAccess { .. } => {
                 "0.0".to_string() // Stub
             }
-            Expr::Bond(_, _) | Expr::Call { .. } => {
+            Expr::Bond(_, _) | Expr::Call { .. } | Expr::Spread(_) | Expr::Await(_) | Expr::Record(_) => {
                 "0.0".to_string() // Stub
             }
         };
@@ -295,13 +308,21 @@ impl CodeGenerator {
                         let _const_name = self.add_string_constant(&s);
                         ir.push_str(&format!("    ; Print Distance/Duration: \"{}\"\n", s));
                     }
+                    Expr::Percent { value } => {
+                        ir.push_str(&format!("    ; Print Percent: \"{}%\"\n", value));
+                    }
+                    Expr::Bool(b) => {
+                        let s = if *b { "真" } else { "偽" };
+                        let _const_name = self.add_string_constant(s);
+                        ir.push_str(&format!("    ; Print Bool: \"{}\"\n", s));
+                    }
                     // AGN 2.0
                     Expr::PropertyAccess { .. } => {
                          let s = "[PropertyAccess Stub]";
                          let _const_name = self.add_string_constant(s);
                          ir.push_str(&format!("    ; Print PropertyAccess: \"{}\"\n", s));
                     }
-                    Expr::Bond(_, _) | Expr::Call { .. } => {
+                    Expr::Bond(_, _) | Expr::Call { .. } | Expr::Spread(_) | Expr::Await(_) | Expr::Record(_) => {
                          let s = "[Bond/Call Stub]";
                          let _const_name = self.add_string_constant(s);
                          ir.push_str(&format!("    ; Print Bond/Call: \"{}\"\n", s));
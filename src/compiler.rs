@@ -201,6 +201,32 @@ impl CompileResult {
     }
 }
 
+/// 実行せずに正規化・字句解析・構文解析・型推論だけを行い、診断を返す (--check)
+/// 空なら問題なし
+pub fn check(source: &str) -> Vec<String> {
+    let normalizer = Normalizer::new();
+    let (normalized, _corrections, map) = normalizer.normalize_with_map(source);
+
+    // 位置は正規化前のソースで報告する
+    let mut lexer = Lexer::new(&normalized);
+    let (tokens, spans) = lexer.tokenize_with_spans();
+    let mut parser = Parser::with_spans(tokens, map.spans_to_original(spans));
+    let program = match parser.parse() {
+        Ok(program) => program,
+        Err(e) => match parser.current_span() {
+            Some(span) => return vec![format!("Parse error: {}: {}", span, e)],
+            None => return vec![format!("Parse error: {}", e)],
+        },
+    };
+
+    TypeInferencer::new()
+        .infer(&program)
+        .diagnostics
+        .into_iter()
+        .map(|d| format!("Type error: {}", d))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,4 +256,20 @@ mod tests {
             Err(e) => panic!("Compile failed: {}", e),
         }
     }
+
+    #[test]
+    fn test_check_reports_errors_without_running() {
+        assert!(check("X は 10 だ\nX を 表示する").is_empty());
+
+        let diagnostics = check("X は Y だ\nif X equals 5 then\n要旨 は X を 要約する\nend");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].starts_with("Type error: line 3"), "{:?}", diagnostics);
+
+        let diagnostics = check("X は だ");
+        assert!(diagnostics[0].starts_with("Parse error"), "{:?}", diagnostics);
+
+        // 位置は正規化 (字下げの除去・ひょうじする → 表示する) 前のソースの列で報告する
+        let diagnostics = check("  X を ひょうじする ) だ");
+        assert!(diagnostics[0].starts_with("Parse error: 1行14列: "), "{:?}", diagnostics);
+    }
 }
@@ -0,0 +1,406 @@
+//! AGN Formatter - ソース整形
+//! 解析済みの AST を正規化された AGN ソースとして出力する (agn --format)
+
+use crate::parser::{Condition, Expr, LayoutDirection, Program, SearchOrder, SpatialFilter, Statement};
+
+/// インデント幅 (ブロック1段あたり)
+const INDENT: &str = "  ";
+
+/// プログラムを整形済みのソースにする
+/// 1行1文、ブロックは2スペースで字下げして おわり で閉じる
+pub fn format_program(program: &Program) -> String {
+    let mut out = String::new();
+    format_block(&program.statements, 0, &mut out);
+    out
+}
+
+fn format_block(statements: &[Statement], depth: usize, out: &mut String) {
+    for stmt in statements {
+        format_statement(stmt, depth, out);
+    }
+}
+
+fn line(depth: usize, text: &str, out: &mut String) {
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str(text);
+    out.push('\n');
+}
+
+/// [先頭行] + 本体 + おわり
+fn block(depth: usize, header: &str, body: &[Statement], out: &mut String) {
+    line(depth, header, out);
+    format_block(body, depth + 1, out);
+    line(depth, "おわり", out);
+}
+
+fn format_statement(stmt: &Statement, depth: usize, out: &mut String) {
+    match stmt {
+        Statement::Spanned { inner, .. } => format_statement(inner, depth, out),
+        Statement::Comment { text, trailing } => {
+            // 行末コメントは直前の行に戻す
+            if *trailing && out.ends_with('\n') {
+                out.pop();
+                out.push_str(&format!(" //{}\n", text));
+            } else {
+                line(depth, &format!("//{}", text), out);
+            }
+        }
+        Statement::Assignment { target, value } => {
+            line(depth, &format!("{} は {} だ", expr(target), expr(value)), out)
+        }
+        Statement::Assert { condition: cond } => match cond {
+            Condition::NotEquals(left, right) => {
+                line(depth, &format!("{} は {} ではない", expr(left), expr(right)), out)
+            }
+            other => line(depth, &format!("{} ではない", condition(other)), out),
+        },
+        Statement::LoadAsset { target, path } => {
+            line(depth, &format!("{} は {} を 読み込む", expr(target), expr(path)), out)
+        }
+        Statement::SaveScreen { path } => line(depth, &format!("画面 を {} に 保存する", expr(path)), out),
+        Statement::Log { value, channel } => {
+            line(depth, &format!("{} を {} に 記録する", expr(value), string_literal(channel)), out)
+        }
+        Statement::ComponentDefine { target, style, component } => {
+            line(depth, &format!("{} は {} な {} だ", expr(target), word(style), component), out)
+        }
+        Statement::BinaryOp { target, operand, verb } => line(depth, &binary_op(target, operand, verb, ""), out),
+        Statement::UnaryOp { operand, verb } => line(depth, &format!("{} を {}", expr(operand), verb), out),
+        Statement::AsyncOp { operand, verb } => line(depth, &format!("{} を 並列で {}", expr(operand), verb), out),
+        Statement::Parallel { inner } => match inner.unspanned() {
+            Statement::AiOp { result, input, verb, .. } => {
+                line(depth, &format!("{} は {} を 並列で {}", expr(result), expr(input), verb), out)
+            }
+            Statement::BinaryOp { target, operand, verb } => {
+                line(depth, &binary_op(target, operand, verb, "並列で "), out)
+            }
+            other => format_statement(other, depth, out),
+        },
+        Statement::Await { target } => line(depth, &format!("待つ {}", expr(target)), out),
+        Statement::Cancel { target } => line(depth, &format!("取り消す {}", expr(target)), out),
+        Statement::SetVisibility { target, visible } => {
+            let verb = if *visible { "見せる" } else { "隠す" };
+            line(depth, &format!("{} を {}", expr(target), verb), out)
+        }
+        Statement::SetEnabled { target, enabled } => {
+            let state = if *enabled { "有効" } else { "無効" };
+            line(depth, &format!("{} を {} に する", expr(target), state), out)
+        }
+        Statement::IfStatement { condition: cond, then_block, else_block } => {
+            // 以上 は とき 構文でしか書けない
+            if let (Condition::GreaterOrEqual(left, right), None) = (cond, else_block) {
+                let header = format!("{} が {} 以上 の とき", when_subject(left), expr(right));
+                return block(depth, &header, then_block, out);
+            }
+            line(depth, &format!("もし {} ならば", condition(cond)), out);
+            format_block(then_block, depth + 1, out);
+            if let Some(else_block) = else_block {
+                line(depth, "そうでなければ", out);
+                format_block(else_block, depth + 1, out);
+            }
+            line(depth, "おわり", out);
+        }
+        Statement::RepeatStatement { count, body } => {
+            let header = match count {
+                Expr::Number(_) => format!("{} 回 繰り返す", expr(count)),
+                // 数値リテラル以外の回数は英語構文でしか書けない
+                _ => format!("repeat {} times", expr(count)),
+            };
+            block(depth, &header, body, out);
+        }
+        Statement::WhileStatement { condition: cond, body } => {
+            block(depth, &format!("{} の間 繰り返す", condition(cond)), body, out);
+        }
+        Statement::Break => line(depth, "やめる", out),
+        Statement::Continue => line(depth, "つぎへ", out),
+        Statement::AiOp { result, input, verb, options } => {
+            let text = match options {
+                Some(options) => format!("{} は {} を {} に {}", expr(result), expr(input), expr(options), verb),
+                None => format!("{} は {} を {}", expr(result), expr(input), verb),
+            };
+            line(depth, &text, out);
+        }
+        Statement::ScreenOp { operand } => line(depth, &format!("show {} to 画面", expr(operand)), out),
+        Statement::EventHandler { target, event, body, interval } => {
+            let interval = interval.map(|secs| format!(" {} 秒 間隔 で", number(secs))).unwrap_or_default();
+            let header = match (target, event.as_str()) {
+                (Expr::Variable(name), "hover") if name == "self" => "マウス が 上 に あるとき".to_string(),
+                (Expr::Variable(_), "click") => format!("{} を 押したとき{}", expr(target), interval),
+                (Expr::Variable(_), "drag") => format!("{} を 動かしたとき{}", expr(target), interval),
+                _ => format!("on {} {}{}", expr(target), event, interval),
+            };
+            block(depth, &header, body, out);
+        }
+        Statement::EventListener { event_type, from_var, to_var, body } => {
+            let mut header = format!("on イベント({})", event_type);
+            if let Some(from) = from_var {
+                header.push_str(&format!(" から {}", from));
+            }
+            if let Some(to) = to_var {
+                header.push_str(&format!(" to {}", to));
+            }
+            header.push_str(" {");
+            line(depth, &header, out);
+            format_block(body, depth + 1, out);
+            line(depth, "}", out);
+        }
+        Statement::DelayStatement { duration, body } => {
+            block(depth, &format!("{} 秒 後 に", expr(duration)), body, out)
+        }
+        Statement::AnimateStatement { duration, target, property, value } => line(
+            depth,
+            &format!("{} 秒 かけて {} の {} を {} に する", expr(duration), expr(target), property, expr(value)),
+            out,
+        ),
+        Statement::Block { target, body } => block(depth, &format!("{} の中 に", expr(target)), body, out),
+        Statement::Bind { target, property, variable } => {
+            line(depth, &format!("{} の {} を {} に 束縛する", expr(target), property, variable), out)
+        }
+        Statement::Layout { target, direction } => {
+            let direction = match direction {
+                LayoutDirection::Vertical => "縦並び",
+                LayoutDirection::Horizontal => "横並び",
+            };
+            line(depth, &format!("{} を {} に 置く", expr(target), direction), out)
+        }
+        Statement::SpatialSearch { result, max_distance, filters, order, limit } => {
+            let mut text = format!("{} は {} より近い 人", expr(result), expr(max_distance));
+            for filter in filters {
+                text.push_str(&spatial_filter(filter));
+            }
+            text.push_str(" を");
+            match order {
+                Some(SearchOrder::Nearest) => text.push_str(" 近い順 に"),
+                Some(SearchOrder::HighestToku) => text.push_str(" 徳の高い順 に"),
+                None => {}
+            }
+            if let Some(limit) = limit {
+                text.push_str(&format!(" {} 人", expr(limit)));
+            }
+            text.push_str(" 探す");
+            line(depth, &text, out);
+        }
+        Statement::BeaconBroadcast { beacon_type, .. } => {
+            // 種類を省略すると「暇」
+            if beacon_type == "暇" {
+                line(depth, "ビーコン を 発信する", out)
+            } else {
+                line(depth, &format!("{} の ビーコン を 発信する", word(beacon_type)), out)
+            }
+        }
+        Statement::Notify { target, message } => {
+            line(depth, &format!("{} に {} を 通知する", expr(target), expr(message)), out)
+        }
+        Statement::TokuAccrue { target, amount } => {
+            line(depth, &format!("{} に {} だけ 徳 を 加算する", expr(target), expr(amount)), out)
+        }
+        Statement::RuleDefinition { name, body } => block(depth, &format!("ルール {}", name), body, out),
+        Statement::ActionDefinition { name, params, body } => {
+            let header = if params.is_empty() {
+                format!("アクション {}", name)
+            } else {
+                format!("アクション {}({})", name, params.join(", "))
+            };
+            block(depth, &header, body, out);
+        }
+        Statement::VariableUpdate { target, value, verb } => line(depth, &variable_update(target, value, verb), out),
+        Statement::ReturnStatement { value } => line(depth, &format!("結果 を {} とする", expr(value)), out),
+        Statement::ActionCall { name, args } => line(depth, &call(name, args), out),
+    }
+}
+
+/// [ターゲット] に [値] を [動詞]。画面 や 画面 の 中央 は逆順で書く
+fn binary_op(target: &Expr, operand: &Expr, verb: &str, adverb: &str) -> String {
+    match target {
+        Expr::Variable(name) if name == "Screen" => format!("{} を 画面 に {}", expr(operand), verb),
+        Expr::Variable(name) if name.contains('.') => {
+            let (base, modifier) = name.split_once('.').unwrap_or_default();
+            let base = if base == "Screen" { "画面" } else { base };
+            format!("{} を {} の {} に {}", expr(operand), base, modifier, verb)
+        }
+        _ => format!("{} に {} を {}{}", expr(target), expr(operand), adverb, verb),
+    }
+}
+
+/// 変数更新: 1 だけ増減するなら [式] を 増やす、それ以外は [式] に [値] を [動詞]
+/// 単純な変数 (日本語構文だと BinaryOp になる) と 更新する は英語構文で書く
+fn variable_update(target: &Expr, value: &Expr, verb: &str) -> String {
+    let english = match verb {
+        "増やす" => Some(("increase", "by")),
+        "減らす" => Some(("decrease", "by")),
+        "更新する" => Some(("update", "to")),
+        _ => None,
+    };
+    if let Some((keyword, preposition)) = english {
+        if matches!(target, Expr::Variable(_)) || verb == "更新する" {
+            return format!("{} {} {} {}", keyword, expr(target), preposition, expr(value));
+        }
+    }
+    let is_one = matches!(value, Expr::Number(n) if *n == 1.0);
+    if is_one && matches!(verb, "増やす" | "減らす" | "深くする") {
+        format!("{} を {}", expr(target), verb)
+    } else {
+        format!("{} に {} を {}", expr(target), expr(value), verb)
+    }
+}
+
+/// とき 構文の主語。自分.徳 は 徳 と書ける
+fn when_subject(left: &Expr) -> String {
+    match left {
+        Expr::PropertyAccess { target, property } if property == "徳" && matches!(target.as_ref(), Expr::Variable(n) if n == "自分") => {
+            "徳".to_string()
+        }
+        _ => expr(left),
+    }
+}
+
+fn spatial_filter(filter: &SpatialFilter) -> String {
+    let field = &filter.field;
+    match &filter.condition {
+        Condition::Equals(_, Expr::String(value)) => format!(" で {} が {} な 人", field, value),
+        Condition::GreaterOrEqual(_, threshold) => format!(" で {} が {} 以上 の 人", field, expr(threshold)),
+        Condition::GreaterThan(_, threshold) => format!(" で {} が {} より大きい の 人", field, expr(threshold)),
+        Condition::LessThan(_, threshold) => format!(" で {} が {} より小さい の 人", field, expr(threshold)),
+        other => format!(" で {} が {} な 人", field, condition(other)),
+    }
+}
+
+fn condition(cond: &Condition) -> String {
+    match cond {
+        Condition::Equals(left, right) => format!("{} と等しい {}", expr(left), expr(right)),
+        Condition::GreaterThan(left, right) => format!("{} より大きい {}", expr(left), expr(right)),
+        Condition::LessThan(left, right) => format!("{} より小さい {}", expr(left), expr(right)),
+        Condition::GreaterOrEqual(left, right) => format!("{} 以上 {}", expr(left), expr(right)),
+        Condition::Nearer(distance) => format!("{} より近い", expr(distance)),
+        Condition::Farther(distance) => format!("{} より遠い", expr(distance)),
+        Condition::HasBond(left, right) => format!("{} と {} の間に 絆 がある", expr(left), expr(right)),
+        Condition::Truthy(value) => expr(value),
+        Condition::NotEquals(left, right) => format!("{} は {} ではない", expr(left), expr(right)),
+        Condition::And(left, right) => format!("{} かつ {}", condition(left), condition(right)),
+        Condition::Or(left, right) => format!("{} または {}", condition(left), condition(right)),
+    }
+}
+
+fn expr(e: &Expr) -> String {
+    match e {
+        Expr::Number(n) => number(*n),
+        Expr::Bool(b) => if *b { "真" } else { "偽" }.to_string(),
+        Expr::String(s) => string_literal(s),
+        Expr::Variable(name) => name.clone(),
+        Expr::Distance { value, unit } | Expr::Duration { value, unit } => format!("{}{}", number(*value), unit),
+        Expr::Percent { value } => format!("{}%", number(*value)),
+        Expr::PropertyAccess { target, property } => format!("{}.{}", expr(target), property),
+        Expr::Bond(left, right) => format!("絆({}, {})", expr(left), expr(right)),
+        Expr::Call { name, args } => call(name, args),
+        Expr::Spread(inner) => format!("...{}", expr(inner)),
+        Expr::Await(inner) => format!("待つ {}", expr(inner)),
+        Expr::Record(fields) => {
+            let fields: Vec<String> = fields.iter().map(|(k, v)| format!("{}: {}", word(k), expr(v))).collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+fn call(name: &str, args: &[Expr]) -> String {
+    let args: Vec<String> = args.iter().map(expr).collect();
+    format!("{}({})", name, args.join(", "))
+}
+
+fn number(n: f64) -> String {
+    format!("{}", n)
+}
+
+/// 名詞として書ける語はそのまま、空白などを含むなら文字列リテラルにする
+fn word(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        s.to_string()
+    } else {
+        string_literal(s)
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(code: &str) -> Program {
+        let mut lexer = Lexer::new(code);
+        Parser::new(lexer.tokenize()).parse().unwrap()
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let code = r#"X は 10 だ
+X に 5 を 足す
+メッセージ は "こんにちは \"AGN\"" だ
+もし X より大きい 3 かつ X より小さい 100 ならば
+    メッセージ を 表示する
+そうでなければ
+  "小さい" を 表示する
+おわり
+3 回 繰り返す
+X に 1 を 足す
+おわり
+アクション 二倍(N)
+  結果 を N とする
+おわり
+Y は 二倍(X) だ
+ボタン は 青い な ボタン だ
+ボタン を 押したとき
+  ボタン を 隠す
+おわり
+カード の中 に
+  ボタン を 縦並び に 置く
+おわり
+徳 が 500 以上 の とき
+  ビーコン を 発信する
+おわり
+近所 は 100m より近い 人 で 状態 が 暇 な 人 を 近い順 に 3 人 探す
+User.Toku を 増やす
+Z は 50% だ
+"#;
+        let program = parse(code);
+        let formatted = format_program(&program);
+        let reparsed = parse(&formatted);
+        assert_eq!(format!("{:?}", reparsed.statements), format!("{:?}", program.statements));
+
+        // 整形済みのソースは変わらない
+        assert_eq!(format_program(&reparsed), formatted);
+        assert!(formatted.contains("もし X より大きい 3 かつ X より小さい 100 ならば\n  メッセージ を 表示する\nそうでなければ\n"));
+    }
+
+    #[test]
+    fn test_format_preserves_comments() {
+        let code = "// 初期値\nX は 10 だ  // 個数\n3 回 繰り返す\n    // 増やす\n  X に 1 を 足す\nおわり // ループ終わり\n";
+        let mut lexer = Lexer::with_comments(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        assert_eq!(
+            format_program(&program),
+            "// 初期値\nX は 10 だ // 個数\n3 回 繰り返す\n  // 増やす\n  X に 1 を 足す\nおわり // ループ終わり\n"
+        );
+
+        // 通常の字句解析ではコメントは捨てられる
+        assert_eq!(parse(code).statements.len(), 2);
+    }
+
+    #[test]
+    fn test_format_comment_inside_parens() {
+        let code = "Y は Mix(\n  1, // one\n  2\n) だ\nY を 表示する";
+        let format = |code: &str| format_program(&Parser::new(Lexer::with_comments(code)).parse().unwrap());
+        let formatted = format(code);
+        // 括弧内のコメントは文の行末に移る
+        assert_eq!(formatted, "Y は Mix(1, 2) だ // one\nY を 表示する\n");
+        assert_eq!(format(&formatted), formatted);
+    }
+}
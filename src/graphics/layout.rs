@@ -1,9 +1,20 @@
 use taffy::prelude::*;
 use crate::symbol_table::Value;
 
+/// レイアウト結果 1 件 (x, y, 幅, 高さ, 値)。物理ピクセル
+pub type LayoutRect = (f32, f32, f32, f32, Value);
+
 pub struct LayoutEngine {
     taffy: Taffy,
     root: Node,
+    /// 論理ピクセル → 物理ピクセルの倍率 (hi-dpi では 2.0 など)
+    pub scale_factor: f32,
+    /// 前回レイアウトしたときのルート (ツリーが変わるまで結果を再利用する)
+    cached_root: Option<Value>,
+    /// リサイズ・UIメッセージで立つ再計算フラグ
+    pub dirty: bool,
+    /// レイアウトを計算した回数
+    pub computations: usize,
 }
 
 impl LayoutEngine {
@@ -11,10 +22,29 @@ impl LayoutEngine {
         let mut taffy = Taffy::new();
         // default root
         let root = taffy.new_leaf(Style::default()).unwrap();
-        Self { taffy, root }
+        Self { taffy, root, scale_factor: 1.0, cached_root: None, dirty: true, computations: 0 }
+    }
+
+    /// ツリーが変わったとき、またはダーティなときだけレイアウトする。前回の結果を使えるなら None
+    pub fn compute_if_changed(&mut self, root_value: Option<&Value>, width: f32, height: f32) -> Option<Vec<LayoutRect>> {
+        if !self.dirty && self.cached_root.as_ref() == root_value {
+            return None;
+        }
+        let results = match root_value {
+            Some(root_value) => self.compute_layout(root_value, width, height),
+            None => Vec::new(),
+        };
+        self.computations += 1;
+        self.cached_root = root_value.cloned();
+        self.dirty = false;
+        Some(results)
     }
 
+    /// 物理ピクセルの画面サイズを受け取り、論理サイズでレイアウトして物理ピクセルの矩形を返す
     pub fn compute_layout(&mut self, root_value: &Value, width: f32, height: f32) -> Vec<(f32, f32, f32, f32, Value)> {
+        let scale = self.scale_factor;
+        let (width, height) = (width / scale, height / scale);
+
         // Rebuild tree from Value
         self.taffy = Taffy::new();
         
@@ -39,15 +69,25 @@ impl LayoutEngine {
         
         // Collect results
         let mut results = Vec::new();
+        // 非表示のルートは何も描画・ヒットテストしない
+        if !root_value.is_visible() {
+            return results;
+        }
         // Collect from content_root, offset by root (0,0)
         self.collect_results(content_root, root_value, 0.0, 0.0, &mut results);
+        for (x, y, w, h, _) in &mut results {
+            *x *= scale;
+            *y *= scale;
+            *w *= scale;
+            *h *= scale;
+        }
         
         results
     }
     
     fn build_tree(&mut self, value: &Value) -> Node {
         match value {
-             Value::Component { style: _, ty: _, label: _, children, layout } => {
+             Value::Component { children, layout, .. } => {
                  let mut flex_dir = FlexDirection::Column;
                  if let Some(l) = layout {
                      if l == "horizontal" {
@@ -70,7 +110,8 @@ impl LayoutEngine {
                  
                  let node = self.taffy.new_leaf(style).unwrap();
                  
-                 for child in children {
+                 // 非表示の子はレイアウトから除外 (collect_results と同じ順序を保つ)
+                 for child in children.iter().filter(|c| c.is_visible()) {
                      let child_node = self.build_tree(child);
                      self.taffy.add_child(node, child_node).unwrap();
                  }
@@ -115,12 +156,97 @@ impl LayoutEngine {
         
         if let Value::Component { children, .. } = value {
              if let Ok(children_nodes) = self.taffy.children(node) {
-                 for (i, child_node) in children_nodes.iter().enumerate() {
-                     if i < children.len() {
-                         self.collect_results(*child_node, &children[i], x, y, results);
-                     }
+                 let visible: Vec<&Value> = children.iter().filter(|c| c.is_visible()).collect();
+                 for (child_node, child) in children_nodes.iter().zip(visible) {
+                     self.collect_results(*child_node, child, x, y, results);
                  }
              }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn button(label: &str, visible: bool) -> Value {
+        Value::Component {
+            style: "Button".to_string(),
+            ty: "ボタン".to_string(),
+            label: Some(label.to_string()),
+            children: vec![Value::String(label.to_string())],
+            layout: None,
+            visible,
+            enabled: true,
+        }
+    }
+
+    fn labels(results: &[(f32, f32, f32, f32, Value)]) -> Vec<String> {
+        results.iter().filter_map(|(_, _, _, _, v)| match v {
+            Value::Component { label: Some(l), .. } => Some(l.clone()),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_hidden_component_excluded_from_hit_testing() {
+        let mut root = Value::Component {
+            style: "Card".to_string(),
+            ty: "コンテナ".to_string(),
+            label: Some("カード".to_string()),
+            children: vec![button("A", true), button("B", true)],
+            layout: None,
+            visible: true,
+            enabled: true,
+        };
+        let mut engine = LayoutEngine::new();
+        assert_eq!(labels(&engine.compute_layout(&root, 800.0, 600.0)), vec!["カード", "A", "B"]);
+
+        root.update_components("A", &|c| if let Value::Component { visible, .. } = c { *visible = false });
+        let results = engine.compute_layout(&root, 800.0, 600.0);
+        assert_eq!(labels(&results), vec!["カード", "B"]);
+
+        root.update_components("カード", &|c| if let Value::Component { visible, .. } = c { *visible = false });
+        assert!(engine.compute_layout(&root, 800.0, 600.0).is_empty());
+    }
+
+    #[test]
+    fn test_logical_size_scaled_to_physical() {
+        let root = Value::Component {
+            style: "Card".to_string(),
+            ty: "コンテナ".to_string(),
+            label: Some("カード".to_string()),
+            children: vec![Value::Image("icon.png".to_string())],
+            layout: None,
+            visible: true,
+            enabled: true,
+        };
+        let mut engine = LayoutEngine::new();
+        engine.scale_factor = 2.0;
+        let results = engine.compute_layout(&root, 1600.0, 1200.0);
+
+        // 画像は論理 100x100、親のパディングは論理 20
+        let (x, y, w, h, _) = results[1].clone();
+        assert_eq!((x, y, w, h), (40.0, 40.0, 200.0, 200.0));
+    }
+
+    #[test]
+    fn test_layout_cached_until_tree_changes() {
+        let mut engine = LayoutEngine::new();
+        let root = button("A", true);
+
+        // ツリーが同じでダーティでなければ再計算しない
+        assert_eq!(labels(&engine.compute_if_changed(Some(&root), 200.0, 120.0).unwrap()), vec!["A"]);
+        assert!(engine.compute_if_changed(Some(&root), 200.0, 120.0).is_none());
+        assert_eq!(engine.computations, 1);
+
+        engine.dirty = true;
+        assert!(engine.compute_if_changed(Some(&root), 200.0, 120.0).is_some());
+        assert_eq!(engine.computations, 2);
+
+        let changed = button("B", true);
+        assert_eq!(labels(&engine.compute_if_changed(Some(&changed), 200.0, 120.0).unwrap()), vec!["B"]);
+        assert!(engine.compute_if_changed(None, 200.0, 120.0).unwrap().is_empty());
+        assert_eq!(engine.computations, 4);
+    }
+}
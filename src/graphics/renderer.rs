@@ -30,6 +30,10 @@ pub struct TextDrawCommand {
     pub size: f32,
 }
 
+/// テクスチャを参照する頂点 (画像) を示すエフェクトビット
+/// このビットがない頂点はテクスチャを読まないので、どのバインドグループでも描ける
+pub const EFFECT_TEXTURED: u32 = 8;
+
 #[derive(Clone, Debug)]
 pub struct DrawBatch {
     pub index_start: u32,
@@ -62,6 +66,9 @@ impl Renderer {
     }
 
     fn update_batch(&mut self, added_indices: u32, texture_key: Option<&str>) {
+        if added_indices == 0 {
+            return;
+        }
         // If batches empty, start one
         if self.batches.is_empty() {
              self.batches.push(DrawBatch {
@@ -76,10 +83,11 @@ impl Renderer {
         let last_idx = self.batches.len() - 1;
         let last_key = &self.batches[last_idx].texture_key;
         
+        // テクスチャなしの図形は直前のバッチにそのまま追加する (バインドグループの切り替えは画像のときだけ)
         let match_key = match (last_key, texture_key) {
-            (None, None) => true,
+            (_, None) => true,
             (Some(a), Some(b)) => a == b,
-            _ => false,
+            (None, Some(_)) => false,
         };
         
         if match_key {
@@ -255,7 +263,7 @@ impl Renderer {
                 position: [px, py],
                 color,
                 uv: [u, v],
-                effect_flags,
+                effect_flags: effect_flags | EFFECT_TEXTURED,
             }
         });
 
@@ -289,6 +297,11 @@ impl Renderer {
         }
     }
     
+    /// このフレームで発行される描画コール数
+    pub fn draw_call_count(&self) -> usize {
+        self.batches.len()
+    }
+
     // Returns vertex buffer and index buffer
     pub fn get_buffers(&self, device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer, u32) {
         let vertex_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -358,6 +371,9 @@ impl Renderer {
             }
         }).collect();
 
+        // 前フレームの使用中マークを外す (使われないグリフは後で追い出されうる)
+        atlas.trim();
+
         renderer.prepare(
             device,
             queue,
@@ -1,7 +1,7 @@
 struct Globals {
     screen_size: vec2<f32>,
     time: f32,
-    _pad: f32,
+    scale_factor: f32,
 }
 
 struct VertexInput {
@@ -31,7 +31,7 @@ fn vs_main(
     
     // Effect: Shake (Bit 1)
     if ((model.effect_flags & 2u) != 0u) {
-        let shake_amp = 5.0;
+        let shake_amp = 5.0 * globals.scale_factor;
         pos.x += sin(t * 20.0 + model.position.y * 0.1) * shake_amp;
     }
 
@@ -53,7 +53,10 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    var color = textureSample(t_diffuse, s_diffuse, in.uv) * in.color;
+    // Textured (Bit 3): only images read the bound texture
+    let sampled = textureSample(t_diffuse, s_diffuse, in.uv);
+    let textured = (in.effect_flags & 8u) != 0u;
+    var color = select(vec4<f32>(1.0), sampled, textured) * in.color;
     
     // Effect: Pulse/Glow (Bit 0)
     if ((in.effect_flags & 1u) != 0u) {
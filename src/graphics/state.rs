@@ -1,11 +1,12 @@
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use winit::window::Window;
 
 use crate::graphics::renderer::{Renderer, GpuVertex};
 use crate::graphics::layout::LayoutEngine;
 use crate::graphics::animation::{AnimationController, Animation};
 use crate::symbol_table::{SymbolTable, Value};
+use crate::interpreter::{EventThrottle, ScreenAnchor};
 use wgpu::util::DeviceExt;
 use image::GenericImageView;
 use web_time::Instant;
@@ -18,7 +19,7 @@ use web_time::Instant;
 pub struct Globals {
     pub screen_size: [f32; 2],
     pub time: f32,
-    pub _pad: f32,
+    pub scale_factor: f32,
 }
 
 #[derive(Clone, Debug)]
@@ -32,7 +33,10 @@ pub struct Particle {
 }
 
 pub struct State {
-    pub surface: wgpu::Surface<'static>,
+    /// ウィンドウのサーフェス (new_offscreen では None)
+    pub surface: Option<wgpu::Surface<'static>>,
+    /// サーフェスの代わりの描画先 (new_offscreen)
+    pub offscreen: Option<wgpu::Texture>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
@@ -46,12 +50,16 @@ pub struct State {
     
     // Interaction State
     pub event_handlers: HashMap<String, HashMap<String, Vec<Animation>>>,
+    pub event_throttles: HashMap<(String, String), EventThrottle>,
     pub hovered_component: Option<String>,
     pub cursor_pos: Option<(f32, f32)>,
     pub particles: Vec<Particle>, // NEW
+    pub screen_texts: HashMap<ScreenAnchor, String>, // 画面 の 中央 に 表示する (位置ごとに最新の1件)
     
     // Cache
     pub layout_rects: Vec<(f32, f32, f32, f32, String)>, // x,y,w,h, label (only for hit testing)
+    pub disabled_components: HashSet<String>, // 無効化されたコンポーネント (クリックを無視)
+    pub layout_cache: Vec<(f32, f32, f32, f32, Value)>, // 前回のレイアウト結果 (ツリーが変わるまで再利用)
     
     // Resources
     pub bind_group: wgpu::BindGroup,
@@ -67,9 +75,88 @@ pub struct State {
     // Let's key by target name so component usage is easy.
 }
 
+/// 取得したアダプタの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdapterKind {
+    /// ハードウェア GPU
+    Hardware,
+    /// ソフトウェア実装 (WARP / llvmpipe など)
+    Fallback,
+}
+
+/// ハードウェア → ソフトウェア (フォールバック) の順にアダプタを探す
+async fn request_adapter(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface<'_>>,
+) -> Option<(wgpu::Adapter, AdapterKind)> {
+    for (force_fallback_adapter, kind) in [(false, AdapterKind::Hardware), (true, AdapterKind::Fallback)] {
+        let adapter = instance.request_adapter(
+            &wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: surface,
+                force_fallback_adapter,
+            },
+        ).await;
+        if let Some(adapter) = adapter {
+            return Some((adapter, kind));
+        }
+    }
+    None
+}
+
+/// 垂直同期の有無から表示モードを選ぶ。未対応のモードはサポートされているものに落とす
+/// vsync あり: Fifo / なし: Mailbox → Immediate の順に探し、どちらもなければ Fifo
+pub fn choose_present_mode(vsync: bool, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+    let preferred: &[wgpu::PresentMode] = if vsync {
+        &[wgpu::PresentMode::Fifo]
+    } else {
+        &[wgpu::PresentMode::Mailbox, wgpu::PresentMode::Immediate, wgpu::PresentMode::Fifo]
+    };
+    let mode = preferred.iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        // Fifo は仕様上常にサポートされるが、念のため先頭のモードに落とす
+        .or(supported.first().copied())
+        .unwrap_or(wgpu::PresentMode::Fifo);
+    if mode != preferred[0] {
+        log::warn!("[Graphics] 表示モード {:?} は未対応のため {:?} を使用します", preferred[0], mode);
+    }
+    mode
+}
+
+/// アダプタとデバイスを取得する。使える GPU がなければエラー
+async fn request_gpu(
+    instance: &wgpu::Instance,
+    surface: Option<&wgpu::Surface<'_>>,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), String> {
+    let (adapter, kind) = request_adapter(instance, surface)
+        .await
+        .ok_or_else(|| "No GPU adapter available".to_string())?;
+    if kind == AdapterKind::Fallback {
+        log::warn!("[Graphics] ハードウェアGPUが見つからないため、ソフトウェアレンダラ ({}) を使用します", adapter.get_info().name);
+    }
+
+    let (device, queue) = adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            required_features: wgpu::Features::empty(),
+            required_limits: if cfg!(target_arch = "wasm32") {
+                wgpu::Limits::downlevel_webgl2_defaults()
+            } else {
+                wgpu::Limits::default()
+            },
+            label: None,
+        },
+        None, // Trace path
+    ).await.map_err(|e| format!("Failed to create GPU device: {}", e))?;
+
+    Ok((adapter, device, queue))
+}
+
 impl State {
     // Creating some of the wgpu types requires async code
-    pub async fn new(window: Arc<Window>) -> Self {
+    /// GPU が使えない環境 (ヘッドレスCIなど) ではパニックせずにエラーを返す
+    /// `vsync` が false のときは Mailbox / Immediate を優先する (ベンチマーク用)
+    pub async fn new(window: Arc<Window>, vsync: bool) -> Result<Self, String> {
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -92,42 +179,26 @@ impl State {
         // Let's rely on `wgpu::SurfaceTarget` being compatible.
         
         // Create surface safely (handles Wasm/Native)
-        let surface = instance.create_surface(window.clone()).unwrap();
-
-        let adapter = instance.request_adapter(
-            &wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            },
-        ).await.unwrap();
+        let surface = instance
+            .create_surface(window.clone())
+            .map_err(|e| format!("Failed to create surface: {}", e))?;
 
-        let (device, queue) = adapter.request_device(
-            &wgpu::DeviceDescriptor {
-                required_features: wgpu::Features::empty(),
-                required_limits: if cfg!(target_arch = "wasm32") {
-                    wgpu::Limits::downlevel_webgl2_defaults()
-                } else {
-                    wgpu::Limits::default()
-                },
-                label: None,
-            },
-            None, // Trace path
-        ).await.unwrap();
+        let (adapter, device, queue) = request_gpu(&instance, Some(&surface)).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
         // Shader code assumes sRGB output?
         let surface_format = surface_caps.formats.iter()
             .copied()
             .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
+            .or(surface_caps.formats.first().copied())
+            .ok_or_else(|| "Surface is not supported by the adapter".to_string())?;
             
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode: choose_present_mode(vsync, &surface_caps.present_modes),
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
@@ -135,6 +206,43 @@ impl State {
         
         surface.configure(&device, &config);
 
+        let mut state = Self::with_target(device, queue, config, Some(surface));
+        state.set_scale_factor(window.scale_factor());
+        Ok(state)
+    }
+
+    /// ウィンドウなしでテクスチャに描画する State (CI のスナップショットテスト用)
+    pub async fn new_offscreen(width: u32, height: u32) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let (_, device, queue) = request_gpu(&instance, None).await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width,
+            height,
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+        let mut state = Self::with_target(device, queue, config, None);
+        state.offscreen = Some(create_target_texture(&state.device, &state.config));
+        Ok(state)
+    }
+
+    /// 描画先に依存しないパイプライン・リソースを作る
+    fn with_target(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+        surface: Option<wgpu::Surface<'static>>,
+    ) -> Self {
+        let size = winit::dpi::PhysicalSize::new(config.width, config.height);
+
         // Load shader
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
@@ -147,7 +255,7 @@ impl State {
         let globals_data = Globals {
             screen_size: [size.width as f32, size.height as f32],
             time: 0.0,
-            _pad: 0.0,
+            scale_factor: 1.0,
         };
         let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Globals Buffer"),
@@ -281,6 +389,7 @@ impl State {
 
         Self {
             surface,
+            offscreen: None,
             device,
             queue,
             config,
@@ -291,10 +400,14 @@ impl State {
             symbol_table,
             animation_controller,
             event_handlers: HashMap::new(),
+            event_throttles: HashMap::new(),
             hovered_component: None,
             cursor_pos: None,
             particles: Vec::new(),
+            screen_texts: HashMap::new(),
             layout_rects: Vec::new(),
+            disabled_components: HashSet::new(),
+            layout_cache: Vec::new(),
             
             // Resources
             bind_group,
@@ -308,10 +421,20 @@ impl State {
         }
     }
 
-    pub fn register_event(&mut self, target: String, event: String, animations: Vec<Animation>) {
+    pub fn register_event(&mut self, target: String, event: String, animations: Vec<Animation>, interval: Option<f64>) {
+        let key = (target.clone(), event.clone());
+        match interval {
+            Some(secs) => self.event_throttles.insert(key, EventThrottle::new(secs)),
+            None => self.event_throttles.remove(&key),
+        };
         let handlers = self.event_handlers.entry(target).or_insert_with(HashMap::new);
         handlers.insert(event, animations);
     }
+
+    /// 間引き間隔内のイベントなら false
+    fn should_fire(&mut self, target: &str, event: &str) -> bool {
+        throttle_allows(&mut self.event_throttles, target, event, crate::utils::now_ms())
+    }
     
     pub fn load_image(&mut self, target: String, path: String) {
         println!("[State] Loading image for {}: {}", target, path);
@@ -395,14 +518,17 @@ impl State {
         self.check_hover();
     }
     
-    pub fn handle_click(&self, x: f32, y: f32) -> Option<String> {
+    /// クリック位置のコンポーネントを返す。無効・間引き中なら None
+    pub fn handle_click(&mut self, x: f32, y: f32) -> Option<String> {
          // Iterate backwards (front-to-back)
-         for (lx, ly, w, h, label) in self.layout_rects.iter().rev() {
-             if x >= *lx && x <= lx + w && y >= *ly && y <= ly + h {
-                 return Some(label.clone());
-             }
+         let hit = self.layout_rects.iter().rev()
+             .find(|(lx, ly, w, h, _)| x >= *lx && x <= lx + w && y >= *ly && y <= ly + h)
+             .map(|(.., label)| label.clone())?;
+         // 最前面が無効なら背面へ透過させずに無視する
+         if self.disabled_components.contains(&hit) {
+             return None;
          }
-         None
+         Some(hit).filter(|t| self.should_fire(t, "click"))
     }
 
     pub fn check_hover(&mut self) {
@@ -420,8 +546,8 @@ impl State {
                  // (Optional: reverse animation? Not implemented)
                  
                  // Mouse enter new
-                  if let Some(target) = &found_hover {
-                      if let Some(handlers) = self.event_handlers.get(target) {
+                  if let Some(target) = found_hover.clone().filter(|t| self.should_fire(t, "hover")) {
+                      if let Some(handlers) = self.event_handlers.get(&target) {
                           if let Some(anims) = handlers.get("hover") {
                               println!("[State] Hover Triggered on {}", target);
                               for anim in anims {
@@ -439,17 +565,21 @@ impl State {
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
+            self.layout_engine.dirty = true;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
+            match &self.surface {
+                Some(surface) => surface.configure(&self.device, &self.config),
+                None => self.offscreen = Some(create_target_texture(&self.device, &self.config)),
+            }
             
             // Update Uniform
             let globals_data = Globals {
                 screen_size: [new_size.width as f32, new_size.height as f32],
                 time: self.start_time.elapsed().as_secs_f32(),
-                _pad: 0.0,
+                scale_factor: self.layout_engine.scale_factor,
             };
             self.queue.write_buffer(&self.globals_buffer, 0, bytemuck::cast_slice(&[globals_data]));
         }
@@ -506,22 +636,94 @@ impl State {
         let globals_data = Globals {
             screen_size: [self.size.width as f32, self.size.height as f32],
             time,
-            _pad: 0.0,
+            scale_factor: self.layout_engine.scale_factor,
         };
         self.queue.write_buffer(&self.globals_buffer, 0, bytemuck::cast_slice(&[globals_data]));
 
-        let output = self.surface.get_current_texture()?;
+        let Some(surface) = &self.surface else {
+            // オフスクリーン: 保持しているテクスチャに描画する
+            if let Some(texture) = self.offscreen.take() {
+                self.draw_frame(&texture.create_view(&wgpu::TextureViewDescriptor::default()), ui_root);
+                self.offscreen = Some(texture);
+            }
+            return Ok(());
+        };
+        let output = surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
 
-        // 1. Layout
-        let mut layout_rects = Vec::new();
-        if let Some(root_val) = ui_root {
-             layout_rects = self.layout_engine.compute_layout(
-                 root_val, 
-                 self.size.width as f32, 
-                 self.size.height as f32
-             );
+        self.draw_frame(&view, ui_root);
+        output.present();
+    
+        Ok(())
+    }
+
+    /// 画面を描画して RGBA 画像として読み戻す
+    /// サーフェスは読み戻せないため、ウィンドウ使用時は別のテクスチャに描画する
+    pub fn capture(&mut self, ui_root: Option<&Value>) -> Result<image::RgbaImage, String> {
+        let texture = match self.offscreen.take() {
+            Some(texture) => texture,
+            None => create_target_texture(&self.device, &self.config),
+        };
+        self.draw_frame(&texture.create_view(&wgpu::TextureViewDescriptor::default()), ui_root);
+        let image = read_texture(&self.device, &self.queue, &texture);
+        if self.surface.is_none() {
+            self.offscreen = Some(texture);
         }
+        image
+    }
+
+    /// 現在の画面を PNG として保存する
+    pub fn save_screenshot(&mut self, ui_root: Option<&Value>, path: &str) -> Result<(), String> {
+        let image = self.capture(ui_root)?;
+        image.save(path).map_err(|e| format!("Failed to save screenshot '{}': {}", path, e))?;
+        println!("[State] Screenshot saved: {}", path);
+        Ok(())
+    }
+
+    /// hi-dpi の倍率を変更する (ScaleFactorChanged)。レイアウトは論理ピクセルで計算される
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.layout_engine.scale_factor = scale_factor as f32;
+        self.layout_engine.dirty = true;
+    }
+
+    /// 指定位置に文字列を表示する (同じ位置の前の表示は置き換える)
+    pub fn show_text(&mut self, text: String, anchor: ScreenAnchor) {
+        self.screen_texts.insert(anchor, text);
+    }
+
+    /// 次の描画でレイアウトを再計算させる (テキストや画像の変更時に呼ぶ)
+    pub fn mark_layout_dirty(&mut self) {
+        self.layout_engine.dirty = true;
+    }
+
+    /// ツリーが変わったとき、またはダーティなときだけレイアウトを再計算する
+    fn update_layout(&mut self, ui_root: Option<&Value>) {
+        let size = (self.size.width as f32, self.size.height as f32);
+        let Some(layout) = self.layout_engine.compute_if_changed(ui_root, size.0, size.1) else {
+            return;
+        };
+        self.layout_cache = layout;
+
+        // Store rects for hit testing (need to clone label)
+        self.layout_rects = self.layout_cache.iter().filter_map(|(x,y,w,h,v)| {
+             if let Value::Component { label: Some(l), .. } = v {
+                 Some((*x, *y, *w, *h, l.clone()))
+             } else {
+                 None
+             }
+        }).collect();
+        self.disabled_components = self.layout_cache.iter().filter_map(|(_, _, _, _, v)| match v {
+             Value::Component { label: Some(l), enabled: false, .. } => Some(l.clone()),
+             _ => None,
+        }).collect();
+
+    }
+
+    /// UI を指定したビューに描画してキューに送る
+    fn draw_frame(&mut self, view: &wgpu::TextureView, ui_root: Option<&Value>) {
+        // 1. Layout
+        self.update_layout(ui_root);
+        let layout_rects = std::mem::take(&mut self.layout_cache);
 
         // 2. Tessellate
         self.renderer.begin();
@@ -555,22 +757,13 @@ impl State {
             }
         }
 
-        // Store rects for hit testing (need to clone label)
-        self.layout_rects = layout_rects.iter().filter_map(|(x,y,w,h,v)| {
-             if let Value::Component { label: Some(l), .. } = v {
-                 Some((*x, *y, *w, *h, l.clone()))
-             } else {
-                 None
-             }
-        }).collect();
-
-        for (x, y, w, h, val) in layout_rects {
+        for &(x, y, w, h, ref val) in &layout_rects {
             match val {
                 Value::Image(path) => {
                      // Draw Image (TODO: Support effects on raw images?)
-                     self.renderer.draw_image(x, y, w, h, &path, 0);
+                     self.renderer.draw_image(x, y, w, h, path, 0);
                 }
-                Value::Component { style, ty: _, label, .. } => {
+                Value::Component { style, ty: _, label, enabled, .. } => {
                     let mut shadow_depth = 0.0;
                     let mut color_override = None;
                     let mut effect_flags = 0;
@@ -607,23 +800,42 @@ impl State {
                         _ => [0.5, 0.5, 0.5, 1.0],
                     };
                     
-                    let color = color_override.unwrap_or(base_color);
+                    let mut color = color_override.unwrap_or(base_color);
+                    if !enabled {
+                        // 無効: 灰色寄りに薄く表示
+                        for c in color.iter_mut().take(3) {
+                            *c = *c * 0.5 + 0.25;
+                        }
+                        color[3] *= 0.5;
+                    }
                     
                     // Draw Shadow
-                    self.renderer.draw_shadow_rect(x, y, w, h, 10.0, shadow_depth);
+                    let radius = 10.0 * self.layout_engine.scale_factor;
+                    self.renderer.draw_shadow_rect(x, y, w, h, radius, shadow_depth);
                     
                     // Draw Component
-                    self.renderer.draw_rounded_rect(x, y, w, h, 10.0, color, effect_flags);
+                    self.renderer.draw_rounded_rect(x, y, w, h, radius, color, effect_flags);
                     
                     // Label text? (Not implemented in renderer yet, passing rect is needed)
                 }
                 Value::String(s) => {
                      // Draw Text
-                     self.renderer.draw_text(&s, x, y, [0.0, 0.0, 0.0, 1.0], 24.0); // Black text, size 24
+                     self.renderer.draw_text(s, x, y, [0.0, 0.0, 0.0, 1.0], 24.0 * self.layout_engine.scale_factor); // Black text, size 24 (logical)
                 }
                 _ => {}
             }
         }
+        self.layout_cache = layout_rects;
+
+        // 位置付きの表示 (UI の上に重ねる)
+        let text_size = 24.0 * self.layout_engine.scale_factor;
+        let margin = 16.0 * self.layout_engine.scale_factor;
+        for (anchor, text) in &self.screen_texts {
+            // 全角幅で見積もる
+            let w = text.chars().count() as f32 * text_size;
+            let (x, y) = anchor.place(self.size.width as f32, self.size.height as f32, w, text_size * 1.2, margin);
+            self.renderer.draw_text(text, x, y, [1.0, 1.0, 1.0, 1.0], text_size);
+        }
         
         // Render Particles (on top of UI)
         for p in &self.particles {
@@ -652,7 +864,7 @@ impl State {
             let mut _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -699,8 +911,251 @@ impl State {
 
     
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
-    
-        Ok(())
+    }
+}
+
+/// 描画先として使え、読み戻しもできるテクスチャ
+fn create_target_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Target"),
+        size: wgpu::Extent3d { width: config.width, height: config.height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        view_formats: &[],
+    })
+}
+
+/// テクスチャの内容を CPU に読み戻して RGBA 画像にする (COPY_SRC が必要)
+fn read_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) -> Result<image::RgbaImage, String> {
+    let (width, height) = (texture.width(), texture.height());
+    let bgra = match texture.format() {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        other => return Err(format!("Unsupported texture format for readback: {:?}", other)),
+    };
+    // 行の長さは COPY_BYTES_PER_ROW_ALIGNMENT (256) の倍数でなければならない
+    let unpadded_bytes_per_row = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback Encoder") });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (tx, rx) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        tx.send(result).ok();
+    });
+    device.poll(wgpu::Maintain::Wait);
+    rx.recv()
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("Failed to map readback buffer: {}", e))?;
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    for row in slice.get_mapped_range().chunks(padded_bytes_per_row as usize) {
+        pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+    }
+    buffer.unmap();
+    if bgra {
+        pixels.chunks_mut(4).for_each(|p| p.swap(0, 2));
+    }
+    image::RgbaImage::from_raw(width, height, pixels).ok_or_else(|| "Readback size mismatch".to_string())
+}
+
+/// 間引き対象 (ターゲット, イベント) の登録があれば間隔を確認する。登録がなければ常に true
+fn throttle_allows(throttles: &mut HashMap<(String, String), EventThrottle>, target: &str, event: &str, now_ms: f64) -> bool {
+    match throttles.get_mut(&(target.to_string(), event.to_string())) {
+        Some(throttle) => throttle.try_fire(now_ms),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_click_throttle_fires_once_within_interval() {
+        let mut throttles = HashMap::new();
+        throttles.insert(("Button".to_string(), "click".to_string()), EventThrottle::new(0.5));
+
+        let fired = [0.0, 100.0].iter().filter(|&&t| throttle_allows(&mut throttles, "Button", "click", t)).count();
+        assert_eq!(fired, 1);
+        assert!(throttle_allows(&mut throttles, "Button", "click", 600.0));
+        // 間引き登録のないイベントは素通し
+        assert!(throttle_allows(&mut throttles, "Button", "hover", 600.0));
+    }
+
+    #[test]
+    fn test_request_gpu_without_adapter_is_error() {
+        // バックエンドなしのインスタンスではアダプタが見つからない (GPU のない環境と同じ)
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::empty(),
+            ..Default::default()
+        });
+        let result = pollster::block_on(request_gpu(&instance, None));
+        assert_eq!(result.err(), Some("No GPU adapter available".to_string()));
+    }
+
+    #[test]
+    fn test_read_texture_returns_rendered_pixels() {
+        // GPU (ソフトウェア含む) がない環境では検証できない
+        let instance = wgpu::Instance::default();
+        let Ok((_, device, queue)) = pollster::block_on(request_gpu(&instance, None)) else {
+            return;
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: None,
+            size: wgpu::Extent3d { width: 70, height: 30, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Bgra8Unorm,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::RED), store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let image = read_texture(&device, &queue, &texture).unwrap();
+        assert_eq!(image.dimensions(), (70, 30));
+        assert_eq!(image.get_pixel(69, 29).0, [255, 0, 0, 255]);
+
+        let path = std::env::temp_dir().join("agn_screenshot_test.png");
+        image.save(&path).unwrap();
+        assert_eq!(image::open(&path).unwrap().dimensions(), (70, 30));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_offscreen_renders_red_component() {
+        // GPU (ソフトウェア含む) がない環境では検証できない
+        let Ok(mut state) = pollster::block_on(State::new_offscreen(200, 120)) else {
+            return;
+        };
+        let root = Value::Component {
+            style: "赤い".to_string(),
+            ty: "箱".to_string(),
+            label: Some("Box".to_string()),
+            children: Vec::new(),
+            layout: None,
+            visible: true,
+            enabled: true,
+        };
+        let image = state.capture(Some(&root)).unwrap();
+        assert_eq!(image.dimensions(), (200, 120));
+
+        let (x, y, w, h, _) = state.layout_rects[0].clone();
+        let inside = image.get_pixel((x + w / 2.0) as u32, (y + h / 2.0) as u32).0;
+        assert!(inside[0] > 200 && inside[1] < 150 && inside[2] < 150, "{:?}", inside);
+        // 矩形の外 (右側) は背景色のまま
+        let outside = image.get_pixel((x + w) as u32 + 30, (y + h / 2.0) as u32).0;
+        assert!(outside[0] < 100, "{:?}", outside);
+    }
+
+    #[test]
+    fn test_batched_draw_calls_bounded_by_images() {
+        let Ok(mut state) = pollster::block_on(State::new_offscreen(400, 300)) else {
+            return;
+        };
+        // 50 個の子のうち 5 個が画像 (矩形の間に挟まる)
+        let children: Vec<Value> = (0..50)
+            .map(|i| {
+                if i % 10 == 5 {
+                    Value::Image(format!("image{}.png", i))
+                } else {
+                    Value::Component {
+                        style: "青い".to_string(),
+                        ty: "箱".to_string(),
+                        label: Some(format!("Box{}", i)),
+                        children: Vec::new(),
+                        layout: None,
+                        visible: true,
+                        enabled: true,
+                    }
+                }
+            })
+            .collect();
+        let image_count = children.iter().filter(|c| matches!(c, Value::Image(_))).count();
+        let root = Value::Component {
+            style: "白い".to_string(),
+            ty: "カード".to_string(),
+            label: Some("Feed".to_string()),
+            children,
+            layout: None,
+            visible: true,
+            enabled: true,
+        };
+
+        state.capture(Some(&root)).unwrap();
+        assert!(
+            state.renderer.draw_call_count() <= image_count + 1,
+            "{} draw calls for {} images",
+            state.renderer.draw_call_count(),
+            image_count
+        );
+    }
+
+    #[test]
+    fn test_unsupported_present_mode_falls_back() {
+        use wgpu::PresentMode::*;
+        assert_eq!(choose_present_mode(true, &[Mailbox, Fifo]), Fifo);
+        assert_eq!(choose_present_mode(false, &[Fifo, Mailbox]), Mailbox);
+        assert_eq!(choose_present_mode(false, &[Fifo, Immediate]), Immediate);
+        // vsync なしを要求しても対応していなければ Fifo
+        assert_eq!(choose_present_mode(false, &[Fifo]), Fifo);
+        // サポート一覧に Fifo すらない場合は先頭のモード
+        assert_eq!(choose_present_mode(true, &[Immediate]), Immediate);
+    }
+
+    #[test]
+    fn test_fallback_adapter_when_no_hardware() {
+        let instance = wgpu::Instance::default();
+        let request = |force_fallback_adapter| {
+            pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+                force_fallback_adapter,
+                ..Default::default()
+            }))
+        };
+        // ハードウェア GPU のある環境ではフォールバック経路を検証できない
+        if request(false).is_some() {
+            return;
+        }
+        let fallback_available = request(true).is_some();
+        let kind = pollster::block_on(request_adapter(&instance, None)).map(|(_, kind)| kind);
+        assert_eq!(kind, fallback_available.then_some(AdapterKind::Fallback));
     }
 }
@@ -1,43 +1,308 @@
 //! AGN Interpreter - インタプリタ
 //! ASTを直接実行する（制御構文を含む）
 
-use crate::parser::{Condition, Expr, Program, Statement};
+use crate::lexer::Span;
+use crate::utils::{format_number, Clock, Locale, SystemClock};
+use crate::parser::{Condition, Expr, Program, SearchOrder, SpatialFilter, Statement};
 use crate::symbol_table::{SymbolTable, Value};
+use crate::ai_runtime::{is_image_verb, AiBackend, AiError, AiOptions, AiRuntime};
 use std::sync::Arc;
 use std::sync::Mutex as StdMutex;
 use std::sync::mpsc::Sender;
 
 use crate::graphics::animation::Animation;
 use crate::bridge::{P2PBridge, UIManager};
+use crate::p2p::RankLadder;
 // unused import: SocialTokuEvent
 
 #[derive(Debug, Clone)]
 pub enum RuntimeMessage {
     String(String),
     Animate(Animation),
-    RegisterEvent(String, String, Vec<Animation>),
+    /// (ターゲット, イベント, アニメーション, 間引き間隔 [秒])
+    RegisterEvent(String, String, Vec<Animation>, Option<f64>),
     LoadImage(String, String),
+    /// (ターゲット, 表示文字列) 束縛された変数の変更による文字の差し替え
+    SetText(String, String),
+    /// 現在の画面を PNG として保存する (保存先パス)
+    SaveScreenshot(String),
+    /// 変数名のコンポーネントを画面のルートとして表示する
+    ShowComponent(String),
+    /// (表示文字列, 位置) 画面 の 中央 に 表示する
+    Display(String, ScreenAnchor),
+}
+
+/// 画面上の名前付きの表示位置 (画面 の 中央 / 上部 / 下部 / 左 / 右)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScreenAnchor {
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl ScreenAnchor {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "中央" | "真ん中" | "Center" | "center" => Some(Self::Center),
+            "上部" | "Top" | "top" => Some(Self::Top),
+            "下部" | "Bottom" | "bottom" => Some(Self::Bottom),
+            "左" | "左側" | "Left" | "left" => Some(Self::Left),
+            "右" | "右側" | "Right" | "right" => Some(Self::Right),
+            _ => None,
+        }
+    }
+
+    /// 画面 (width x height) に w x h の要素を置くときの左上座標。margin は画面端からの余白
+    pub fn place(self, width: f32, height: f32, w: f32, h: f32, margin: f32) -> (f32, f32) {
+        let center_x = (width - w) / 2.0;
+        let center_y = (height - h) / 2.0;
+        match self {
+            Self::Center => (center_x, center_y),
+            Self::Top => (center_x, margin),
+            Self::Bottom => (center_x, height - h - margin),
+            Self::Left => (margin, center_y),
+            Self::Right => (width - w - margin, center_y),
+        }
+    }
 }
 
 pub static SCREEN_CHANNEL: StdMutex<Option<Sender<RuntimeMessage>>> = StdMutex::new(None);
 
+/// 算術エラー (ゼロ除算) の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArithmeticMode {
+    /// 値を変更しない (従来の挙動)
+    #[default]
+    Lenient,
+    /// 結果を nil にしてランタイムエラーを報告
+    Strict,
+    /// 結果を NaN にしてランタイムエラーを報告
+    NanSentinel,
+}
+
+/// ランクの計算に使う徳
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankBasis {
+    /// 現在の徳 (従来の挙動)
+    #[default]
+    Balance,
+    /// これまでに得た徳の累計。徳を使ってもランクが下がらない
+    Earned,
+}
+
+/// 文の実行後の制御の流れ (やめる / つぎへ はループまで伝わる)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    Normal,
+    Break,
+    Continue,
+}
+
+/// 条件付きループ (while / の間 繰り返す) の反復回数の上限。無限ループを防ぐ
+const MAX_WHILE_ITERATIONS: usize = 100_000;
+
+/// インタプリタ設定
+#[derive(Debug, Clone)]
+pub struct InterpreterConfig {
+    /// 徳・優先度などの数値表示ロケール
+    pub locale: Locale,
+    /// Webターゲット (wasm32) として実行するか
+    pub web_target: bool,
+    /// ゼロ除算などの算術エラーの扱い
+    pub arithmetic: ArithmeticMode,
+    /// `X.ランク` の称号を決める段位表
+    pub rank_ladder: RankLadder,
+    /// ランクを現在の徳と累計のどちらで決めるか
+    pub rank_basis: RankBasis,
+    /// execute・イベント配送 1回あたりの実行時間の上限 (ミリ秒)。超えたら残りの文を実行せずに中断する
+    pub max_runtime_ms: Option<f64>,
+}
+
+// wasm32 では web_target が true になるため derive できない
+#[allow(clippy::derivable_impls)]
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        Self {
+            locale: Locale::default(),
+            web_target: cfg!(target_arch = "wasm32"),
+            arithmetic: ArithmeticMode::default(),
+            rank_ladder: RankLadder::default(),
+            rank_basis: RankBasis::default(),
+            max_runtime_ms: None,
+        }
+    }
+}
+
+/// 実行時間の予算の消費状況 (外側の入口から実行を始めるたびにリセット)
+#[derive(Debug, Clone, Default)]
+pub struct RunBudget {
+    /// 最も外側の入口 (execute・イベント配送など) を開始した時刻 (実行中でなければ None)
+    pub started_ms: Option<f64>,
+    /// 上限を超えて中断したか
+    pub exceeded: bool,
+    /// 実行中の入口の入れ子の深さ (イベント内から別のイベントを起こした場合など)
+    pub depth: usize,
+}
+
+/// 実行時間の予算の計測区間。破棄されると入口を抜けたものとして扱う
+struct BudgetGuard {
+    budget: Arc<StdMutex<RunBudget>>,
+}
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        let mut budget = self.budget.lock().unwrap();
+        budget.depth = budget.depth.saturating_sub(1);
+        if budget.depth == 0 {
+            budget.started_ms = None;
+        }
+    }
+}
+
+/// イベントの間引き: 前回の発火から interval 以内のイベントを捨てる
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventThrottle {
+    pub interval_ms: f64,
+    pub last_fired_ms: Option<f64>,
+}
+
+impl EventThrottle {
+    pub fn new(interval_secs: f64) -> Self {
+        Self { interval_ms: interval_secs * 1000.0, last_fired_ms: None }
+    }
+
+    /// 発火してよければ時刻を記録して true を返す
+    pub fn try_fire(&mut self, now_ms: f64) -> bool {
+        if let Some(last) = self.last_fired_ms {
+            if now_ms - last < self.interval_ms {
+                return false;
+            }
+        }
+        self.last_fired_ms = Some(now_ms);
+        true
+    }
+}
+
+/// イベント種別 -> (from, to, 本体) の一覧
+type EventListenerMap = std::collections::HashMap<String, Vec<(Option<String>, Option<String>, Vec<Statement>)>>;
+
+/// 保存・復元用のセッション状態 (serialize_session / restore_session)
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct SessionState {
+    variables: std::collections::HashMap<String, Value>,
+    rules: std::collections::HashMap<String, Vec<Statement>>,
+    actions: std::collections::HashMap<String, (Vec<String>, Vec<Statement>)>,
+    /// (ターゲット, イベント, 本体, 間引き間隔 [秒])
+    event_handlers: Vec<(String, String, Vec<Statement>, Option<f64>)>,
+    event_listeners: EventListenerMap,
+}
+
+/// Webターゲットで動作しないネイティブ専用の文であれば、その名前を返す
+pub fn native_only_statement(stmt: &Statement) -> Option<&'static str> {
+    match stmt.unspanned() {
+        // ローカルファイルからの画像読み込みはネイティブウィンドウのみ
+        Statement::LoadAsset { .. } => Some("LoadAsset (読み込む)"),
+        // 画面の保存はネイティブウィンドウの描画結果を読み戻す
+        Statement::SaveScreen { .. } => Some("SaveScreen (保存する)"),
+        _ => None,
+    }
+}
+
+/// 文が値を書き込む変数名 (execute_one の戻り値・並列実行の代入先)
+fn produced_variable(stmt: &Statement) -> Option<&str> {
+    match stmt.unspanned() {
+        Statement::Assignment { target: Expr::Variable(name), .. }
+        | Statement::LoadAsset { target: Expr::Variable(name), .. }
+        | Statement::ComponentDefine { target: Expr::Variable(name), .. }
+        | Statement::BinaryOp { target: Expr::Variable(name), .. }
+        | Statement::VariableUpdate { target: Expr::Variable(name), .. }
+        | Statement::AiOp { result: Expr::Variable(name), .. }
+        | Statement::SpatialSearch { result: Expr::Variable(name), .. } => Some(name.as_str()),
+        Statement::ReturnStatement { .. } => Some("結果"),
+        _ => None,
+    }
+}
+
+/// AI動詞のオプション値を型付きのオプションに変換する
+/// レコードは項目ごとに振り分け、それ以外の値は言語として扱う
+fn ai_options_from_value(value: &Value) -> AiOptions {
+    match value {
+        Value::Record(fields) => AiOptions::from_fields(
+            fields.iter().map(|(k, v)| (k.clone(), v.to_string())),
+        ),
+        Value::Nil => AiOptions::default(),
+        other => AiOptions::language(other.to_string()),
+    }
+}
+
+/// デバッガ用フック: 各ステートメント実行直前に呼ばれる
+pub type DebugHook = Arc<dyn Fn(&Statement, &SymbolTable) + Send + Sync>;
+
+/// ホストが登録する組み込み関数
+pub type Builtin = Arc<dyn Fn(Vec<Value>) -> Value + Send + Sync>;
+
+/// ホストが登録する非同期の組み込み関数 (ネットワーク・ストレージ等)
+pub type AsyncBuiltin =
+    Arc<dyn Fn(Vec<Value>) -> std::pin::Pin<Box<dyn std::future::Future<Output = Value> + Send>> + Send + Sync>;
+
+/// 登録済みの組み込み関数
+#[derive(Clone)]
+pub enum HostFunction {
+    Sync(Builtin),
+    Async(AsyncBuiltin),
+}
+
+/// 並列タスクの取り消しハンドル
+/// ネイティブでは tokio のタスクを中断し、WASMではフラグで結果の受け渡しを止める
+#[derive(Clone)]
+pub struct CancelHandle {
+    cancelled: Arc<std::sync::atomic::AtomicBool>,
+    #[cfg(not(target_arch = "wasm32"))]
+    abort: Option<tokio::task::AbortHandle>,
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, std::sync::atomic::Ordering::SeqCst);
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(abort) = &self.abort {
+            abort.abort();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
-fn spawn_async<F>(future: F)
+fn spawn_async<F>(future: F) -> CancelHandle
 where F: std::future::Future<Output = ()> + Send + 'static {
-    tokio::spawn(future);
+    CancelHandle {
+        cancelled: Arc::new(std::sync::atomic::AtomicBool::new(false)),
+        abort: Some(tokio::spawn(future).abort_handle()),
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
-fn spawn_async<F>(future: F)
+fn spawn_async<F>(future: F) -> CancelHandle
 where F: std::future::Future<Output = ()> + 'static {
     wasm_bindgen_futures::spawn_local(future);
+    CancelHandle { cancelled: Arc::new(std::sync::atomic::AtomicBool::new(false)) }
 }
 
 #[derive(Clone)]
 pub struct Interpreter {
     pub symbol_table: Arc<StdMutex<SymbolTable>>,
     pub context_stack: Arc<StdMutex<Vec<String>>>,
+    /// 実行中の繰り返しの添字 (外側から順)。ループ内のコンポーネント名の接尾辞に使う
+    pub loop_indices: Arc<StdMutex<Vec<usize>>>,
     pub event_handlers: Arc<StdMutex<std::collections::HashMap<(String, String), Vec<Statement>>>>,
+    pub event_throttles: Arc<StdMutex<std::collections::HashMap<(String, String), EventThrottle>>>,
+    /// 変数名 -> 文字を束縛したコンポーネントのラベル
+    pub bindings: Arc<StdMutex<std::collections::HashMap<String, Vec<String>>>>,
     pub rules: Arc<StdMutex<std::collections::HashMap<String, Vec<Statement>>>>,
     pub actions: Arc<StdMutex<std::collections::HashMap<String, (Vec<String>, Vec<Statement>)>>>,
     // Phase 15: Event Listeners (Event -> Vec<Statement>)
@@ -47,6 +312,28 @@ pub struct Interpreter {
     // Phase 18: Bridges
     pub p2p: Arc<dyn P2PBridge>,
     pub ui: Arc<dyn UIManager>,
+
+    // Debugger: ブレークポイント・変数インスペクション用
+    pub on_statement: Option<DebugHook>,
+
+    // ホスト定義の組み込み関数 (呼び出し時に最優先で照合)
+    pub builtins: Arc<StdMutex<std::collections::HashMap<String, HostFunction>>>,
+
+    // 並列実行中の結果 (ハンドル -> 完了通知)
+    pub pending: Arc<StdMutex<std::collections::HashMap<u64, tokio::sync::oneshot::Receiver<Value>>>>,
+    pub next_handle: Arc<std::sync::atomic::AtomicU64>,
+    pub cancel_handles: Arc<StdMutex<std::collections::HashMap<u64, CancelHandle>>>,
+
+    // Source map: 実行中の文の位置と、位置付きランタイムエラー
+    pub current_span: Arc<StdMutex<Option<Span>>>,
+    pub runtime_errors: Arc<StdMutex<Vec<String>>>,
+
+    // 共有状態: クローン時は Arc のコピーのみ (WASMではイベントごとにクローンされる)
+    pub ai: Arc<dyn AiBackend>,
+    pub config: Arc<InterpreterConfig>,
+    /// 実行時間の計測に使う時計 (テストでは MockClock)
+    pub clock: Arc<dyn Clock>,
+    pub run_budget: Arc<StdMutex<RunBudget>>,
 }
 
  impl Interpreter {
@@ -60,12 +347,26 @@ pub struct Interpreter {
         Self {
             symbol_table: Arc::new(StdMutex::new(SymbolTable::new())),
             context_stack: Arc::new(StdMutex::new(Vec::new())),
+            loop_indices: Arc::new(StdMutex::new(Vec::new())),
             rules: Arc::new(StdMutex::new(std::collections::HashMap::new())),
             actions: Arc::new(StdMutex::new(std::collections::HashMap::new())),
             event_listeners: Arc::new(StdMutex::new(std::collections::HashMap::new())),
             event_handlers: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            event_throttles: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            bindings: Arc::new(StdMutex::new(std::collections::HashMap::new())),
             p2p,
             ui,
+            on_statement: None,
+            builtins: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            pending: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            next_handle: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            cancel_handles: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            current_span: Arc::new(StdMutex::new(None)),
+            runtime_errors: Arc::new(StdMutex::new(Vec::new())),
+            ai: Arc::new(AiRuntime::new()),
+            config: Arc::new(InterpreterConfig::default()),
+            clock: Arc::new(SystemClock),
+            run_budget: Arc::new(StdMutex::new(RunBudget::default())),
         }
     }
 
@@ -73,18 +374,158 @@ pub struct Interpreter {
         Self {
             symbol_table,
             context_stack: Arc::new(StdMutex::new(Vec::new())),
+            loop_indices: Arc::new(StdMutex::new(Vec::new())),
             rules: Arc::new(StdMutex::new(std::collections::HashMap::new())),
             actions: Arc::new(StdMutex::new(std::collections::HashMap::new())),
             event_listeners: Arc::new(StdMutex::new(std::collections::HashMap::new())),
             event_handlers: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            event_throttles: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            bindings: Arc::new(StdMutex::new(std::collections::HashMap::new())),
             p2p,
             ui,
+            on_statement: None,
+            builtins: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            pending: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            next_handle: Arc::new(std::sync::atomic::AtomicU64::new(0)),
+            cancel_handles: Arc::new(StdMutex::new(std::collections::HashMap::new())),
+            current_span: Arc::new(StdMutex::new(None)),
+            runtime_errors: Arc::new(StdMutex::new(Vec::new())),
+            ai: Arc::new(AiRuntime::new()),
+            config: Arc::new(InterpreterConfig::default()),
+            clock: Arc::new(SystemClock),
+            run_budget: Arc::new(StdMutex::new(RunBudget::default())),
+        }
+    }
+
+    pub fn with_config(mut self, config: InterpreterConfig) -> Self {
+        self.config = Arc::new(config);
+        self
+    }
+
+    /// 時計を差し替える (テストでは MockClock)
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// AIバックエンドを差し替える (テスト・独自バックエンド用)
+    pub fn with_ai_backend(mut self, ai: Arc<dyn AiBackend>) -> Self {
+        self.ai = ai;
+        self
+    }
+
+    /// 変数の値を取得する (埋め込み先から実行結果を読む用)
+    pub fn get_variable(&self, name: &str) -> Option<Value> {
+        self.symbol_table.lock().unwrap().lookup(name).cloned()
+    }
+
+    /// 変数を設定する (埋め込み先から実行前に入力を渡す用)
+    pub fn set_variable(&self, name: &str, value: Value) {
+        self.symbol_table.lock().unwrap().register(name, value);
+    }
+
+    /// 変数・ルール・アクション・イベントを JSON に保存する (ページ再読み込み対策)
+    /// 並列実行中の値 (Pending) は復元できないため nil として保存する
+    pub fn serialize_session(&self) -> String {
+        let variables = self
+            .symbol_table
+            .lock()
+            .unwrap()
+            .symbols
+            .iter()
+            .map(|(name, value)| {
+                let value = if matches!(value, Value::Pending(_)) { Value::Nil } else { value.clone() };
+                (name.clone(), value)
+            })
+            .collect();
+        let throttles = self.event_throttles.lock().unwrap();
+        let event_handlers = self
+            .event_handlers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((target, event), body)| {
+                let interval = throttles
+                    .get(&(target.clone(), event.clone()))
+                    .map(|t| t.interval_ms / 1000.0);
+                (target.clone(), event.clone(), body.clone(), interval)
+            })
+            .collect();
+        let state = SessionState {
+            variables,
+            rules: self.rules.lock().unwrap().clone(),
+            actions: self.actions.lock().unwrap().clone(),
+            event_handlers,
+            event_listeners: self.event_listeners.lock().unwrap().clone(),
+        };
+        serde_json::to_string(&state).unwrap_or_else(|e| {
+            log::warn!("[Session] Failed to serialize: {}", e);
+            "{}".to_string()
+        })
+    }
+
+    /// serialize_session の結果から状態を復元する (既存の状態は置き換える)
+    pub fn restore_session(&self, data: &str) -> Result<(), String> {
+        let state: SessionState = serde_json::from_str(data).map_err(|e| format!("Invalid session data: {}", e))?;
+
+        self.symbol_table.lock().unwrap().symbols = state.variables;
+        *self.rules.lock().unwrap() = state.rules;
+        *self.actions.lock().unwrap() = state.actions;
+        *self.event_listeners.lock().unwrap() = state.event_listeners;
+
+        let mut handlers = self.event_handlers.lock().unwrap();
+        let mut throttles = self.event_throttles.lock().unwrap();
+        handlers.clear();
+        throttles.clear();
+        for (target, event, body, interval) in state.event_handlers {
+            if let Some(secs) = interval {
+                throttles.insert((target.clone(), event.clone()), EventThrottle::new(secs));
+            }
+            handlers.insert((target, event), body);
         }
+        Ok(())
+    }
+
+    /// 組み込み関数を登録する (例: 倍(n))。同名のアクションや既存の組み込みより優先される
+    pub fn register_builtin(&self, name: &str, builtin: Builtin) {
+        self.builtins.lock().unwrap().insert(name.to_string(), HostFunction::Sync(builtin));
+    }
+
+    /// 非同期の組み込み関数を登録する (呼び出し時に await される)
+    pub fn register_async_builtin(&self, name: &str, builtin: AsyncBuiltin) {
+        self.builtins.lock().unwrap().insert(name.to_string(), HostFunction::Async(builtin));
+    }
+
+    /// 登録済みの組み込み関数を呼び出す (未登録なら None)
+    async fn call_builtin(&self, name: &str, args: &[Expr]) -> Option<Value> {
+        let function = self.builtins.lock().unwrap().get(name).cloned()?;
+        let arg_values = self.eval_args(args).await;
+        Some(match function {
+            HostFunction::Sync(f) => f(arg_values),
+            HostFunction::Async(f) => f(arg_values).await,
+        })
+    }
+
+    /// デバッグフックを設定する（未設定時はオーバーヘッドなし）
+    pub fn set_debug_hook(&mut self, hook: DebugHook) {
+        self.on_statement = Some(hook);
+    }
+
+    /// ランタイムエラーを実行中のソース位置付きで記録する
+    fn report_error(&self, message: String) {
+        let located = match *self.current_span.lock().unwrap() {
+            Some(span) => format!("{}: {}", span, message),
+            None => message,
+        };
+        log::warn!("[Runtime] {}", located);
+        self.ui.error(&located);
+        self.runtime_errors.lock().unwrap().push(located);
     }
 
     async fn eval_expr(&self, expr: &Expr) -> Value {
         match expr {
             Expr::Number(n) => Value::Number(*n),
+            Expr::Bool(b) => Value::Bool(*b),
             Expr::String(s) => Value::String(s.clone()),
             Expr::Variable(name) => {
                 let table = self.symbol_table.lock().unwrap();
@@ -97,10 +538,23 @@ pub struct Interpreter {
             Expr::Duration { value, unit } => {
                 Value::String(format!("{}{}", value, unit))
             }
+            Expr::Percent { value } => Value::Number(value / 100.0),
             // AGN 2.0: Property Access (Stub)
             // AGN 2.0: Property Access
             Expr::PropertyAccess { target, property } => {
-                let target_val = Box::pin(self.eval_expr(&target)).await;
+                // ハンドラ内の self / これ はイベント対象のコンポーネント
+                let target_val = if matches!(target.as_ref(), Expr::Variable(n) if n == "self" || n == "これ") {
+                    let Some(current) = self.context_stack.lock().unwrap().last().cloned() else {
+                        self.report_error(format!("'{}' used outside of an event handler", property));
+                        return Value::Nil;
+                    };
+                    if let Some(value) = self.component_property(&current, property) {
+                        return value;
+                    }
+                    Value::String(current)
+                } else {
+                    Box::pin(self.eval_expr(&target)).await
+                };
                 
                 // Case 1: Bond property access (bond(A, B).level)
                 if let Value::Bond(rel) = &target_val {
@@ -113,7 +567,15 @@ pub struct Interpreter {
                     }
                 }
 
-                // Case 2: ID-based property access (User.Toku, Post.Author)
+                // Case 2: Record field access (ランキング(3) の要素.徳)
+                if let Value::Record(_) = &target_val {
+                    return target_val.field(property).cloned().unwrap_or_else(|| {
+                        self.report_error(format!("Unknown record field: {}", property));
+                        Value::Nil
+                    });
+                }
+
+                // Case 3: ID-based property access (User.Toku, Post.Author)
                 if let Value::String(id) = target_val {
                     // Try as Feed Event first
                     if let Some(event) = self.p2p.get_feed_event(&id).await {
@@ -131,30 +593,36 @@ pub struct Interpreter {
                             Value::Number(score as f64)
                         }
                         "rssi" | "信号強度" | "RSSI" => {
-                            // Simulated RSSI for verification
-                            Value::Number(-65.0)
+                            // 検出済みのピアなら平滑化したRSSI (未検出時はシミュレーション値)
+                            match self.p2p.find_peer(&id).await {
+                                Some(peer) => Value::Number(peer.smoothed_rssi()),
+                                None => Value::Number(-65.0),
+                            }
                         }
                         "distance" | "距離" => {
-                            // Simulated Distance
-                            Value::Number(5.0)
+                            match self.p2p.find_peer(&id).await {
+                                Some(peer) => Value::Number(peer.estimated_distance),
+                                None => Value::Number(5.0), // Simulated Distance
+                            }
                         }
                         "duration" | "接触時間" => {
                             // Simulated Contact Duration
                             Value::Number(10.0)
                         }
                         "ランク" | "Rank" | "rank" => {
-                            // Dummy logic based on score
-                            let score = self.p2p.get_toku(&id);
-                            let rank = if score > 1000 { "徳人" } else { "一般" };
-                            Value::String(rank.to_string())
+                            let score = match self.config.rank_basis {
+                                RankBasis::Balance => self.p2p.get_toku(&id),
+                                RankBasis::Earned => self.p2p.toku_earned(&id),
+                            };
+                            Value::String(self.config.rank_ladder.rank_for(score).to_string())
                         }
                         _ => {
-                            log::warn!("Unknown property: {}", property);
+                            self.report_error(format!("Unknown property: {}", property));
                             Value::Nil
                         },
                     }
                 } else {
-                    log::warn!("Target not found or not ID: {:?}", target_val);
+                    self.report_error(format!("Target not found or not ID: {:?}", target_val));
                     Value::Nil
                 }
             }
@@ -170,8 +638,23 @@ pub struct Interpreter {
                     Value::Nil
                 }
             }
+            // 呼び出し引数以外での展開は値そのもの
+            Expr::Spread(inner) => Box::pin(self.eval_expr(inner)).await,
+            Expr::Await(inner) => Box::pin(self.await_expr(inner)).await,
+            Expr::Record(fields) => {
+                let mut values = Vec::with_capacity(fields.len());
+                for (key, value) in fields {
+                    values.push((key.clone(), Box::pin(self.eval_expr(value)).await));
+                }
+                Value::Record(values)
+            }
             // AGN 2.0: Call
             Expr::Call { name, args } => {
+                // ホスト定義の組み込み関数
+                if let Some(value) = self.call_builtin(name, args).await {
+                    return value;
+                }
+
                 // Check if it's an AI verb
                 let is_ai_verb = match name.as_str() {
                     "要約する" | "summarize" | "翻訳する" | "translate" | "想像する" | "imagine" => true,
@@ -179,16 +662,12 @@ pub struct Interpreter {
                 };
 
                 if is_ai_verb {
-                    let mut arg_vals = Vec::new();
-                    for arg in args {
-                        arg_vals.push(Box::pin(self.eval_expr(arg)).await);
-                    }
+                    let arg_vals = self.eval_args(args).await;
                     let input = arg_vals.get(0).map(|v| v.to_string()).unwrap_or_default();
-                    let option = arg_vals.get(1).map(|v| v.to_string());
+                    let options = arg_vals.get(1).map(ai_options_from_value).unwrap_or_default();
                     
-                    let runtime = crate::ai_runtime::AiRuntime::new();
-                    match runtime.execute_verb(name, &input, option).await {
-                        Ok(result) => Value::String(result),
+                    match self.run_ai_verb(name, &input, &options).await {
+                        Ok(result) => result,
                         Err(e) => {
                             log::error!("[AI Error] {}", e);
                             Value::Nil
@@ -206,6 +685,22 @@ pub struct Interpreter {
                              Value::Bond(rel)
                         } else { Value::Nil }
                     } else { Value::Nil }
+                } else if name == "ランキング" || name == "ranking" {
+                    // 徳ランキング: [{名前, 徳}, ...] をスコア降順で返す
+                    let n = match args.first() {
+                        Some(arg) => match Box::pin(self.eval_expr(arg)).await {
+                            Value::Number(n) if n > 0.0 => n as usize,
+                            _ => 0,
+                        },
+                        None => 10,
+                    };
+                    let entries = self.p2p.top_toku(n).into_iter().map(|(id, score)| {
+                        Value::Record(vec![
+                            ("名前".to_string(), Value::String(id)),
+                            ("徳".to_string(), Value::Number(score as f64)),
+                        ])
+                    }).collect();
+                    Value::List(entries)
                 } else if name == "set_status" || name == "ステータスを設定する" {
                     let from = args.get(0).map(|a| Box::pin(self.eval_expr(a)));
                     let to = args.get(1).map(|a| Box::pin(self.eval_expr(a)));
@@ -229,32 +724,26 @@ pub struct Interpreter {
     async fn eval_condition(&self, condition: &Condition) -> bool {
         match condition {
             Condition::Equals(left, right) => {
-                let left_val = self.eval_expr(left).await;
-                let right_val = self.eval_expr(right).await;
-                match (left_val, right_val) {
-                    (Value::Number(a), Value::Number(b)) => a == b,
-                    (Value::String(a), Value::String(b)) => a == b,
-                    _ => false,
-                }
+                self.eval_expr(left).await == self.eval_expr(right).await
             }
             Condition::GreaterThan(left, right) => {
-                let left_val = self.eval_expr(left).await;
-                let right_val = self.eval_expr(right).await;
-                match (left_val, right_val) {
-                    (Value::Number(a), Value::Number(b)) => {
-                        log::info!("Condition: {} > {} = {}", a, b, a > b);
-                        a > b
-                    },
-                    _ => false,
-                }
+                self.eval_expr(left).await > self.eval_expr(right).await
             }
             Condition::LessThan(left, right) => {
-                let left_val = self.eval_expr(left).await;
-                let right_val = self.eval_expr(right).await;
-                match (left_val, right_val) {
-                    (Value::Number(a), Value::Number(b)) => a < b,
-                    _ => false,
-                }
+                self.eval_expr(left).await < self.eval_expr(right).await
+            }
+            Condition::GreaterOrEqual(left, right) => {
+                self.eval_expr(left).await >= self.eval_expr(right).await
+            }
+            Condition::NotEquals(left, right) => {
+                self.eval_expr(left).await != self.eval_expr(right).await
+            }
+            // 左辺で結果が決まれば右辺は評価しない
+            Condition::And(left, right) => {
+                Box::pin(self.eval_condition(left)).await && Box::pin(self.eval_condition(right)).await
+            }
+            Condition::Or(left, right) => {
+                Box::pin(self.eval_condition(left)).await || Box::pin(self.eval_condition(right)).await
             }
             // Eeyo: 空間条件（後方互換性のためにプレースホルダー）
             Condition::Nearer(_) | Condition::Farther(_) => {
@@ -274,27 +763,176 @@ pub struct Interpreter {
                     false
                 }
             }
-            Condition::Truthy(expr) => {
-                let val = self.eval_expr(expr).await;
-                match val {
-                    Value::Number(n) => n != 0.0,
-                    Value::String(s) => !s.is_empty(),
-                    Value::Bond(rel) => rel.has_bond(),
-                    Value::Component { .. } => true,
-                    Value::Image(_) => true,
-                    Value::Nil => false,
-                }
-            }
+            Condition::Truthy(expr) => self.eval_expr(expr).await.is_truthy(),
         }
     }
 
     pub async fn execute(&self, program: &Program) {
+        let _budget = self.start_budget();
         self.execute_statements(&program.statements).await;
     }
 
+    /// 公開の入口で実行時間の計測を始める。入れ子の入口では外側の開始時刻を引き継ぐ
+    fn start_budget(&self) -> BudgetGuard {
+        let mut budget = self.run_budget.lock().unwrap();
+        if budget.depth == 0 {
+            *budget = RunBudget { started_ms: Some(self.clock.now_ms()), exceeded: false, depth: 0 };
+        }
+        budget.depth += 1;
+        BudgetGuard { budget: self.run_budget.clone() }
+    }
+
+    /// 実行時間の上限を超えたか。超えた時点で一度だけエラーを報告する
+    fn runtime_budget_exceeded(&self) -> bool {
+        let Some(limit) = self.config.max_runtime_ms else { return false };
+        let mut budget = self.run_budget.lock().unwrap();
+        if budget.exceeded {
+            return true;
+        }
+        let Some(started) = budget.started_ms else { return false };
+        let elapsed = self.clock.now_ms() - started;
+        if elapsed <= limit {
+            return false;
+        }
+        budget.exceeded = true;
+        drop(budget);
+        self.report_error(format!("Runtime budget of {} ms exceeded after {} ms; execution aborted", limit, elapsed));
+        true
+    }
+
+    /// 1文だけを現在の状態に対して実行し、代入先などの生成値を返す (REPL・エディタ連携用)
+    pub async fn execute_one(&self, stmt: &Statement) -> Option<Value> {
+        let _budget = self.start_budget();
+        self.execute_statements(std::slice::from_ref(stmt)).await;
+        self.get_variable(produced_variable(stmt)?)
+    }
+
+    /// 文を並列に実行する。代入先には Pending を登録し、完了時の値は 待つ で受け取る
+    fn spawn_parallel(&self, inner: &Statement) {
+        let inner = inner.clone();
+        let Some(name) = produced_variable(&inner).map(str::to_string) else {
+            // 代入先がなければ結果を待つ必要はない (fire and forget)
+            let interpreter_clone = self.clone();
+            spawn_async(async move {
+                interpreter_clone.execute_statements(std::slice::from_ref(&inner)).await;
+            });
+            return;
+        };
+
+        // 並列タスクは現在の変数のスナップショット上で実行する
+        let snapshot = SymbolTable { symbols: self.symbol_table.lock().unwrap().symbols.clone() };
+        let worker = self.fork_with_table(Arc::new(StdMutex::new(snapshot)));
+        let handle = self.next_handle.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.pending.lock().unwrap().insert(handle, rx);
+        self.symbol_table.lock().unwrap().register(&name, Value::Pending(handle));
+
+        // WASMでは中断できないため、取り消し済みなら結果を渡さない
+        let cancelled = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let flag = cancelled.clone();
+        let mut cancel = spawn_async(async move {
+            let value = worker.execute_one(&inner).await.unwrap_or(Value::Nil);
+            if !flag.load(std::sync::atomic::Ordering::SeqCst) {
+                let _ = tx.send(value);
+            }
+        });
+        cancel.cancelled = cancelled;
+        self.cancel_handles.lock().unwrap().insert(handle, cancel);
+    }
+
+    /// 並列実行を取り消す。以後 待つ と Cancelled が返る
+    fn cancel_pending(&self, value: Value) {
+        let Value::Pending(handle) = value else {
+            self.report_error(format!("Cannot cancel non-pending value: {}", value));
+            return;
+        };
+        match self.cancel_handles.lock().unwrap().remove(&handle) {
+            Some(cancel) => cancel.cancel(),
+            None => log::debug!("[Runtime] Pending #{} already finished", handle),
+        }
+    }
+
+    /// 式の値が Pending なら完了を待って解決値を返す (Pending 以外はそのまま)
+    /// 変数の場合は解決値で置き換えるため、同じ変数を何度待ってもよい
+    async fn await_expr(&self, expr: &Expr) -> Value {
+        let value = self.eval_expr(expr).await;
+        let Value::Pending(handle) = value else {
+            return value;
+        };
+        let receiver = self.pending.lock().unwrap().remove(&handle);
+        let resolved = match receiver {
+            // 送信側が結果を渡さずに破棄された = 取り消し
+            Some(rx) => {
+                let resolved = rx.await.unwrap_or(Value::Cancelled);
+                self.cancel_handles.lock().unwrap().remove(&handle);
+                resolved
+            }
+            None => {
+                self.report_error(format!("Pending value #{} was already awaited", handle));
+                Value::Nil
+            }
+        };
+        if let Expr::Variable(name) = expr {
+            self.symbol_table.lock().unwrap().register(name, resolved.clone());
+        }
+        resolved
+    }
+
+    /// ループ内で定義するコンポーネントの名前に添字を付ける (項目 → 項目[0], 入れ子は 項目[0][1])
+    fn loop_component_name(&self, name: &str) -> String {
+        let indices = self.loop_indices.lock().unwrap();
+        indices.iter().fold(name.to_string(), |acc, i| format!("{}[{}]", acc, i))
+    }
+
+    /// 空間検索フィルターの比較値 (右辺) を評価済みのリテラルに置き換える
+    async fn resolve_spatial_filter(&self, filter: &SpatialFilter) -> SpatialFilter {
+        let resolve = |value: Value, original: &Expr| match value {
+            Value::Number(n) => Expr::Number(n),
+            Value::String(s) => Expr::String(s),
+            _ => original.clone(),
+        };
+        let condition = match &filter.condition {
+            Condition::GreaterThan(l, r) => Condition::GreaterThan(l.clone(), resolve(self.eval_expr(r).await, r)),
+            Condition::LessThan(l, r) => Condition::LessThan(l.clone(), resolve(self.eval_expr(r).await, r)),
+            Condition::GreaterOrEqual(l, r) => Condition::GreaterOrEqual(l.clone(), resolve(self.eval_expr(r).await, r)),
+            other => other.clone(),
+        };
+        SpatialFilter { field: filter.field.clone(), condition }
+    }
+
+    /// ラベルで指定したコンポーネントのフィールドを読む (該当しなければ None)
+    fn component_property(&self, label: &str, property: &str) -> Option<Value> {
+        let table = self.symbol_table.lock().unwrap();
+        let component = table.symbols.values().find_map(|v| v.find_component(label))?;
+        let Value::Component { style, ty, label, children, visible, enabled, .. } = component else {
+            return None;
+        };
+        match property {
+            "色" | "スタイル" | "style" => Some(Value::String(style.clone())),
+            "種類" | "type" => Some(Value::String(ty.clone())),
+            "名前" | "label" => Some(label.clone().map(Value::String).unwrap_or(Value::Nil)),
+            "文字" | "text" => Some(children.iter().find(|c| matches!(c, Value::String(_))).cloned().unwrap_or(Value::Nil)),
+            "表示" | "visible" => Some(Value::Bool(*visible)),
+            "有効" | "enabled" => Some(Value::Bool(*enabled)),
+            _ => None,
+        }
+    }
+
     async fn resolve_target_id(&self, expr: &Expr) -> String {
         match expr {
-            Expr::Variable(name) => name.clone(),
+            // これら: 現在のブロックの親 (ブロック外ではエラーを報告し、名前のまま返す)
+            Expr::Variable(name) if name == "これら" => {
+                let parent = self.context_stack.lock().unwrap().last().cloned();
+                parent.unwrap_or_else(|| {
+                    self.report_error("'これら' used outside of block".to_string());
+                    name.clone()
+                })
+            }
+            // ループ内で定義したコンポーネントは添字付きラベルを指す
+            Expr::Variable(name) => match self.symbol_table.lock().unwrap().lookup(name) {
+                Some(Value::Component { label: Some(label), .. }) => label.clone(),
+                _ => name.clone(),
+            },
             Expr::PropertyAccess { .. } | Expr::Bond(_, _) => {
                 let val = self.eval_expr(expr).await;
                 match val {
@@ -306,10 +944,34 @@ pub struct Interpreter {
         }
     }
 
-    pub async fn execute_statements(&self, statements: &[Statement]) {
+    pub async fn execute_statements(&self, statements: &[Statement]) -> Flow {
         // let mut handles = Vec::new();
 
         for stmt in statements {
+            let stmt = match stmt {
+                Statement::Spanned { span, inner } => {
+                    *self.current_span.lock().unwrap() = Some(*span);
+                    inner.unspanned()
+                }
+                other => other,
+            };
+
+            if self.runtime_budget_exceeded() {
+                return Flow::Normal;
+            }
+
+            if let Some(hook) = &self.on_statement {
+                let table = self.symbol_table.lock().unwrap();
+                hook(stmt, &table);
+            }
+
+            if self.config.web_target {
+                if let Some(name) = native_only_statement(stmt) {
+                    self.report_error(format!("{} is not supported on web target", name));
+                    continue;
+                }
+            }
+
             match stmt {
                 Statement::Assignment { target, value } => {
                     let val = self.eval_expr(value).await;
@@ -329,10 +991,10 @@ pub struct Interpreter {
                                      let mut table = self.symbol_table.lock().unwrap();
                                      if let Some(Value::Component { children: _, .. }) = table.lookup(parent_name).cloned().as_mut() {
                                           // Update parent
-                                          let parent_val = table.get_value(parent_name);
-                                          if let Value::Component { style, ty, label, mut children, layout } = parent_val {
+                                          let mut parent_val = table.get_value(parent_name);
+                                          if let Value::Component { children, .. } = &mut parent_val {
                                               children.push(val); // Add copy of value
-                                              table.update(parent_name, Value::Component { style, ty, label, children, layout });
+                                              table.update(parent_name, parent_val);
                                           }
                                      }
                                  }
@@ -354,24 +1016,31 @@ pub struct Interpreter {
                             }
                         }
                         _ => {
-                            log::warn!("Assignment to unsupported target type: {:?}", target);
+                            self.report_error(format!("Assignment to unsupported target type: {:?}", target));
                         }
                     }
                 }
                 
                 Statement::ComponentDefine { target, style, component } => {
                      if let Expr::Variable(name) = target {
+                         let unique = self.loop_component_name(name);
                          let mut table = self.symbol_table.lock().unwrap();
 
                          let comp_val = Value::Component { 
                              style: style.clone(), 
                              ty: component.clone(), 
-                             label: Some(name.clone()),
+                             label: Some(unique.clone()),
                              children: Vec::new(),
                              layout: None,
+                             visible: true,
+                             enabled: true,
                          };
                          
                          table.register(&name, comp_val.clone());
+                         if unique != *name {
+                             // ループ内: 添字付きの名前でも参照できるようにする (名前単体は最新の要素)
+                             table.register(&unique, comp_val.clone());
+                         }
                          
                          // Check context stack to see if we are inside a block
                          drop(table); // unlock value table before locking stack
@@ -380,10 +1049,10 @@ pub struct Interpreter {
                          if let Some(parent_name) = stack.last() {
                              let mut table = self.symbol_table.lock().unwrap();
                                   if let Some(Value::Component { children: _, .. }) = table.lookup(parent_name).cloned().as_mut() {
-                                       let parent_val = table.get_value(parent_name);
-                                       if let Value::Component { style, ty, label, mut children, layout } = parent_val {
+                                       let mut parent_val = table.get_value(parent_name);
+                                       if let Value::Component { children, .. } = &mut parent_val {
                                            children.push(comp_val);
-                                           table.update(parent_name, Value::Component { style, ty, label, children, layout });
+                                           table.update(parent_name, parent_val);
                                        }
                                   }
                          }
@@ -400,29 +1069,20 @@ pub struct Interpreter {
                     }
                     
                     // Execute body
-                    Box::pin(self.execute_statements(&body)).await;
+                    let flow = Box::pin(self.execute_statements(&body)).await;
                     
                     // Pop stack
                     {
                         let mut stack = self.context_stack.lock().unwrap();
                         stack.pop();
                     }
+                    if flow != Flow::Normal {
+                        return flow;
+                    }
                 }
                 Statement::Layout { target, direction } => {
-                    let target_id = self.resolve_target_id(target).await;
-                    // Set layout on target. 
-                    // If target is "これら", use current context (parent).
-                    let target_name = if target_id == "これら" {
-                        let stack = self.context_stack.lock().unwrap();
-                        if let Some(parent) = stack.last() {
-                            parent.clone()
-                        } else {
-                            eprintln!("Error: 'これら' used outside of block");
-                            continue;
-                        }
-                    } else {
-                        target_id
-                    };
+                    // Set layout on target (これら は resolve_target_id で親に解決済み)
+                    let target_name = self.resolve_target_id(target).await;
                     
                     let dir_str = match direction {
                         crate::parser::LayoutDirection::Vertical => "vertical".to_string(),
@@ -430,11 +1090,10 @@ pub struct Interpreter {
                     };
                     
                     let mut table = self.symbol_table.lock().unwrap();
-                    let val = table.get_value(&target_name);
-                    if let Value::Component { style, ty, label, children, .. } = val {
-                        table.update(&target_name, Value::Component { 
-                            style, ty, label, children, layout: Some(dir_str) 
-                        });
+                    let mut val = table.get_value(&target_name);
+                    if let Value::Component { layout, .. } = &mut val {
+                        *layout = Some(dir_str);
+                        table.update(&target_name, val);
                     }
                 }
                 Statement::LoadAsset { target, path } => {
@@ -443,12 +1102,12 @@ pub struct Interpreter {
                          let path_str = match path_val {
                              Value::String(s) => s,
                              _ => {
-                                 eprintln!("Error: Asset path must be a string");
+                                 self.ui.error("Error: Asset path must be a string");
                                  continue;
                              }
                          };
                          
-                         println!("[System] Loading asset: {}", path_str);
+                         self.ui.print(&format!("[System] Loading asset: {}", path_str));
                          // Send to Native Window
                          self.ui.send_runtime_message(RuntimeMessage::LoadImage(name.clone(), path_str.clone()));
                          // Keep Value::Image for SymbolTable if needed
@@ -460,39 +1119,82 @@ pub struct Interpreter {
                          drop(table);
                      }
                 }
+                Statement::SaveScreen { path } => {
+                    match self.eval_expr(path).await {
+                        Value::String(path) => {
+                            self.ui.send_runtime_message(RuntimeMessage::SaveScreenshot(path));
+                        }
+                        other => self.report_error(format!("Screenshot path must be a string, got {}", other)),
+                    }
+                }
+                Statement::Log { value, channel } => {
+                    let val = self.eval_expr(value).await;
+                    log::debug!("[Log:{}] {}", channel, val);
+                    self.ui.log(channel, &val.to_string());
+                }
                 Statement::BinaryOp { target, operand, verb } => {
                     let op_val = self.eval_expr(operand).await;
                     
                     match target {
                         Expr::Variable(name) => {
                             // Handle "Screen" Display (e.g. "MainButton を 画面 に 表示する")
-                            if (name == "Screen" || name == "Screen.Center") && verb == "表示する" {
-                                 log::info!("[Output] {}", op_val);
-                                 self.ui.send_runtime_message(RuntimeMessage::String(op_val.to_string()));
+                            if name == "Screen" && verb == "表示する" {
+                                 self.show_on_screen(operand, op_val);
+                                 continue;
+                            }
+                            // 画面 の 中央 に 表示する
+                            if let Some(position) = name.strip_prefix("Screen.").filter(|_| verb == "表示する") {
+                                 match ScreenAnchor::from_name(position) {
+                                     Some(anchor) => {
+                                         log::info!("[Output:{:?}] {}", anchor, op_val);
+                                         self.ui.send_runtime_message(RuntimeMessage::Display(op_val.to_string(), anchor));
+                                     }
+                                     None => {
+                                         self.report_error(format!("Unknown screen position '{}'", position));
+                                         self.show_on_screen(operand, op_val);
+                                     }
+                                 }
                                  continue;
                             }
 
                             let mut table = self.symbol_table.lock().unwrap();
-                            // Numeric Operations
-                            if let Some(Value::Number(current)) = table.lookup(name).cloned() {
-                                if let Value::Number(op_num) = op_val {
+                            // Numeric Operations (真偽値は 1/0 として計算する)
+                            if let Some(current) = table.lookup(name).and_then(Value::as_number) {
+                                if let Some(op_num) = op_val.as_number() {
                                      let result = match verb.as_str() {
-                                        "足す" | "加算する" | "増やす" => current + op_num,
-                                        "引く" | "減らす" => current - op_num,
-                                        "掛ける" => current * op_num,
-                                        "割る" => if op_num != 0.0 { current / op_num } else { current },
-                                        _ => current,
+                                        "足す" | "加算する" | "増やす" => Value::Number(current + op_num),
+                                        "引く" | "減らす" => Value::Number(current - op_num),
+                                        "掛ける" => Value::Number(current * op_num),
+                                        "割る" if op_num == 0.0 => match self.config.arithmetic {
+                                            ArithmeticMode::Lenient => Value::Number(current),
+                                            ArithmeticMode::Strict => {
+                                                self.report_error(format!("Division by zero: {} を 0 で割る", name));
+                                                Value::Nil
+                                            }
+                                            ArithmeticMode::NanSentinel => {
+                                                self.report_error(format!("Division by zero: {} を 0 で割る", name));
+                                                Value::Number(f64::NAN)
+                                            }
+                                        },
+                                        "割る" => Value::Number(current / op_num),
+                                        _ => Value::Number(current),
                                      };
-                                     table.update(name, Value::Number(result));
+                                     table.update(name, result);
+                                }
+                            }
+                            // String Concatenation (数値は表示形式で連結する)
+                            else if let Some(Value::String(current)) = table.lookup(name).cloned() {
+                                if matches!(verb.as_str(), "足す" | "加算する") && matches!(op_val, Value::String(_) | Value::Number(_)) {
+                                    table.update(name, Value::String(format!("{}{}", current, op_val)));
                                 }
                             }
                             // Component Operations (e.g. "つなぐ")
                             else if let Some(Value::Component { .. }) = table.lookup(name).cloned() {
                                 if verb == "つなぐ" {
-                                    let parent_val = table.get_value(name);
-                                    if let Value::Component { style, ty, label, mut children, layout } = parent_val {
+                                    let mut parent_val = table.get_value(name);
+                                    if let Value::Component { children, .. } = &mut parent_val {
                                         children.push(op_val.clone());
-                                        table.update(name, Value::Component { style, ty, label, children, layout });
+                                        table.update(name, parent_val);
                                     }
                                 }
                             }
@@ -528,15 +1230,15 @@ pub struct Interpreter {
                                 let num_val = if let Value::Number(n) = op_val { Some(n) } else { None };
                                 if verb == "深くする" || verb == "増やす" || verb == "deepen" || verb == "increase" {
                                     if let Some(n) = num_val {
-                                        self.p2p.deepen_bond(&l, &r, n as u32);
+                                        self.deepen_bond(&l, &r, n as u32).await;
                                     } else {
-                                        self.p2p.deepen_bond(&l, &r, 1); // default 1
+                                        self.deepen_bond(&l, &r, 1).await; // default 1
                                     }
                                 }
                             }
                         }
                         _ => {
-                            log::warn!("BinaryOp on unsupported target type: {:?}", target);
+                            self.report_error(format!("BinaryOp on unsupported target type: {:?}", target));
                         }
                     }
                 }
@@ -556,22 +1258,97 @@ pub struct Interpreter {
                     // Actually, we don't need to track handles for basic async op unless we join.
                     // For now, fire and forget.
                 }
+                Statement::Parallel { inner } => {
+                    self.spawn_parallel(inner);
+                }
+                Statement::Await { target } => {
+                    self.await_expr(target).await;
+                }
+                Statement::Cancel { target } => {
+                    let value = self.eval_expr(target).await;
+                    self.cancel_pending(value);
+                }
+                Statement::SetVisibility { target, visible } => {
+                    let target_id = self.resolve_target_id(target).await;
+                    let visible = *visible;
+                    let updated = self.symbol_table.lock().unwrap().update_components(&target_id, &|c| {
+                        if let Value::Component { visible: v, .. } = c { *v = visible; }
+                    });
+                    if updated == 0 {
+                        self.report_error(format!("Component not found: {}", target_id));
+                    }
+                }
+                Statement::Bind { target, property, variable } => {
+                    if property != "文字" && property != "text" {
+                        self.report_error(format!("Unsupported binding property: {}", property));
+                        continue;
+                    }
+                    let target_id = self.resolve_target_id(target).await;
+                    self.bindings.lock().unwrap().entry(variable.clone()).or_default().push(target_id);
+                    self.refresh_bindings(variable);
+                }
+                Statement::SetEnabled { target, enabled } => {
+                    let target_id = self.resolve_target_id(target).await;
+                    let enabled = *enabled;
+                    let updated = self.symbol_table.lock().unwrap().update_components(&target_id, &|c| {
+                        if let Value::Component { enabled: e, .. } = c { *e = enabled; }
+                    });
+                    if updated == 0 {
+                        self.report_error(format!("Component not found: {}", target_id));
+                    }
+                }
                 Statement::IfStatement { condition, then_block, else_block } => {
                     let cond_result = self.eval_condition(condition).await;
-                    if cond_result {
-                        Box::pin(self.execute_statements(then_block)).await;
+                    let flow = if cond_result {
+                        Box::pin(self.execute_statements(then_block)).await
                     } else if let Some(else_stmts) = else_block {
-                        Box::pin(self.execute_statements(else_stmts)).await;
+                        Box::pin(self.execute_statements(else_stmts)).await
+                    } else {
+                        Flow::Normal
+                    };
+                    if flow != Flow::Normal {
+                        return flow;
                     }
                 }
                 Statement::RepeatStatement { count, body } => {
                     let count_val = self.eval_expr(count).await;
                     if let Value::Number(n) = count_val {
                         let iterations = n as usize;
-                        for _ in 0..iterations {
-                            Box::pin(self.execute_statements(body)).await;
+                        self.loop_indices.lock().unwrap().push(0);
+                        for i in 0..iterations {
+                            if self.runtime_budget_exceeded() {
+                                break;
+                            }
+                            if let Some(index) = self.loop_indices.lock().unwrap().last_mut() {
+                                *index = i;
+                            }
+                            if Box::pin(self.execute_statements(body)).await == Flow::Break {
+                                break;
+                            }
+                        }
+                        self.loop_indices.lock().unwrap().pop();
+                    }
+                }
+                Statement::WhileStatement { condition, body } => {
+                    let mut iterations = 0;
+                    self.loop_indices.lock().unwrap().push(0);
+                    while self.eval_condition(condition).await {
+                        if self.runtime_budget_exceeded() {
+                            break;
+                        }
+                        if iterations == MAX_WHILE_ITERATIONS {
+                            log::warn!("while loop stopped after {} iterations (possible infinite loop)", MAX_WHILE_ITERATIONS);
+                            break;
+                        }
+                        if let Some(index) = self.loop_indices.lock().unwrap().last_mut() {
+                            *index = iterations;
+                        }
+                        iterations += 1;
+                        if Box::pin(self.execute_statements(body)).await == Flow::Break {
+                            break;
                         }
                     }
+                    self.loop_indices.lock().unwrap().pop();
                 }
                 Statement::AiOp { result, input, verb, options } => {
                     let input_val = self.eval_expr(input).await;
@@ -581,21 +1358,18 @@ pub struct Interpreter {
                         _ => String::new(),
                     };
                     
-                    let option_val = if let Some(opt_expr) = options {
-                        let val = self.eval_expr(opt_expr).await;
-                        Some(val.to_string())
-                    } else {
-                        None
+                    let ai_options = match options {
+                        Some(opt_expr) => ai_options_from_value(&self.eval_expr(opt_expr).await),
+                        None => AiOptions::default(),
                     };
 
-                    let runtime = crate::ai_runtime::AiRuntime::new();
                     let result_id = self.resolve_target_id(result).await;
 
-                    match runtime.execute_verb(verb, &input_str, option_val).await {
+                    match self.run_ai_verb(verb, &input_str, &ai_options).await {
                         Ok(ai_result) => {
                             log::info!("[AI] {} result: {}", verb, &ai_result);
                             let mut table = self.symbol_table.lock().unwrap();
-                            table.register(&result_id, Value::String(ai_result));
+                            table.register(&result_id, ai_result);
                         }
                         Err(e) => {
                             log::error!("[AI Error] {}: {}", verb, e);
@@ -606,10 +1380,7 @@ pub struct Interpreter {
                 }
                 Statement::ScreenOp { operand } => {
                     let val = self.eval_expr(operand).await;
-                    log::info!("[Output] {}", val);
-                    if let Value::String(s) = val {
-                         self.ui.send_runtime_message(RuntimeMessage::String(s));
-                    }
+                    self.show_on_screen(operand, val);
                 }
                 Statement::DelayStatement { duration, body } => {
                     let duration_val = self.eval_expr(duration).await;
@@ -618,21 +1389,32 @@ pub struct Interpreter {
                         let sleep_ms = (secs * 1000.0) as u64;
                         crate::utils::sleep(sleep_ms).await;
                         
-                        Box::pin(self.execute_statements(body)).await;
+                        let flow = Box::pin(self.execute_statements(body)).await;
+                        if flow != Flow::Normal {
+                            return flow;
+                        }
                     }
                 }
                 
-                Statement::EventHandler { target, event, body } => {
+                Statement::EventHandler { target, event, body, interval } => {
                     // Resolve target ID
                     let target_id = self.resolve_target_id(target).await;
+                    let key = (target_id.clone(), event.clone());
                     
                     // Register handler in Interpreter
                     {
                         let mut handlers = self.event_handlers.lock().unwrap();
-                        handlers.insert((target_id.clone(), event.clone()), body.clone());
+                        handlers.insert(key.clone(), body.clone());
+                    }
+                    {
+                        let mut throttles = self.event_throttles.lock().unwrap();
+                        match interval {
+                            Some(secs) => throttles.insert(key, EventThrottle::new(*secs)),
+                            None => throttles.remove(&key),
+                        };
                     }
 
-                    self.ui.send_runtime_message(RuntimeMessage::RegisterEvent(target_id.clone(), event.clone(), Vec::new()));
+                    self.ui.send_runtime_message(RuntimeMessage::RegisterEvent(target_id.clone(), event.clone(), Vec::new(), *interval));
                     
                     log::info!("[RegisterEvent] {} {}", target_id, event);
                 }
@@ -659,9 +1441,8 @@ pub struct Interpreter {
                 }
 
                 // === Eeyo: 空間・通信ステートメント (Phase 13) ===
-                Statement::SpatialSearch { result, max_distance, filters } => {
+                Statement::SpatialSearch { result, max_distance, filters, order, limit } => {
                     let result_id = self.resolve_target_id(result).await;
-                    // ... (rest of SpatialSearch logic)
                     let distance = match self.eval_expr(max_distance).await {
                         Value::Number(n) => n,
                         Value::String(s) => {
@@ -672,20 +1453,32 @@ pub struct Interpreter {
                         _ => 10.0,
                     };
                     
-                    let filter_vec: Vec<(String, String)> = filters.iter()
-                        .map(|f| (f.field.clone(), format!("{:?}", f.condition)))
-                        .collect();
-                    
-                    let peers = self.p2p.spatial_search(distance, &filter_vec).await;
+                    // 比較値を評価してリテラルにする (状態 が 暇 の 暇 は値名のまま)
+                    let mut resolved = Vec::with_capacity(filters.len());
+                    for filter in filters {
+                        resolved.push(self.resolve_spatial_filter(filter).await);
+                    }
                     
-                    let result_str = if peers.is_empty() {
-                        "[]".to_string()
-                    } else {
-                        format!("[{} peers found]", peers.len())
-                    };
+                    let mut peers = self.p2p.spatial_search(distance, &resolved).await;
+                    match order {
+                        Some(SearchOrder::Nearest) => {
+                            peers.sort_by(|a, b| a.estimated_distance.total_cmp(&b.estimated_distance));
+                        }
+                        Some(SearchOrder::HighestToku) => {
+                            peers.sort_by_key(|p| std::cmp::Reverse(self.p2p.get_toku(&p.peer_id)));
+                        }
+                        None => {}
+                    }
+                    if let Some(limit) = limit {
+                        if let Value::Number(n) = self.eval_expr(limit).await {
+                            peers.truncate(n.max(0.0) as usize);
+                        }
+                    }
                     
+                    // 結果はピアIDのリスト (.徳 / .距離 などで参照できる)
+                    let ids = peers.into_iter().map(|p| Value::String(p.peer_id)).collect();
                     let mut table = self.symbol_table.lock().unwrap();
-                    table.register(&result_id, Value::String(result_str));
+                    table.register(&result_id, Value::List(ids));
                 }
                 Statement::BeaconBroadcast { beacon_type, duration, payload: _ } => {
                     // 発信時間を取得
@@ -754,8 +1547,9 @@ pub struct Interpreter {
                 
                 // Phase 15: Event Listener Register
                 Statement::EventListener { event_type, from_var, to_var, body } => {
+                    // 日本語名 (初対面 / ありがとう) も英語名と同じリスナーとして登録する
                     let mut listeners = self.event_listeners.lock().unwrap();
-                    listeners.entry(event_type.clone())
+                    listeners.entry(crate::p2p::SocialEventType::from_name(event_type).name().to_string())
                         .or_insert_with(Vec::new)
                         .push((from_var.clone(), to_var.clone(), body.clone()));
                     log::info!("[AGN] Registered EventListener for {}", event_type);
@@ -764,10 +1558,7 @@ pub struct Interpreter {
                 Statement::VariableUpdate { target, value, verb } => {
                     // 1. Evaluate value to update with
                     let val = self.eval_expr(value).await;
-                    let amount = match val {
-                        Value::Number(n) => n as i32,
-                        _ => 0,
-                    };
+                    let amount = val.as_number().map_or(0, |n| n as i32);
                     
                     // 2. Resolve target
                     // Supported patterns:
@@ -807,17 +1598,14 @@ pub struct Interpreter {
                                 }
                             }
                         }
+                        Expr::Variable(var_name) if var_name == "FeedList" && (verb == "更新する" || verb == "update") => {
+                             // Special case for FeedList (ロックを取らずに await し、後続の文も実行する)
+                             self.update_feed_ui().await;
+                        }
                         Expr::Variable(var_name) => {
                              // Local variable update
                              let mut table = self.symbol_table.lock().unwrap();
-                             // Special case for FeedList
-                             if var_name == "FeedList" && (verb == "更新する" || verb == "update") {
-                                 drop(table); // release lock before await
-                                 self.update_feed_ui().await;
-                                 return;
-                             }
-
-                             if let Value::Number(current) = table.get_value(&var_name) {
+                             if let Some(current) = table.get_value(var_name).as_number() {
                                   match verb.as_str() {
                                       "増やす" | "increase" => {
                                           table.update(&var_name, Value::Number(current + amount as f64));
@@ -826,7 +1614,7 @@ pub struct Interpreter {
                                           table.update(&var_name, Value::Number(current - amount as f64));
                                       }
                                       "更新する" | "update" | "set" => {
-                                          if let Value::Number(new_val) = val {
+                                          if let Some(new_val) = val.as_number() {
                                               table.update(&var_name, Value::Number(new_val));
                                           }
                                       }
@@ -839,7 +1627,7 @@ pub struct Interpreter {
                              let to_val = self.eval_expr(right).await;
                              if let (Value::String(from), Value::String(to)) = (from_val, to_val) {
                                  if verb == "深くする" || verb == "deepen" {
-                                     self.p2p.deepen_bond(&from, &to, amount as u32);
+                                     self.deepen_bond(&from, &to, amount as u32).await;
                                  }
                              }
                         }
@@ -854,46 +1642,145 @@ pub struct Interpreter {
                     let mut table = self.symbol_table.lock().unwrap();
                     table.register("結果", val);
                 }
+                Statement::Assert { condition } => {
+                    if !self.eval_condition(condition).await {
+                        self.report_error(format!("Assertion failed: {:?}", condition));
+                    }
+                }
                 Statement::ActionCall { name, args } => {
                     Box::pin(self.execute_action(name, args)).await;
                 }
+                // 残りの文を飛ばして、囲んでいるループに伝える
+                Statement::Break => return Flow::Break,
+                Statement::Continue => return Flow::Continue,
+                // Spanned はループの先頭で外してある
+                Statement::Comment { .. } | Statement::Spanned { .. } => {}
+            }
+
+            if let Some(name) = produced_variable(stmt) {
+                self.refresh_bindings(name);
             }
         }
         //     let _ = handle.await;
         // }
+        Flow::Normal
     }
 
-
-    pub fn fork_with_table(&self, symbol_table: Arc<StdMutex<SymbolTable>>) -> Self {
-        Self {
-            symbol_table,
+    /// 変数に束縛されたコンポーネントの文字を差し替え、UIへ通知する (ツリーは再構築しない)
+    fn refresh_bindings(&self, name: &str) {
+        let Some(labels) = self.bindings.lock().unwrap().get(name).cloned() else {
+            return;
+        };
+        let mut table = self.symbol_table.lock().unwrap();
+        let text = table.get_value(name).to_string();
+        for label in labels {
+            table.update_components(&label, &|c| {
+                if let Value::Component { children, .. } = c {
+                    match children.iter_mut().find(|child| matches!(child, Value::String(_))) {
+                        Some(child) => *child = Value::String(text.clone()),
+                        None => children.insert(0, Value::String(text.clone())),
+                    }
+                }
+            });
+            self.ui.send_runtime_message(RuntimeMessage::SetText(label, text.clone()));
+        }
+    }
+
+
+    pub fn fork_with_table(&self, symbol_table: Arc<StdMutex<SymbolTable>>) -> Self {
+        Self {
+            symbol_table,
             context_stack: Arc::new(StdMutex::new(Vec::new())),
+            loop_indices: Arc::new(StdMutex::new(Vec::new())),
             event_handlers: self.event_handlers.clone(),
+            event_throttles: self.event_throttles.clone(),
+            bindings: self.bindings.clone(),
             rules: self.rules.clone(),
             actions: self.actions.clone(),
             event_listeners: self.event_listeners.clone(),
             p2p: self.p2p.clone(),
             ui: self.ui.clone(),
+            on_statement: self.on_statement.clone(),
+            builtins: self.builtins.clone(),
+            pending: self.pending.clone(),
+            next_handle: self.next_handle.clone(),
+            cancel_handles: self.cancel_handles.clone(),
+            current_span: self.current_span.clone(),
+            runtime_errors: self.runtime_errors.clone(),
+            ai: self.ai.clone(),
+            config: self.config.clone(),
+            clock: self.clock.clone(),
+            run_budget: self.run_budget.clone(),
+        }
+    }
+
+    /// 呼び出し引数を評価する。Expr::Spread のリストは位置引数に展開する
+    async fn eval_args(&self, args: &[Expr]) -> Vec<Value> {
+        let mut values = Vec::new();
+        for arg in args {
+            match arg {
+                Expr::Spread(inner) => match Box::pin(self.eval_expr(inner)).await {
+                    Value::List(items) => values.extend(items),
+                    other => {
+                        self.report_error(format!("Spread argument is not a list: {}", other));
+                        values.push(other);
+                    }
+                },
+                _ => values.push(Box::pin(self.eval_expr(arg)).await),
+            }
+        }
+        values
+    }
+
+    /// 徳を送る(送り手, 受け手, 額): 成功すれば 真。残高不足などは何も変更せずエラーを報告して 偽
+    async fn transfer_toku(&self, args: &[Expr]) -> Value {
+        let arg_values = self.eval_args(args).await;
+        let (from, to, amount) = match arg_values.as_slice() {
+            [Value::String(from), Value::String(to), Value::Number(amount)] if *amount >= 0.0 => {
+                (from.clone(), to.clone(), *amount as u32)
+            }
+            _ => {
+                self.report_error("徳を送る expects (送り手, 受け手, 額)".to_string());
+                return Value::Bool(false);
+            }
+        };
+        match self.p2p.transfer_toku(&from, &to, amount) {
+            Ok(first_met) => {
+                if first_met {
+                    self.trigger_first_met(&from, &to).await;
+                }
+                Value::Bool(true)
+            }
+            Err(e) => {
+                self.report_error(e);
+                Value::Bool(false)
+            }
         }
     }
 
     pub async fn execute_action(&self, name: &str, args: &[Expr]) -> Value {
+        let _budget = self.start_budget();
+        // 文として呼ばれた組み込み関数 (倍(3) のみの行)
+        if let Some(value) = self.call_builtin(name, args).await {
+            return value;
+        }
+        if name == "徳を送る" || name == "send_toku" {
+            return self.transfer_toku(args).await;
+        }
+
         let (params, body) = {
             let actions = self.actions.lock().unwrap();
             match actions.get(name) {
                 Some(data) => data.clone(),
                 None => {
-                    log::error!("Action not found: {}", name);
+                    self.report_error(format!("Action not found: {}", name));
                     return Value::Nil;
                 }
             }
         };
 
-        // Evaluate arguments
-        let mut arg_values = Vec::new();
-        for arg in args {
-            arg_values.push(Box::pin(self.eval_expr(arg)).await);
-        }
+        // Evaluate arguments (...リスト は位置引数に展開)
+        let arg_values = self.eval_args(args).await;
 
         // Create scoped table
         let mut table = SymbolTable::new();
@@ -905,15 +1792,19 @@ pub struct Interpreter {
         }
         
         let scoped_interpreter = self.fork_with_table(Arc::new(StdMutex::new(table)));
+        let call_site = *self.current_span.lock().unwrap();
         Box::pin(scoped_interpreter.execute_statements(&body)).await;
+        *self.current_span.lock().unwrap() = call_site;
         
         let result = scoped_interpreter.symbol_table.lock().unwrap().get_value("結果");
         result
     }
 
     pub async fn execute_rule(&self, rule_name: &str, viewer: &str, post_id: &str) -> i32 {
-        let rules_guard = self.rules.lock().unwrap();
-        if let Some(body) = rules_guard.get(rule_name) {
+        let _budget = self.start_budget();
+        // ルール本体はロック中にクローンし、await 前にガードを解放する
+        let body = self.rules.lock().unwrap().get(rule_name).cloned();
+        if let Some(body) = body {
             // Create scoped interpreter
             let mut table = SymbolTable::new();
             
@@ -929,25 +1820,13 @@ pub struct Interpreter {
             let scoped_interpreter = self.fork_with_table(symbol_table);
             
             // Execute
-            // We need to release the rules lock before executing, as statements might access rules?
-            // Actually execute_statements doesn't lock rules except for definition.
-            // But to be safe, clone body.
-            let body_clone = body.clone();
-            drop(rules_guard);
-            
-            Box::pin(scoped_interpreter.execute_statements(&body_clone)).await;
+            Box::pin(scoped_interpreter.execute_statements(&body)).await;
             
             // Retrieve result
             // Retrieve result
             let table = scoped_interpreter.symbol_table.lock().unwrap();
-            let val_ja = match table.get_value("優先度") {
-                Value::Number(n) => n,
-                _ => 0.0,
-            };
-            let val_en = match table.get_value("priority") {
-                Value::Number(n) => n,
-                _ => 0.0,
-            };
+            let val_ja = table.get_number("優先度").unwrap_or(0.0);
+            let val_en = table.get_number("priority").unwrap_or(0.0);
             
             (val_ja + val_en) as i32
         } else {
@@ -976,22 +1855,37 @@ pub struct Interpreter {
         for (event, score) in event_scores.into_iter().take(20) {
             let mut event_children = Vec::new();
             
-            // Header: Author Name + Priority Badge
+            // Header: Author Name + Toku / Priority Badges
+            let locale = self.config.locale;
+            let author_toku = self.p2p.get_toku(&event.actor_id);
             let header = Value::Component {
                 style: "Header".to_string(),
                 ty: "コンテナ".to_string(),
                 label: None,
                 children: vec![
                     Value::String(format!("{} さん", event.actor_id)),
+                    Value::Component {
+                         style: "Badge".to_string(),
+                         ty: "ラベル".to_string(),
+                         label: None,
+                         children: vec![Value::String(format!("徳: {}", format_number(author_toku as f64, locale)))],
+                         layout: None,
+                         visible: true,
+                         enabled: true,
+                    },
                     Value::Component {
                          style: "Badge".to_string(), // New style needed? 
                          ty: "ラベル".to_string(),
                          label: None,
-                         children: vec![Value::String(format!("優先度: {}", score))],
+                         children: vec![Value::String(format!("優先度: {}", format_number(score as f64, locale)))],
                          layout: None,
+                         visible: true,
+                         enabled: true,
                     }
                 ],
                 layout: Some("horizontal".to_string()),
+                visible: true,
+                enabled: true,
             };
             event_children.push(header);
             
@@ -1013,6 +1907,8 @@ pub struct Interpreter {
                 label: Some(button_id),
                 children: vec![Value::String("徳を送る".to_string())],
                 layout: None,
+                visible: true,
+                enabled: true,
             };
             event_children.push(button);
 
@@ -1023,6 +1919,8 @@ pub struct Interpreter {
                 label: Some(format!("Post_{}", event.id)),
                 children: event_children,
                 layout: Some("vertical".to_string()),
+                visible: true,
+                enabled: true,
             };
             
             children.push(post_comp);
@@ -1039,15 +1937,11 @@ pub struct Interpreter {
              // Retrieve checking type? 
              // We just overwrite content but keep style/ty if possible?
              // Since we construct children, we can just update children if we get the old value.
-             let old_val = table.get_value("FeedList");
-             if let Value::Component { style, ty, label, .. } = old_val {
-                 table.update("FeedList", Value::Component {
-                     style,
-                     ty,
-                     label,
-                     children,
-                     layout: Some("vertical".to_string()),
-                 });
+             let mut feed = table.get_value("FeedList");
+             if let Value::Component { children: feed_children, layout, .. } = &mut feed {
+                 *feed_children = children;
+                 *layout = Some("vertical".to_string());
+                 table.update("FeedList", feed);
              }
         }
     }
@@ -1077,12 +1971,45 @@ pub struct Interpreter {
         }
     }
 
+    /// UIイベントを登録済みハンドラに配送する。無効化・間引き中・未登録なら false
+    pub async fn dispatch_event(&self, target: &str, event_type: &str) -> bool {
+        let key = (target.to_string(), event_type.to_string());
+        let handler_body = self.event_handlers.lock().unwrap().get(&key).cloned();
+        let Some(body) = handler_body else {
+            log::warn!("[AGN] No handler found for event: {} on {}", event_type, target);
+            return false;
+        };
+
+        let disabled = self.symbol_table.lock().unwrap().symbols.values()
+            .any(|v| v.find_component(target).is_some_and(|c| !c.is_enabled()));
+        if disabled {
+            log::debug!("[AGN] Ignored event on disabled component: {} on {}", event_type, target);
+            return false;
+        }
+
+        if let Some(throttle) = self.event_throttles.lock().unwrap().get_mut(&key) {
+            if !throttle.try_fire(crate::utils::now_ms()) {
+                log::debug!("[AGN] Throttled event: {} on {}", event_type, target);
+                return false;
+            }
+        }
+
+        log::info!("[AGN] Handling event: {} on {}", event_type, target);
+        let _budget = self.start_budget();
+        // ハンドラ内の "self" はターゲットを指す
+        self.context_stack.lock().unwrap().push(target.to_string());
+        self.execute_statements(&body).await;
+        self.context_stack.lock().unwrap().pop();
+        true
+    }
+
     pub async fn trigger_event(&self, event_type: &str, from_id: &str, to_id: &str) {
         log::info!("[AGN] Triggering event: {} ({} -> {})", event_type, from_id, to_id);
+        let _budget = self.start_budget();
         
         let handlers = {
             let map = self.event_listeners.lock().unwrap();
-            map.get(event_type).cloned()
+            map.get(crate::p2p::SocialEventType::from_name(event_type).name()).cloned()
         };
         
         if let Some(handler_list) = handlers {
@@ -1101,6 +2028,50 @@ pub struct Interpreter {
         }
     }
 
+    /// 絆を深め、初めて絆ができたときは 初対面 (FirstMet) イベントをリスナーに送る
+    async fn deepen_bond(&self, from: &str, to: &str, amount: u32) {
+        if self.p2p.deepen_bond(from, to, amount) {
+            self.trigger_first_met(from, to).await;
+        }
+    }
+
+    /// 初対面 (FirstMet) イベントを発火する
+    async fn trigger_first_met(&self, from: &str, to: &str) {
+        let event = crate::p2p::SocialTokuEvent::new(from, to, crate::p2p::SocialEventType::FirstMet);
+        Box::pin(self.trigger_social_event(&event)).await;
+    }
+
+    /// ソーシャルイベントを種類名でリスナーに振り分ける (Custom も名前で一致させる)
+    pub async fn trigger_social_event(&self, event: &crate::p2p::SocialTokuEvent) {
+        self.trigger_event(event.event_type.name(), &event.actor_id, &event.target_id).await;
+    }
+
+    /// 値を画面に出す。変数に束縛されたコンポーネントは描画対象として UI に送り、
+    /// それ以外は表示用の文字列にする
+    fn show_on_screen(&self, operand: &Expr, val: Value) {
+        log::info!("[Output] {}", val);
+        match (&val, operand) {
+            (Value::Component { .. }, Expr::Variable(name)) => {
+                self.ui.send_runtime_message(RuntimeMessage::ShowComponent(name.clone()));
+            }
+            _ => self.ui.print(&val.to_string()),
+        }
+    }
+
+    /// AI動詞をバックエンドで実行する
+    /// 画像生成の動詞は Value::Image、それ以外は Value::String を返す
+    /// 制限時間 (options.timeout) を過ぎたら AiError::Timeout
+    async fn run_ai_verb(&self, verb: &str, input: &str, options: &AiOptions) -> Result<Value, AiError> {
+        let call = async {
+            if is_image_verb(verb) {
+                self.ai.generate_image(input, options).await.map(Value::Image)
+            } else {
+                self.ai.execute_verb(verb, input, options).await.map(Value::String)
+            }
+        };
+        crate::utils::timeout(options.effective_timeout(), call).await.unwrap_or(Err(AiError::Timeout))
+    }
+
     pub async fn execute_verb(&self, verb: &str, value: Value) {
         let _input = value.to_string();
         let _option: Option<String> = None;
@@ -1111,7 +2082,7 @@ pub struct Interpreter {
                 // Logic already in eval_expr/execute_statements
             }
             "display" | "表示する" | "出す" => {
-                self.ui.notify(&value.to_string());
+                self.ui.print(&value.to_string());
             }
             "broadcast" | "発信する" => {
                 self.p2p.broadcast_beacon(&value.to_string(), None).await;
@@ -1270,6 +2241,22 @@ mod tests {
         assert!(bond.strength > 0);
     }
 
+    #[tokio::test]
+    async fn test_toku_transfer_over_budget_is_noop() {
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p.clone(), Arc::new(crate::bridge::mock::MockUIManager));
+
+        let code = "徳を送る(\"Alice\", \"Bob\", 30)\n徳を送る(\"Alice\", \"Bob\", 500)";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+        interpreter.execute(&program).await;
+
+        // 1回目だけ反映され、予算超過の2回目はどちらの残高も変えない
+        assert_eq!(p2p.get_toku("Alice"), 70);
+        assert_eq!(p2p.get_toku("Bob"), 130);
+        assert!(p2p.has_bond("Alice", "Bob"));
+        assert_eq!(interpreter.runtime_errors.lock().unwrap().len(), 1);
+    }
+
     #[tokio::test]
     async fn test_event_listener_execution() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -1310,6 +2297,74 @@ mod tests {
         assert!(bond.strength > 0);
     }
 
+    #[tokio::test]
+    async fn test_custom_social_event_runs_listener() {
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::MockUIManager);
+        let interpreter = Interpreter::with_bridges(p2p.clone(), ui);
+
+        let code = r#"
+            on Event(Festival) from Alice to Bob {
+                Alice.徳 に 30 を 増やす
+            }
+        "#;
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+        interpreter.execute(&program).await;
+
+        let event_type = crate::p2p::SocialEventType::from_name("Festival");
+        assert_eq!(event_type, crate::p2p::SocialEventType::Custom("Festival".to_string()));
+
+        let before = p2p.get_toku("Alice");
+        let event = crate::p2p::SocialTokuEvent::new("Alice", "Bob", event_type);
+        interpreter.trigger_social_event(&event).await;
+        assert_eq!(p2p.get_toku("Alice"), before + 30);
+    }
+
+    #[tokio::test]
+    async fn test_first_met_event_fires_once() {
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::MockUIManager);
+        let interpreter = Interpreter::with_bridges(p2p.clone(), ui);
+
+        let code = r#"
+            on Event(初対面) from Alice to Bob {
+                Alice.徳 に 10 を 増やす
+            }
+            bond("Alice", "Bob") を 深くする
+            bond("Alice", "Bob") を 深くする
+            bond("Alice", "Bob") に 5 を 増やす
+        "#;
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+        let before = p2p.get_toku("Alice");
+        interpreter.execute(&program).await;
+
+        // 初めての絆だけがボーナスの対象
+        assert_eq!(p2p.get_toku("Alice"), before + 10);
+        assert!(p2p.has_bond("Alice", "Bob"));
+    }
+
+    #[tokio::test]
+    async fn test_first_met_event_fires_on_transfer() {
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::MockUIManager);
+        let interpreter = Interpreter::with_bridges(p2p.clone(), ui);
+
+        let code = r#"
+            on Event(初対面) from Alice to Bob {
+                Bob.徳 に 10 を 増やす
+            }
+            徳を送る("Alice", "Bob", 5)
+            徳を送る("Alice", "Bob", 5)
+        "#;
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+        let before = p2p.get_toku("Bob");
+        interpreter.execute(&program).await;
+
+        // 送付 2 回分 + 初対面のボーナス 1 回分
+        assert_eq!(p2p.get_toku("Bob"), before + 5 + 5 + 10);
+        assert!(p2p.has_bond("Alice", "Bob"));
+    }
+
     #[tokio::test]
     async fn test_umeda_verification() {
         let _ = env_logger::builder().is_test(true).try_init();
@@ -1350,4 +2405,1071 @@ mod tests {
         let rel = p2p.get_bond("Me", "Stranger");
         assert_eq!(rel.strength, 11, "Bond strength should increase by 1");
     }
+
+    #[tokio::test]
+    async fn test_debug_hook_called_per_statement() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let code = "X は 0 だ\nrepeat 3 times increase X by 1 end";
+        let mut lexer = Lexer::new(code);
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::MockUIManager);
+        let mut interpreter = Interpreter::with_bridges(p2p, ui);
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = count.clone();
+        interpreter.set_debug_hook(Arc::new(move |_stmt, _table| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+        interpreter.execute(&program).await;
+
+        // Assignment + Repeat + 3 x body
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_runtime_error_reports_action_line() {
+        let code = "X は 0 だ\nアクション 調べる(P)\n  Z は P.unknown_prop だ\nおわり\nY は 調べる(\"Alice\") だ";
+        let mut lexer = Lexer::new(code);
+        let (tokens, spans) = lexer.tokenize_with_spans();
+        let mut parser = Parser::with_spans(tokens, spans);
+        let program = parser.parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::MockUIManager);
+        let interpreter = Interpreter::with_bridges(p2p, ui);
+        interpreter.execute(&program).await;
+
+        let errors = interpreter.runtime_errors.lock().unwrap();
+        assert_eq!(errors.len(), 1, "errors: {:?}", errors);
+        assert!(errors[0].starts_with("3行"), "got: {}", errors[0]);
+        assert!(errors[0].contains("unknown_prop"));
+        // 呼び出し後は呼び出し元の位置に戻る
+        assert_eq!(interpreter.current_span.lock().unwrap().map(|s| s.line), Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_feed_badges_use_locale_formatting() {
+        async fn badge_texts(locale: Locale) -> Vec<String> {
+            let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+            let ui = Arc::new(crate::bridge::mock::MockUIManager);
+            let interpreter = Interpreter::with_bridges(p2p.clone(), ui)
+                .with_config(InterpreterConfig { locale, ..Default::default() });
+
+            let code = "rule KizatoFeed\n increase priority by 1000\nend";
+            let mut lexer = Lexer::new(code);
+            let program = Parser::new(lexer.tokenize()).parse().unwrap();
+            interpreter.execute(&program).await;
+
+            p2p.add_toku("Alice", 1900);
+            p2p.inject_feed_event(crate::p2p::SocialTokuEvent::new("Alice", "Bob", crate::p2p::SocialEventType::ThankYou));
+            interpreter.symbol_table.lock().unwrap().register("FeedList", Value::Component {
+                style: "Feed".to_string(), ty: "コンテナ".to_string(), label: None, children: Vec::new(), layout: None, visible: true, enabled: true,
+            });
+            interpreter.update_feed_ui().await;
+
+            let feed = interpreter.symbol_table.lock().unwrap().get_value("FeedList");
+            let Value::Component { children, .. } = feed else { panic!("FeedList missing") };
+            let Value::Component { children: post, .. } = &children[0] else { panic!("post missing") };
+            let Value::Component { children: header, .. } = &post[0] else { panic!("header missing") };
+            header.iter().skip(1).map(|badge| match badge {
+                Value::Component { children, .. } => children[0].to_string(),
+                other => other.to_string(),
+            }).collect()
+        }
+
+        assert_eq!(badge_texts(Locale::En).await, vec!["徳: 2,000", "優先度: 1,000"]);
+        assert_eq!(badge_texts(Locale::Plain).await, vec!["徳: 2000", "優先度: 1000"]);
+    }
+
+    #[tokio::test]
+    async fn test_toku_ranking_builtin() {
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::MockUIManager);
+        let interpreter = Interpreter::with_bridges(p2p.clone(), ui);
+
+        p2p.add_toku("Alice", 50);
+        p2p.add_toku("Bob", 400);
+        p2p.add_toku("Carol", 200);
+        p2p.add_toku("Dave", 10);
+
+        let mut lexer = Lexer::new("順位表 は ランキング(3) だ");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        interpreter.execute(&program).await;
+
+        let top = interpreter.symbol_table.lock().unwrap().get_value("順位表");
+        let Value::List(entries) = top else { panic!("Expected list, got {:?}", top) };
+        let names: Vec<String> = entries.iter()
+            .map(|e| e.field("名前").map(|v| v.to_string()).unwrap_or_default())
+            .collect();
+        assert_eq!(names, vec!["Bob", "Carol", "Alice"]);
+        assert!(matches!(entries[0].field("徳"), Some(Value::Number(n)) if *n == 500.0));
+    }
+
+    #[tokio::test]
+    async fn test_conditional_beacon_broadcast() {
+        let code = "自分 は \"Me\" だ\n徳 が 500 以上 の とき\n  ビーコン を 発信する\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        // 徳 100 (初期値): 条件不成立なので発信しない
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p.clone(), Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+        assert_eq!(p2p.broadcasts.lock().unwrap().len(), 0);
+
+        // 徳 600: 条件成立
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        p2p.add_toku("Me", 500);
+        let interpreter = Interpreter::with_bridges(p2p.clone(), Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+        assert_eq!(*p2p.broadcasts.lock().unwrap(), vec![("暇".to_string(), None)]);
+    }
+
+    #[tokio::test]
+    async fn test_load_asset_skipped_on_web_target() {
+        let code = "ロゴ は \"logo.png\" を 読み込む";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        assert!(native_only_statement(&program.statements[0]).is_some());
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::MockUIManager);
+        let interpreter = Interpreter::with_bridges(p2p, ui)
+            .with_config(InterpreterConfig { web_target: true, ..Default::default() });
+        interpreter.execute(&program).await;
+
+        // 読み込みは行われず、警告のみ
+        assert!(interpreter.symbol_table.lock().unwrap().lookup("ロゴ").is_none());
+        let errors = interpreter.runtime_errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("not supported on web target"));
+    }
+
+    /// 初期化回数と呼び出し回数を数えるAIバックエンド
+    struct CountingAiBackend {
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    static COUNTING_BACKEND_INITS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    impl CountingAiBackend {
+        fn new() -> Self {
+            COUNTING_BACKEND_INITS.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Self { calls: std::sync::atomic::AtomicUsize::new(0) }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AiBackend for CountingAiBackend {
+        async fn execute_verb(&self, verb: &str, input: &str, _options: &AiOptions) -> Result<String, crate::ai_runtime::AiError> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(format!("{}:{}", verb, input))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clone_shares_ai_backend() {
+        let code = "文 は \"長い文章\" だ\n要旨 は 文 を 要約する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let backend = Arc::new(CountingAiBackend::new());
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::MockUIManager);
+        let interpreter = Interpreter::with_bridges(p2p, ui).with_ai_backend(backend.clone());
+
+        // イベントごとのクローンを模擬: バックエンドは再初期化されず共有される
+        for _ in 0..3 {
+            interpreter.clone().execute(&program).await;
+        }
+        assert_eq!(COUNTING_BACKEND_INITS.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(backend.calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert!(Arc::ptr_eq(&interpreter.config, &interpreter.clone().config));
+
+        let summary = interpreter.symbol_table.lock().unwrap().get_value("要旨");
+        assert_eq!(summary.to_string(), "要約する:長い文章");
+    }
+
+    /// 応答前に一定時間待つAIバックエンド (並列実行の重なりを観測する)
+    struct DelayedAiBackend {
+        delay: std::time::Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl AiBackend for DelayedAiBackend {
+        async fn execute_verb(&self, verb: &str, input: &str, _options: &AiOptions) -> Result<String, crate::ai_runtime::AiError> {
+            tokio::time::sleep(self.delay).await;
+            Ok(format!("{}:{}", verb, input))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_ai_verbs_overlap() {
+        let code = "A は \"一\" だ\nB は \"二\" だ\n甲 は A を 並列で 要約する\n乙 は B を 並列で 翻訳する\n待つ 甲\n待つ 乙";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let delay = std::time::Duration::from_millis(100);
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+            .with_ai_backend(Arc::new(DelayedAiBackend { delay }));
+
+        let start = std::time::Instant::now();
+        interpreter.execute(&program).await;
+        let elapsed = start.elapsed();
+
+        assert_eq!(interpreter.get_variable("甲"), Some(Value::String("要約する:一".to_string())));
+        assert_eq!(interpreter.get_variable("乙"), Some(Value::String("翻訳する:二".to_string())));
+        // 直列なら 2 * delay かかる
+        assert!(elapsed < delay * 2, "parallel AI calls did not overlap: {:?}", elapsed);
+        assert!(interpreter.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_pending_ai_op() {
+        let code = "A は \"一\" だ\n甲 は A を 並列で 要約する\n取り消す 甲\n乙 は 待つ 甲";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+            .with_ai_backend(Arc::new(DelayedAiBackend { delay: std::time::Duration::from_secs(10) }));
+
+        let start = std::time::Instant::now();
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("乙"), Some(Value::Cancelled));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+        assert!(interpreter.cancel_handles.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_slow_ai_call_times_out() {
+        let code = "A は \"一\" だ\n甲 は A を {タイムアウト: 0.05} に 要約する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+            .with_ai_backend(Arc::new(DelayedAiBackend { delay: std::time::Duration::from_secs(10) }));
+
+        let start = std::time::Instant::now();
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("甲"), Some(Value::String("[AI Error: timeout]".to_string())));
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    /// 受け取ったオプションを記録するAIバックエンド
+    #[derive(Default)]
+    struct RecordingAiBackend {
+        received: std::sync::Mutex<Vec<AiOptions>>,
+    }
+
+    #[async_trait::async_trait]
+    impl AiBackend for RecordingAiBackend {
+        async fn execute_verb(&self, verb: &str, input: &str, options: &AiOptions) -> Result<String, crate::ai_runtime::AiError> {
+            self.received.lock().unwrap().push(options.clone());
+            Ok(format!("{}:{}", verb, input))
+        }
+
+        async fn generate_image(&self, prompt: &str, options: &AiOptions) -> Result<String, crate::ai_runtime::AiError> {
+            self.received.lock().unwrap().push(options.clone());
+            Ok(format!("https://images.example/{}.png", prompt))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_options_reach_ai_backend() {
+        let code = "文 は \"こんにちは\" だ\n要旨 は 文 を {言語: \"en\", 長さ: \"短い\", tone: \"formal\"} に 翻訳する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let backend = Arc::new(RecordingAiBackend::default());
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+            .with_ai_backend(backend.clone());
+        interpreter.execute(&program).await;
+
+        let received = backend.received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].language.as_deref(), Some("en"));
+        assert_eq!(received[0].length.as_deref(), Some("短い"));
+        assert_eq!(received[0].extra, vec![("tone".to_string(), "formal".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_imagine_yields_image() {
+        let mut lexer = Lexer::new("絵 は \"猫\" を 想像する");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+            .with_ai_backend(Arc::new(RecordingAiBackend::default()));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("絵"), Some(Value::Image("https://images.example/猫.png".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_hide_and_show_component() {
+        let code = "甲 は 青 の ボタン だ\n甲 を 隠す";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+        assert!(!interpreter.get_variable("甲").unwrap().is_visible());
+
+        let mut lexer = Lexer::new("甲 を 見せる\n乙 を 隠す");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        interpreter.execute(&program).await;
+        assert!(interpreter.get_variable("甲").unwrap().is_visible());
+        assert_eq!(interpreter.runtime_errors.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_button_ignores_click() {
+        let code = "カウント は 0 だ\n甲 は 青 の ボタン だ\non 甲 click\nカウント に 1 を 足す\nend\n甲 を 無効 に する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert!(!interpreter.dispatch_event("甲", "click").await);
+        assert_eq!(interpreter.get_variable("カウント"), Some(Value::Number(0.0)));
+
+        let mut lexer = Lexer::new("甲 を 有効にする");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        interpreter.execute(&program).await;
+        assert!(interpreter.dispatch_event("甲", "click").await);
+        assert_eq!(interpreter.get_variable("カウント"), Some(Value::Number(1.0)));
+    }
+
+    #[tokio::test]
+    async fn test_components_in_loop_get_unique_names() {
+        let code = "乙 は 白い の コンテナ だ\n乙 の 中 に\n3 回 繰り返す\n甲 は 青 の ボタン だ\nおわり\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        let Some(Value::Component { children, .. }) = interpreter.get_variable("乙") else { panic!("parent missing") };
+        let labels: Vec<_> = children.iter().filter_map(|c| match c {
+            Value::Component { label, .. } => label.clone(),
+            _ => None,
+        }).collect();
+        assert_eq!(labels, vec!["甲[0]", "甲[1]", "甲[2]"]);
+        assert!(interpreter.get_variable("甲[1]").is_some());
+        assert!(interpreter.loop_indices.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_components_in_while_loop_get_unique_names() {
+        let code = "N は 0 だ\n乙 は 白い の コンテナ だ\n乙 の 中 に\nN < 2 の間 繰り返す\n甲 は 青 の ボタン だ\nN に 1 を 足す\nおわり\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        let Some(Value::Component { children, .. }) = interpreter.get_variable("乙") else { panic!("parent missing") };
+        let labels: Vec<_> = children.iter().filter_map(|c| match c {
+            Value::Component { label, .. } => label.clone(),
+            _ => None,
+        }).collect();
+        assert_eq!(labels, vec!["甲[0]", "甲[1]"]);
+        assert!(interpreter.loop_indices.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bound_text_updates_on_variable_change() {
+        let code = "点数 は 0 だ\n甲 は 青 の ラベル だ\n甲 の 文字 を 点数 に 束縛する\n点数 に 5 を 足す";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let ui = Arc::new(crate::bridge::mock::CapturingUIManager::new());
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, ui.clone());
+        interpreter.execute(&program).await;
+
+        let texts: Vec<_> = ui.runtime_messages.lock().unwrap().iter().filter_map(|m| match m {
+            RuntimeMessage::SetText(target, text) => Some((target.clone(), text.clone())),
+            _ => None,
+        }).collect();
+        assert_eq!(texts, vec![("甲".to_string(), "0".to_string()), ("甲".to_string(), "5".to_string())]);
+        let Some(Value::Component { children, .. }) = interpreter.get_variable("甲") else { panic!("label missing") };
+        assert_eq!(children, vec![Value::String("5".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_save_screen_sends_screenshot_request() {
+        let code = "Name は \"shot\" だ\n画面 を \"out.png\" に 保存する\n画面 を 1 に 保存する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let ui = Arc::new(crate::bridge::mock::CapturingUIManager::new());
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, ui.clone());
+        interpreter.execute(&program).await;
+
+        let paths: Vec<_> = ui.runtime_messages.lock().unwrap().iter().filter_map(|m| match m {
+            RuntimeMessage::SaveScreenshot(path) => Some(path.clone()),
+            _ => None,
+        }).collect();
+        assert_eq!(paths, vec!["out.png".to_string()]);
+        assert_eq!(interpreter.runtime_errors.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_screen_anchor_reaches_ui() {
+        let code = "\"hi\" を 画面 の 中央 に 表示する\n\"yo\" を 画面 の 上部 に 表示する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let ui = Arc::new(crate::bridge::mock::CapturingUIManager::new());
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, ui.clone());
+        interpreter.execute(&program).await;
+
+        let displayed: Vec<_> = ui.runtime_messages.lock().unwrap().iter().filter_map(|m| match m {
+            RuntimeMessage::Display(text, anchor) => Some((text.clone(), *anchor)),
+            _ => None,
+        }).collect();
+        assert_eq!(displayed, vec![("hi".to_string(), ScreenAnchor::Center), ("yo".to_string(), ScreenAnchor::Top)]);
+        assert_eq!(ScreenAnchor::Bottom.place(800.0, 600.0, 100.0, 20.0, 10.0), (350.0, 570.0));
+    }
+
+    #[tokio::test]
+    async fn test_screen_shows_non_string_values() {
+        let code = "甲 は 青 の ボタン だ\nshow 3.5 to 画面\n42 を 画面 に 表示する\nshow 甲 to 画面";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let ui = Arc::new(crate::bridge::mock::CapturingUIManager::new());
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, ui.clone());
+        interpreter.execute(&program).await;
+
+        assert_eq!(*ui.outputs.lock().unwrap(), vec!["3.5".to_string(), "42".to_string()]);
+        assert!(ui.runtime_messages.lock().unwrap().iter().any(|m| matches!(m, RuntimeMessage::ShowComponent(name) if name == "甲")));
+    }
+
+    #[tokio::test]
+    async fn test_click_handler_reads_self_style() {
+        let code = "甲 は 青 の ボタン だ\n甲 を 押したとき\n要旨 は self.色 だ\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert!(interpreter.dispatch_event("甲", "click").await);
+        assert_eq!(interpreter.get_variable("要旨"), Some(Value::String("青".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_korera_targets_block_parent() {
+        let code = "乙 は 白い の コンテナ だ\n乙 の 中 に\n甲 は 青 の ボタン だ\nこれら を 無効 に する\nこれら を 隠す\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        let parent = interpreter.get_variable("乙").unwrap();
+        assert!(!parent.is_visible());
+        assert!(!parent.is_enabled());
+        assert!(interpreter.get_variable("甲").unwrap().is_visible());
+        assert!(interpreter.runtime_errors.lock().unwrap().is_empty());
+
+        let mut lexer = Lexer::new("これら を 隠す");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        interpreter.execute(&program).await;
+        assert!(interpreter.runtime_errors.lock().unwrap()[0].contains("'これら' used outside of block"));
+    }
+
+    #[tokio::test]
+    async fn test_throttled_event_handler_fires_once() {
+        let code = "カウント は 0 だ\non Button click 0.5秒 間隔 で\nカウント に 1 を 足す\nend";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert!(interpreter.dispatch_event("Button", "click").await);
+        assert!(!interpreter.dispatch_event("Button", "click").await);
+        assert_eq!(interpreter.get_variable("カウント"), Some(Value::Number(1.0)));
+    }
+
+    #[test]
+    fn test_event_throttle_interval() {
+        let mut throttle = EventThrottle::new(0.5);
+        assert!(throttle.try_fire(1000.0));
+        assert!(!throttle.try_fire(1400.0));
+        assert!(throttle.try_fire(1500.0));
+    }
+
+    #[tokio::test]
+    async fn test_session_round_trip() {
+        let code = "rule Boost\n increase priority by 10\nend\n得点 は 42 だ";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let interpreter = Interpreter::with_bridges(
+            Arc::new(crate::bridge::mock::MockP2PBridge::new()),
+            Arc::new(crate::bridge::mock::MockUIManager),
+        );
+        interpreter.execute(&program).await;
+        let saved = interpreter.serialize_session();
+
+        // 再読み込み後の新しいインタプリタに復元する
+        let restored = Interpreter::with_bridges(
+            Arc::new(crate::bridge::mock::MockP2PBridge::new()),
+            Arc::new(crate::bridge::mock::MockUIManager),
+        );
+        restored.restore_session(&saved).unwrap();
+
+        assert_eq!(restored.get_variable("得点"), Some(Value::Number(42.0)));
+        assert!(restored.rules.lock().unwrap().contains_key("Boost"));
+        assert!(restored.restore_session("not json").is_err());
+    }
+
+    fn mock_peer(id: &str, distance: f64) -> crate::p2p::DetectedPeer {
+        crate::p2p::DetectedPeer {
+            peer_id: id.to_string(),
+            beacon_type: crate::p2p::BeaconType::Idle,
+            estimated_distance: distance,
+            rssi: -65,
+            last_seen_ms: crate::utils::now_ms(),
+            toku_score: None,
+            payload: std::collections::HashMap::new(),
+            rssi_history: Vec::new(),
+            rssi_ema: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spatial_search_order() {
+        let code = "近所 は 10m より近い 人 を 近い順 に 探す\n候補 は 10m より近い 人 を 徳の高い順 に 探す";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        // 遠いが徳の高い peer_far と、近いが徳の低い peer_near
+        p2p.peers.lock().unwrap().extend([mock_peer("peer_far", 8.0), mock_peer("peer_near", 2.0)]);
+        p2p.add_toku("peer_far", 50);
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        let ids = |name: &str| match interpreter.get_variable(name) {
+            Some(Value::List(items)) => items.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            other => panic!("Expected list, got {:?}", other),
+        };
+        assert_eq!(ids("近所"), vec!["peer_near", "peer_far"]);
+        assert_eq!(ids("候補"), vec!["peer_far", "peer_near"]);
+    }
+
+    #[tokio::test]
+    async fn test_spatial_search_limit() {
+        let code = "近所 は 10m より近い 人 を 近い順 に 2人 探す\n全員 は 10m より近い 人 を 探す";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        p2p.peers.lock().unwrap().extend([
+            mock_peer("a", 4.0), mock_peer("b", 1.0), mock_peer("c", 3.0), mock_peer("d", 2.0),
+        ]);
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert_eq!(
+            interpreter.get_variable("近所"),
+            Some(Value::List(vec![Value::String("b".to_string()), Value::String("d".to_string())]))
+        );
+        assert!(matches!(interpreter.get_variable("全員"), Some(Value::List(items)) if items.len() == 4));
+    }
+
+    #[tokio::test]
+    async fn test_spatial_search_toku_threshold() {
+        let code = "基準 は 500 だ\n近所 は 10m より近い 人 で 徳 が 基準 以上 の 人 を 探す";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let with_toku = |id: &str, toku: Option<u32>| crate::p2p::DetectedPeer { toku_score: toku, ..mock_peer(id, 1.0) };
+        p2p.peers.lock().unwrap().extend([
+            with_toku("a", Some(800)), with_toku("b", Some(499)), with_toku("c", Some(500)), with_toku("d", None),
+        ]);
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert_eq!(
+            interpreter.get_variable("近所"),
+            Some(Value::List(vec![Value::String("a".to_string()), Value::String("c".to_string())]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_await_two_async_computations() {
+        let code = "X は 10 だ\nY は 4 だ\nX に 5 を 並列で 足す\nY に 3 を 並列で 掛ける\n和 は 待つ X\n積 は 待つ Y";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("和"), Some(Value::Number(15.0)));
+        assert_eq!(interpreter.get_variable("積"), Some(Value::Number(12.0)));
+        // 待った変数自体も解決値に置き換わる
+        assert_eq!(interpreter.get_variable("X"), Some(Value::Number(15.0)));
+        assert_eq!(interpreter.get_variable("Y"), Some(Value::Number(12.0)));
+        assert!(interpreter.runtime_errors.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_feed_update_does_not_block_following_statements() {
+        let code = "rule KizatoFeed\n increase priority by 10\nend\nupdate FeedList to 0\nX は 1 だ";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        p2p.inject_feed_event(crate::p2p::SocialTokuEvent::new("Alice", "Bob", crate::p2p::SocialEventType::ThankYou));
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.symbol_table.lock().unwrap().register("FeedList", Value::Component {
+            style: "Feed".to_string(), ty: "コンテナ".to_string(), label: None, children: Vec::new(), layout: None, visible: true, enabled: true,
+        });
+
+        // 別タスクからシンボルテーブルへ同時アクセスしてもデッドロックしない
+        let reader = interpreter.clone();
+        let concurrent = tokio::spawn(async move {
+            for _ in 0..100 {
+                let _ = reader.symbol_table.lock().unwrap().get_value("X");
+                tokio::task::yield_now().await;
+            }
+        });
+        tokio::time::timeout(std::time::Duration::from_secs(5), interpreter.execute(&program))
+            .await
+            .expect("feed update deadlocked");
+        concurrent.await.unwrap();
+
+        let table = interpreter.symbol_table.lock().unwrap();
+        assert!(matches!(table.get_value("X"), Value::Number(n) if n == 1.0));
+        let Value::Component { children, .. } = table.get_value("FeedList") else { panic!("FeedList missing") };
+        assert_eq!(children.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_host_variable_access() {
+        let code = "合計 は 入力 だ\n合計 に 5 を 足す";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.set_variable("入力", Value::Number(10.0));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("合計"), Some(Value::Number(15.0)));
+        assert_eq!(interpreter.get_variable("未定義"), None);
+    }
+
+    #[tokio::test]
+    async fn test_execute_one_keeps_state() {
+        let code = "X は 10 だ\nX に 5 を 足す\nX を 表示する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+
+        assert_eq!(interpreter.execute_one(&program.statements[0]).await, Some(Value::Number(10.0)));
+        assert_eq!(interpreter.execute_one(&program.statements[1]).await, Some(Value::Number(15.0)));
+        // 表示は値を生成しない
+        assert_eq!(interpreter.execute_one(&program.statements[2]).await, None);
+        assert_eq!(interpreter.get_variable("X"), Some(Value::Number(15.0)));
+    }
+
+    #[tokio::test]
+    async fn test_add_concatenates_strings() {
+        let code = "greeting は \"a\" だ\ngreeting に \"b\" を 足す\nlabel は \"count: \" だ\nlabel に 5 を 足す";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("greeting"), Some(Value::String("ab".to_string())));
+        assert_eq!(interpreter.get_variable("label"), Some(Value::String("count: 5".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_outputs_routed_through_ui_manager() {
+        let code = "X は 42 だ\nX を 表示する\n\"こんにちは\" を 画面 に 表示する\nロゴ は \"logo.png\" を 読み込む\nmissing(1)";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::CapturingUIManager::new());
+        let interpreter = Interpreter::with_bridges(p2p, ui.clone());
+        interpreter.execute(&program).await;
+
+        let outputs = ui.outputs.lock().unwrap();
+        assert_eq!(outputs[0], "42");
+        assert_eq!(outputs[1], "こんにちは");
+        assert_eq!(outputs[2], "[System] Loading asset: logo.png");
+        let errors = ui.errors.lock().unwrap();
+        assert_eq!(*errors, vec!["Action not found: missing".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_bool_number_coercions() {
+        let code = "F は 真 だ\nF に 1 を 足す\nG は 真 だ\nincrease G by 2\nH は 5 だ\ndecrease H by 真\nN は 10 だ\nN に 真 を 足す\nN に 偽 を 掛ける\nC は 0 だ\nもし 2 ならば\n  C に 1 を 足す\nおわり\nもし 0 ならば\n  C に 10 を 足す\nおわり";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        // 算術では 真 = 1, 偽 = 0
+        assert_eq!(interpreter.get_variable("F"), Some(Value::Number(2.0)));
+        assert_eq!(interpreter.get_variable("G"), Some(Value::Number(3.0)));
+        assert_eq!(interpreter.get_variable("H"), Some(Value::Number(4.0)));
+        assert_eq!(interpreter.get_variable("N"), Some(Value::Number(0.0)));
+        // 条件では 0 以外の数値が真
+        assert_eq!(interpreter.get_variable("C"), Some(Value::Number(1.0)));
+    }
+
+    #[tokio::test]
+    async fn test_break_exits_repeat_early() {
+        let code = "C は 0 だ\nrepeat 10 times\n  C に 1 を 足す\n  もし C と等しい 3 ならば\n    やめる\n  おわり\nend\nW は 0 だ\nwhile 真\n  W に 1 を 足す\n  もし W と等しい 5 ならば\n    break\n  おわり\nend";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("C"), Some(Value::Number(3.0)));
+        assert_eq!(interpreter.get_variable("W"), Some(Value::Number(5.0)));
+    }
+
+    #[tokio::test]
+    async fn test_continue_skips_rest_of_iteration() {
+        // 2 回目だけ足し込みを飛ばす: 1 + 3 + 4 + 5
+        let code = "S は 0 だ\nI は 0 だ\n5 回 繰り返す\n  I に 1 を 足す\n  もし I と等しい 2 ならば\n    つぎへ\n  おわり\n  S に I を 足す\nおわり";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("I"), Some(Value::Number(5.0)));
+        assert_eq!(interpreter.get_variable("S"), Some(Value::Number(13.0)));
+    }
+
+    #[tokio::test]
+    async fn test_while_loop_counts_until_condition_fails() {
+        let code = "X は 0 だ\nX は 10 ではない の間 繰り返す\n  X に 1 を 足す\nおわり\nY は 0 だ\nwhile Y < 3\n  add 1 to Y\nend";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("X"), Some(Value::Number(10.0)));
+        assert_eq!(interpreter.get_variable("Y"), Some(Value::Number(3.0)));
+    }
+
+    #[tokio::test]
+    async fn test_while_loop_iteration_cap() {
+        let code = "X は 0 だ\nwhile 真\n  X に 1 を 足す\nend";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("X"), Some(Value::Number(MAX_WHILE_ITERATIONS as f64)));
+    }
+
+    #[tokio::test]
+    async fn test_log_keeps_channel_tag() {
+        let code = "X は 42 だ\nX を \"debug\" に 記録する\n\"起動しました\" を \"audit\" に 記録する\nX を 表示する";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::CapturingUIManager::new());
+        let interpreter = Interpreter::with_bridges(p2p, ui.clone());
+        interpreter.execute(&program).await;
+
+        // 記録はユーザー向けの出力に混ざらない
+        assert_eq!(*ui.outputs.lock().unwrap(), vec!["42".to_string()]);
+        assert_eq!(*ui.logs.lock().unwrap(), vec![
+            ("debug".to_string(), "42".to_string()),
+            ("audit".to_string(), "起動しました".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_negated_comparison_assertion() {
+        let code = "X は 10 だった\nX は 5 ではない\nX は 10 ではない";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+
+        // 代入はされず、成り立たない比較のみ報告される
+        assert_eq!(interpreter.get_variable("X"), Some(Value::Number(10.0)));
+        let errors = interpreter.runtime_errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Assertion failed"));
+    }
+
+    #[tokio::test]
+    async fn test_spread_list_into_action_args() {
+        let code = "action 合計(A, B, C)\n  S は A だ\n  S に B を 足す\n  S に C を 足す\n  S を 表示する\nend\n合計(...数字)";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let ui = Arc::new(crate::bridge::mock::CapturingUIManager::new());
+        let interpreter = Interpreter::with_bridges(p2p, ui.clone());
+        interpreter.set_variable("数字", Value::List(vec![Value::Number(1.0), Value::Number(2.0), Value::Number(3.0)]));
+        interpreter.execute(&program).await;
+
+        assert_eq!(*ui.outputs.lock().unwrap(), vec!["6".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_division_by_zero_modes() {
+        async fn run(mode: ArithmeticMode) -> (Option<Value>, Vec<String>) {
+            let mut lexer = Lexer::new("X は 10 だ\nX に 0 を 割る");
+            let program = Parser::new(lexer.tokenize()).parse().unwrap();
+            let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+            let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+                .with_config(InterpreterConfig { arithmetic: mode, ..Default::default() });
+            interpreter.execute(&program).await;
+            let errors = interpreter.runtime_errors.lock().unwrap().clone();
+            (interpreter.get_variable("X"), errors)
+        }
+
+        let (value, errors) = run(ArithmeticMode::Lenient).await;
+        assert_eq!(value, Some(Value::Number(10.0)));
+        assert!(errors.is_empty());
+
+        let (value, errors) = run(ArithmeticMode::Strict).await;
+        assert_eq!(value, Some(Value::Nil));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Division by zero"));
+
+        let (value, errors) = run(ArithmeticMode::NanSentinel).await;
+        assert!(matches!(value, Some(Value::Number(n)) if n.is_nan()));
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_percent_multiplies_as_fraction() {
+        let mut lexer = Lexer::new("Price は 200 だ\nPrice に 50% を 掛ける\nRate は ２５％ だ");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+        assert_eq!(interpreter.get_variable("Price"), Some(Value::Number(100.0)));
+        assert_eq!(interpreter.get_variable("Rate"), Some(Value::Number(0.25)));
+    }
+
+    #[tokio::test]
+    async fn test_and_condition_requires_both() {
+        let code = "A は 5 だ\nB は 0 だ\nもし A > 1 かつ B ならば\n  A に 1 を 足す\nおわり\nもし A > 1 かつ B と等しい 0 ならば\n  A に 10 を 足す\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.execute(&program).await;
+        assert_eq!(interpreter.get_variable("A"), Some(Value::Number(15.0)));
+    }
+
+    #[tokio::test]
+    async fn test_or_condition_short_circuits() {
+        let code = "F は 真 だ\nA は 0 だ\nもし F または 数える() ならば\n  A に 1 を 足す\nおわり\nもし 偽 または 数える() ならば\n  A に 10 を 足す\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        interpreter.register_builtin("数える", Arc::new(move |_| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Value::Bool(true)
+        }));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("F"), Some(Value::Bool(true)));
+        assert_eq!(interpreter.get_variable("A"), Some(Value::Number(11.0)));
+        // 左辺が真なら右辺は呼ばれない
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rank_ladder_tiers() {
+        let ladder = RankLadder::new(vec![
+            (500, "銀".to_string()),
+            (0, "見習い".to_string()),
+            (2000, "金".to_string()),
+        ]);
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        p2p.add_toku("Low", 0);
+        p2p.add_toku("Mid", 400);
+        p2p.add_toku("Edge", 1900);
+        p2p.add_toku("High", 4000);
+        let code = "L は \"Low\" だ\nM は \"Mid\" だ\nE は \"Edge\" だ\nH は \"High\" だ\nRL は L.ランク だ\nRM は M.ランク だ\nRE は E.ランク だ\nRH は H.ランク だ";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+            .with_config(InterpreterConfig { rank_ladder: ladder, ..Default::default() });
+        interpreter.execute(&program).await;
+
+        let rank = |name: &str| interpreter.get_variable(name);
+        assert_eq!(rank("RL"), Some(Value::String("見習い".to_string())));
+        assert_eq!(rank("RM"), Some(Value::String("銀".to_string())));
+        assert_eq!(rank("RE"), Some(Value::String("金".to_string())));
+        assert_eq!(rank("RH"), Some(Value::String("金".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_rank_from_earned_history() {
+        async fn ranks(basis: RankBasis) -> (Option<Value>, Option<Value>) {
+            let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+            // Spender は 500 まで貯めてから 400 を送った。Saver は何もしていない
+            p2p.add_toku("Spender", 400);
+            p2p.transfer_toku("Spender", "Friend", 400).unwrap();
+            assert_eq!(p2p.get_toku("Spender"), p2p.get_toku("Saver"));
+
+            let code = "S は \"Spender\" だ\nV は \"Saver\" だ\nRS は S.ランク だ\nRV は V.ランク だ";
+            let mut lexer = Lexer::new(code);
+            let program = Parser::new(lexer.tokenize()).parse().unwrap();
+            let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+                .with_config(InterpreterConfig { rank_basis: basis, ..Default::default() });
+            interpreter.execute(&program).await;
+            (interpreter.get_variable("RS"), interpreter.get_variable("RV"))
+        }
+
+        let general = Some(Value::String("一般".to_string()));
+        let helper = Some(Value::String("協力者".to_string()));
+        assert_eq!(ranks(RankBasis::Balance).await, (general.clone(), general.clone()));
+        assert_eq!(ranks(RankBasis::Earned).await, (helper, general));
+    }
+
+    #[tokio::test]
+    async fn test_register_builtin() {
+        let code = "Y は 倍(21) だ";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.register_builtin("倍", Arc::new(|args: Vec<Value>| match args.first() {
+            Some(Value::Number(n)) => Value::Number(n * 2.0),
+            _ => Value::Nil,
+        }));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("Y"), Some(Value::Number(42.0)));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_budget_aborts_long_loop() {
+        let code = "1000000 回 繰り返す\nY は 刻む() だ\nおわり\nZ は 1 だ";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let clock = Arc::new(crate::utils::MockClock::new(0.0));
+        let config = InterpreterConfig { max_runtime_ms: Some(1000.0), ..Default::default() };
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+            .with_config(config)
+            .with_clock(clock.clone());
+        let ticks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (tick_clock, tick_count) = (clock.clone(), ticks.clone());
+        // 1回の呼び出しで 100ms 進む
+        interpreter.register_builtin("刻む", Arc::new(move |_| {
+            tick_clock.advance(100.0);
+            Value::Number(tick_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst) as f64)
+        }));
+        interpreter.execute(&program).await;
+
+        assert_eq!(ticks.load(std::sync::atomic::Ordering::SeqCst), 11);
+        assert_eq!(interpreter.get_variable("Z"), None);
+        let errors = interpreter.runtime_errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("Runtime budget"), "{}", errors[0]);
+    }
+
+    #[tokio::test]
+    async fn test_runtime_budget_limits_event_handlers() {
+        let code = "on Button click\n  1000000 回 繰り返す\n    Y は 刻む() だ\n  おわり\nend\non Event(Festival) from A to B {\n  1000000 回 繰り返す\n    Y は 刻む() だ\n  おわり\n}";
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+
+        let clock = Arc::new(crate::utils::MockClock::new(0.0));
+        let config = InterpreterConfig { max_runtime_ms: Some(1000.0), ..Default::default() };
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager))
+            .with_config(config)
+            .with_clock(clock.clone());
+        let ticks = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (tick_clock, tick_count) = (clock.clone(), ticks.clone());
+        interpreter.register_builtin("刻む", Arc::new(move |_| {
+            tick_clock.advance(100.0);
+            Value::Number(tick_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst) as f64)
+        }));
+        // ハンドラを登録するだけの実行は予算内で終わる
+        interpreter.execute(&program).await;
+        assert_eq!(ticks.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+        // execute の外から配送されたハンドラも、配送ごとに新しい予算で打ち切られる
+        assert!(interpreter.dispatch_event("Button", "click").await);
+        assert_eq!(ticks.load(std::sync::atomic::Ordering::SeqCst), 11);
+        interpreter.trigger_event("Festival", "Alice", "Bob").await;
+        assert_eq!(ticks.load(std::sync::atomic::Ordering::SeqCst), 22);
+        assert_eq!(interpreter.runtime_errors.lock().unwrap().len(), 2);
+        assert_eq!(interpreter.run_budget.lock().unwrap().started_ms, None);
+    }
+
+    #[tokio::test]
+    async fn test_register_async_builtin() {
+        let code = "天気 は 取得(\"tokyo\") だ";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = Interpreter::with_bridges(p2p, Arc::new(crate::bridge::mock::MockUIManager));
+        interpreter.register_async_builtin("取得", Arc::new(|args: Vec<Value>| {
+            Box::pin(async move {
+                // ネットワーク取得の代わりに少し待ってから値を返す
+                tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+                Value::String(format!("{}: 晴れ", args[0]))
+            })
+        }));
+        interpreter.execute(&program).await;
+
+        assert_eq!(interpreter.get_variable("天気"), Some(Value::String("tokyo: 晴れ".to_string())));
+    }
 }
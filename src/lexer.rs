@@ -1,6 +1,8 @@
 //! AGN Lexer - 字句解析器
 //! 日本語・英語の両方をトークンとして認識する
 
+use std::collections::VecDeque;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     /// 名詞（変数名）
@@ -43,6 +45,12 @@ pub enum Token {
     KeywordRepeat,
     /// times / 回
     KeywordTimes,
+    /// while / の間 (条件付きループ)
+    KeywordWhile,
+    /// break / やめる (ループを抜ける)
+    KeywordBreak,
+    /// continue / つぎへ (次の反復へ進む)
+    KeywordContinue,
     
     // === Comparison ===
     /// equals / と等しい
@@ -51,6 +59,10 @@ pub enum Token {
     KeywordGreaterThan,
     /// less than / より小さい
     KeywordLessThan,
+    /// at least / 以上
+    KeywordAtLeast,
+    /// ではない (否定比較)
+    KeywordNotEqual,
     
     // === English SVO keywords ===
     /// to (for "add X to Y")
@@ -103,6 +115,8 @@ pub enum Token {
     Distance { value: f64, unit: String },
     /// 時間リテラル (5分, 3秒, 1時間)
     Duration { value: f64, unit: String },
+    /// 百分率リテラル (50%, ５０％)。value は書かれた数値そのもの (50)
+    Percent { value: f64 },
     
     // === Eeyo: 空間検索キーワード ===
     /// より近い
@@ -119,6 +133,10 @@ pub enum Token {
     KeywordNotify,
     /// 人 (空間検索の対象)
     KeywordPerson,
+    /// 近い順 (空間検索の並び順)
+    KeywordNearestFirst,
+    /// 徳の高い順 (空間検索の並び順)
+    KeywordTokuFirst,
     /// で (条件接続)
     ParticleDe,
     /// 間に / Between
@@ -155,6 +173,10 @@ pub enum Token {
     KeywordAttach,
     /// かつ / And
     KeywordAnd,
+    /// または / Or
+    KeywordOr,
+    /// 真偽値リテラル (真/偽, true/false)
+    Bool(bool),
     
     // === Phase 15: Event-Driven Kizuna Logic ===
     /// から / From (Event Source)
@@ -173,7 +195,11 @@ pub enum Token {
     RParen,
     /// ,
     Comma,
+    /// : (レコードのキーと値の区切り)
+    Colon,
     
+    /// コメント (// の後ろ)。Lexer::with_comments のときだけ出力される
+    Comment(String),
     /// 改行
     Newline,
     /// ファイル終端
@@ -189,6 +215,12 @@ const KNOWN_JP_VERBS: &[&str] = &[
     // AGN 2.0: ソーシャル動詞
     "増やす", "減らす", "更新する", "付ける", "とする",
     "想像する", // Phase 11
+    "待つ", // 並列実行の結果待ち
+    "取り消す", // 並列実行の取り消し
+    "隠す", "見せる", // コンポーネントの表示切替
+    "束縛する", // データバインディング
+    "保存する", // 画面の保存 (スクリーンショット)
+    "記録する", // チャンネル付きのログ出力
     "get_bond", "set_status", // Phase 15 (Japanese context but func name likely reused or translated?)
     // 日本語エイリアスも検討: "絆を取得する", "ステータスを設定する"
 ];
@@ -203,6 +235,9 @@ const KNOWN_EN_VERBS: &[&str] = &[
 /// 特殊名詞（出力先など）
 // const SPECIAL_NOUNS: &[&str] = &["画面", "Screen"];
 
+/// キーワードで始まるが一語の識別子として読む名詞 (上部 は 上、徳を送る は 徳 より先に照合する)
+const COMPOUND_NOUNS: &[&str] = &["上部", "徳を送る"];
+
 /// 既知の副詞リスト
 const KNOWN_ADVERBS: &[&str] = &["並列で", "async", "parallel"];
 
@@ -214,6 +249,9 @@ const ENGLISH_KEYWORDS: &[(&str, fn() -> Token)] = &[
     ("end", || Token::KeywordEnd),
     ("repeat", || Token::KeywordRepeat),
     ("times", || Token::KeywordTimes),
+    ("while", || Token::KeywordWhile),
+    ("break", || Token::KeywordBreak),
+    ("continue", || Token::KeywordContinue),
     ("equals", || Token::KeywordEquals),
     ("to", || Token::KeywordTo),
     ("let", || Token::KeywordLet),
@@ -240,6 +278,9 @@ const ENGLISH_KEYWORDS: &[(&str, fn() -> Token)] = &[
     ("rank", || Token::KeywordRank),
     ("attach", || Token::KeywordAttach),
     ("and", || Token::KeywordAnd),
+    ("or", || Token::KeywordOr),
+    ("true", || Token::Bool(true)),
+    ("false", || Token::Bool(false)),
     // Phase 15
     ("from", || Token::KeywordFrom),
     ("event", || Token::KeywordEvent),
@@ -252,9 +293,20 @@ const JAPANESE_KEYWORDS: &[(&str, fn() -> Token)] = &[
     ("そうでなければ", || Token::KeywordElse),
     ("おわり", || Token::KeywordEnd),
     ("回", || Token::KeywordTimes),
+    // 絆の条件 (A と B の間に) は条件付きループの の間 より先に照合する
+    ("の間に", || Token::KeywordBetween),
+    ("の間", || Token::KeywordWhile),
+    ("やめる", || Token::KeywordBreak),
+    ("つぎへ", || Token::KeywordContinue),
     ("と等しい", || Token::KeywordEquals),
     ("より大きい", || Token::KeywordGreaterThan),
     ("より小さい", || Token::KeywordLessThan),
+    ("以上", || Token::KeywordAtLeast),
+    ("ではない", || Token::KeywordNotEqual),
+    // 過去形・丁寧形のコピュラは「だ」と同じ扱い ("で" より先に照合する)
+    ("だった", || Token::ParticleDa),
+    ("でした", || Token::ParticleDa),
+    ("です", || Token::ParticleDa),
     // Phase 6: UI & Events
     ("画面", || Token::ScreenNoun),
     ("押したとき", || Token::KeywordClick),
@@ -280,6 +332,8 @@ const JAPANESE_KEYWORDS: &[(&str, fn() -> Token)] = &[
     // Eeyo: 空間検索キーワード (Phase 13)
     ("より近い", || Token::KeywordNearer),
     ("より遠い", || Token::KeywordFarther),
+    ("近い順", || Token::KeywordNearestFirst),
+    ("徳の高い順", || Token::KeywordTokuFirst), // "徳" より先に照合する
     ("暇", || Token::KeywordIdle),
     ("人", || Token::KeywordPerson),
     ("徳", || Token::KeywordToku),
@@ -299,14 +353,42 @@ const JAPANESE_KEYWORDS: &[(&str, fn() -> Token)] = &[
     ("がある", || Token::KeywordAre), // "絆がある" などの判定用
     ("にある", || Token::KeywordAre),
     ("かつ", || Token::KeywordAnd),
+    ("または", || Token::KeywordOr),
     // Phase 15
     ("から", || Token::KeywordFrom),
     ("イベント", || Token::KeywordEvent),
 ];
 
+/// ソース上の位置 (行・列ともに1始まり)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}行{}列", self.line, self.column)
+    }
+}
+
 pub struct Lexer {
     input: Vec<char>,
     pos: usize,
+    // トークンごとの開始位置 (tokens と同じ長さ)
+    spans: Vec<Span>,
+    // 位置計算の再走査を避けるためのカーソル (文字位置, 位置)
+    span_cursor: (usize, Span),
+    // コメントを捨てずに Token::Comment として出力する (整形用)
+    retain_comments: bool,
+    // 括弧の入れ子の深さ。括弧内の改行は文の区切りではないので出力しない
+    paren_depth: usize,
+    // 括弧内のコメント。括弧を閉じた行の行末コメントとして出力する
+    held_comments: Vec<String>,
+    // 読み取り済みでまだ返していないトークン (Iterator 用)
+    pending: VecDeque<Token>,
+    // EOF まで読み終えたか
+    finished: bool,
 }
 
 impl Lexer {
@@ -314,7 +396,44 @@ impl Lexer {
         Self {
             input: input.chars().collect(),
             pos: 0,
+            spans: Vec::new(),
+            span_cursor: (0, Span { line: 1, column: 1 }),
+            retain_comments: false,
+            paren_depth: 0,
+            held_comments: Vec::new(),
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// コメントをトークンとして残す字句解析器 (--format 用)
+    pub fn with_comments(input: &str) -> Self {
+        Self { retain_comments: true, ..Self::new(input) }
+    }
+
+    /// トークン列と各トークンの開始位置を返す
+    pub fn tokenize_with_spans(&mut self) -> (Vec<Token>, Vec<Span>) {
+        let tokens = self.tokenize();
+        (tokens, std::mem::take(&mut self.spans))
+    }
+
+    fn span_at(&mut self, pos: usize) -> Span {
+        let (from, mut span) = if pos < self.span_cursor.0 {
+            (0, Span { line: 1, column: 1 })
+        } else {
+            self.span_cursor
+        };
+        let end = pos.min(self.input.len());
+        for &c in &self.input[from..end] {
+            if c == '\n' {
+                span.line += 1;
+                span.column = 1;
+            } else {
+                span.column += 1;
+            }
         }
+        self.span_cursor = (end, span);
+        span
     }
 
     fn current(&self) -> Option<char> {
@@ -349,12 +468,36 @@ impl Lexer {
             if c.is_ascii_digit() || c == '.' {
                 num_str.push(c);
                 self.advance();
+            } else if let Some(d) = full_width_digit(c) {
+                num_str.push(d);
+                self.advance();
             } else {
                 break;
             }
         }
-        let value = num_str.parse().unwrap_or(0.0);
+        // 指数表記: 1e3, 2.5e-2 (e の後に数字がなければ指数とみなさない)
+        if matches!(self.current(), Some('e' | 'E')) {
+            let sign = matches!(self.input.get(self.pos + 1), Some('+' | '-'));
+            let digit_at = self.pos + 1 + sign as usize;
+            if self.input.get(digit_at).is_some_and(|c| c.is_ascii_digit()) {
+                num_str.extend(&self.input[self.pos..=digit_at]);
+                self.advance_by(digit_at + 1 - self.pos);
+                while let Some(c) = self.current().filter(|c| c.is_ascii_digit()) {
+                    num_str.push(c);
+                    self.advance();
+                }
+            }
+        }
+        let value = num_str.parse().unwrap_or_else(|_| {
+            log::warn!("[Lexer] Invalid number literal '{}', treating as 0", num_str);
+            0.0
+        });
         
+        if matches!(self.current(), Some('%' | '％')) {
+            self.advance();
+            return Token::Percent { value };
+        }
+
         // Eeyo: 距離・時間リテラルの検出
         let remaining: String = self.input[self.pos..].iter().collect();
         
@@ -387,6 +530,10 @@ impl Lexer {
     }
 
     fn read_string(&mut self) -> Token {
+        if self.peek_str(3) == "\"\"\"" {
+            return self.read_block_string();
+        }
+
         self.advance(); // skip opening quote
         let mut s = String::new();
         while let Some(c) = self.current() {
@@ -394,12 +541,44 @@ impl Lexer {
                 self.advance(); // skip closing quote
                 break;
             }
+            // エスケープ: \n \t \" \\ (改行そのものもそのまま保持する)
+            if c == '\\' {
+                if let Some(next) = self.input.get(self.pos + 1).copied() {
+                    let escaped = match next {
+                        'n' => Some('\n'),
+                        't' => Some('\t'),
+                        '"' => Some('"'),
+                        '\\' => Some('\\'),
+                        _ => None,
+                    };
+                    if let Some(e) = escaped {
+                        s.push(e);
+                        self.advance_by(2);
+                        continue;
+                    }
+                }
+            }
             s.push(c);
             self.advance();
         }
         Token::String(s)
     }
 
+    /// """...""" 形式の複数行文字列 (エスケープなし、共通のインデントを除去)
+    fn read_block_string(&mut self) -> Token {
+        self.advance_by(3); // skip opening quotes
+        let mut raw = String::new();
+        while self.current().is_some() {
+            if self.peek_str(3) == "\"\"\"" {
+                self.advance_by(3); // skip closing quotes
+                break;
+            }
+            raw.push(self.current().unwrap());
+            self.advance();
+        }
+        Token::String(dedent_block(&raw))
+    }
+
     fn read_identifier(&mut self) -> String {
         let mut ident = String::new();
         while let Some(c) = self.current() {
@@ -462,272 +641,349 @@ impl Lexer {
         word
     }
 
+    /// 残りの入力をすべて読んでトークン列にする (Iterator として1つずつ読むこともできる)
     pub fn tokenize(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
-
-        loop {
-            self.skip_whitespace();
+        self.spans.clear();
+        self.span_cursor = (0, Span { line: 1, column: 1 });
+        self.by_ref().collect()
+    }
 
-            match self.current() {
-                None => {
-                    tokens.push(Token::EOF);
-                    break;
+    /// 次の字句を読み、得られたトークン (0個以上) を tokens に追加する
+    /// 入力の終わりに達した (EOF を追加した) ら false
+    fn scan_token(&mut self, tokens: &mut VecDeque<Token>) -> bool {
+        match self.current() {
+            None => {
+                tokens.extend(self.held_comments.drain(..).map(Token::Comment));
+                tokens.push_back(Token::EOF);
+                return false;
+            }
+            Some('\n') | Some('\r') => {
+                if self.paren_depth == 0 {
+                    tokens.extend(self.held_comments.drain(..).map(Token::Comment));
+                    tokens.push_back(Token::Newline);
                 }
-                Some('\n') | Some('\r') => {
-                    tokens.push(Token::Newline);
+                self.advance();
+                if self.current() == Some('\n') {
                     self.advance();
-                    if self.current() == Some('\n') {
-                        self.advance();
-                    }
                 }
-                Some('"') => {
-                    tokens.push(self.read_string());
-                }
-                Some(c) if c.is_ascii_digit() => {
-                    tokens.push(self.read_number());
-                }
-                Some(c) if c.is_ascii_alphabetic() => {
-                    // 英語の単語を読む
-                    let word = self.read_english_word();
-                    let word_lower = word.to_lowercase();
-                    
-                    // 英語キーワードチェック
-                    let mut matched = false;
-                    for (kw, token_fn) in ENGLISH_KEYWORDS {
-                        if word_lower == *kw {
-                            tokens.push(token_fn());
-                            matched = true;
-                            break;
-                        }
-                    }
-                    if matched {
-                        continue;
-                    }
-                    
-                    // 英語動詞チェック
-                    for verb in KNOWN_EN_VERBS {
-                        if word_lower == *verb {
-                            tokens.push(Token::Verb(word_lower.clone()));
-                            matched = true;
-                            break;
-                        }
-                    }
-                    if matched {
-                        continue;
-                    }
-                    
-                    // 英語副詞チェック
-                    for adverb in KNOWN_ADVERBS {
-                        if word_lower == *adverb {
-                            tokens.push(Token::Adverb(word_lower.clone()));
-                            matched = true;
-                            break;
-                        }
+            }
+            Some('"') => {
+                tokens.push_back(self.read_string());
+            }
+            Some('`') => {
+                // `徳` のように囲むとキーワードでも識別子として扱う
+                self.advance(); // skip opening backtick
+                let mut name = String::new();
+                while let Some(c) = self.current() {
+                    self.advance();
+                    if c == '`' {
+                        break;
                     }
-                    if matched {
-                        continue;
+                    name.push(c);
+                }
+                tokens.push_back(Token::Noun(name));
+            }
+            Some(c) if c.is_ascii_digit() || full_width_digit(c).is_some() => {
+                tokens.push_back(self.read_number());
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                // 英語の単語を読む
+                let word = self.read_english_word();
+                let word_lower = word.to_lowercase();
+                
+                // 英語キーワードチェック
+                let mut matched = false;
+                for (kw, token_fn) in ENGLISH_KEYWORDS {
+                    if word_lower == *kw {
+                        tokens.push_back(token_fn());
+                        matched = true;
+                        break;
                     }
-                    
-                    // それ以外は名詞
-                    tokens.push(Token::Noun(word));
                 }
-                Some('{') => {
-                    tokens.push(Token::LBrace);
-                    self.advance();
+                if matched {
+                    return true;
                 }
-                Some('}') => {
-                    tokens.push(Token::RBrace);
-                    self.advance();
+                
+                // 英語動詞チェック
+                for verb in KNOWN_EN_VERBS {
+                    if word_lower == *verb {
+                        tokens.push_back(Token::Verb(word_lower.clone()));
+                        matched = true;
+                        break;
+                    }
                 }
-                Some('(') => {
-                    tokens.push(Token::LParen);
-                    self.advance();
+                if matched {
+                    return true;
                 }
-                Some(')') => {
-                    tokens.push(Token::RParen);
-                    self.advance();
+                
+                // 英語副詞チェック
+                for adverb in KNOWN_ADVERBS {
+                    if word_lower == *adverb {
+                        tokens.push_back(Token::Adverb(word_lower.clone()));
+                        matched = true;
+                        break;
+                    }
                 }
-                Some(',') | Some('、') => {
-                    tokens.push(Token::Comma);
-                    self.advance();
+                if matched {
+                    return true;
                 }
-                Some('>') => {
-                    tokens.push(Token::KeywordGreaterThan);
+                
+                // それ以外は名詞
+                tokens.push_back(Token::Noun(word));
+            }
+            Some('{') => {
+                tokens.push_back(Token::LBrace);
+                self.advance();
+            }
+            Some('}') => {
+                tokens.push_back(Token::RBrace);
+                self.advance();
+            }
+            Some('(') => {
+                tokens.push_back(Token::LParen);
+                self.paren_depth += 1;
+                self.advance();
+            }
+            Some(')') => {
+                tokens.push_back(Token::RParen);
+                self.paren_depth = self.paren_depth.saturating_sub(1);
+                self.advance();
+            }
+            Some(',') | Some('、') => {
+                tokens.push_back(Token::Comma);
+                self.advance();
+            }
+            Some(':') | Some('：') => {
+                tokens.push_back(Token::Colon);
+                self.advance();
+            }
+            Some('>') => {
+                tokens.push_back(Token::KeywordGreaterThan);
+                self.advance();
+            }
+            Some('<') => {
+                tokens.push_back(Token::KeywordLessThan);
+                self.advance();
+            }
+            Some('=') => {
+                // Check for ==
+                if self.peek_str(1) == "=" {
+                    tokens.push_back(Token::KeywordEquals);
+                    self.advance_by(2);
+                } else {
+                    // Single = is treated as Is/Assignment?
+                    // Or just Equals?
+                    // For now let's treat = as Equals for condition compatibility
+                    tokens.push_back(Token::KeywordEquals);
                     self.advance();
                 }
-                Some('<') => {
-                    tokens.push(Token::KeywordLessThan);
+            }
+            Some('-') => {
+                // Check for negative number
+                let is_negative_number = if let Some(next) = self.input.get(self.pos + 1) {
+                    next.is_ascii_digit()
+                } else {
+                    false
+                };
+
+                if is_negative_number {
+                    self.advance(); // consume '-'
+                    let token = self.read_number();
+                    let token = match token {
+                        Token::Number(n) => Token::Number(-n),
+                        Token::Distance { value, unit } => Token::Distance { value: -value, unit },
+                        Token::Duration { value, unit } => Token::Duration { value: -value, unit },
+                        Token::Percent { value } => Token::Percent { value: -value },
+                        _ => token,
+                    };
+                    tokens.push_back(token);
+                } else {
+                    // Just a hyphen (maybe for separating words? or unknown)
+                    // For now, skip it like before to avoid breaking other things
                     self.advance();
                 }
-                Some('=') => {
-                    // Check for ==
-                    if self.peek_str(1) == "=" {
-                        tokens.push(Token::KeywordEquals);
-                        self.advance_by(2);
-                    } else {
-                        // Single = is treated as Is/Assignment?
-                        // Or just Equals?
-                        // For now let's treat = as Equals for condition compatibility
-                        tokens.push(Token::KeywordEquals);
+            }
+            Some(_) => {
+                // コメント除去
+                if self.peek_str(2) == "//" {
+                    let text_start = self.pos + 2;
+                    while let Some(c) = self.current() {
+                        if c == '\n' { break; }
                         self.advance();
                     }
+                    if self.retain_comments {
+                        let text = self.input[text_start..self.pos].iter().collect();
+                        if self.paren_depth > 0 {
+                            self.held_comments.push(text);
+                        } else {
+                            tokens.push_back(Token::Comment(text));
+                        }
+                    }
+                    return true;
                 }
-                Some('-') => {
-                    // Check for negative number
-                    let is_negative_number = if let Some(next) = self.input.get(self.pos + 1) {
+
+                // Dot (.) check
+                if self.current() == Some('.') {
+                    // Check if next char is digit (fractional number)
+                    let is_fraction = if let Some(next) = self.input.get(self.pos + 1) {
                         next.is_ascii_digit()
                     } else {
                         false
                     };
 
-                    if is_negative_number {
-                        self.advance(); // consume '-'
-                        let token = self.read_number();
-                        let token = match token {
-                            Token::Number(n) => Token::Number(-n),
-                            Token::Distance { value, unit } => Token::Distance { value: -value, unit },
-                            Token::Duration { value, unit } => Token::Duration { value: -value, unit },
-                            _ => token,
-                        };
-                        tokens.push(token);
+                    if is_fraction {
+                        tokens.push_back(self.read_number());
                     } else {
-                        // Just a hyphen (maybe for separating words? or unknown)
-                        // For now, skip it like before to avoid breaking other things
+                        tokens.push_back(Token::Dot);
                         self.advance();
                     }
+                    return true;
                 }
-                Some(_) => {
-                    // コメント除去
-                    if self.peek_str(2) == "//" {
-                        while let Some(c) = self.current() {
-                            if c == '\n' { break; }
-                            self.advance();
-                        }
-                        continue;
-                    }
 
-                    // Dot (.) check
-                    if self.current() == Some('.') {
-                        // Check if next char is digit (fractional number)
-                        let is_fraction = if let Some(next) = self.input.get(self.pos + 1) {
-                            next.is_ascii_digit()
-                        } else {
-                            false
-                        };
-
-                        if is_fraction {
-                            tokens.push(self.read_number());
-                        } else {
-                            tokens.push(Token::Dot);
-                            self.advance();
-                        }
-                        continue;
-                    }
+                // キーワードで始まる複合名詞は識別子として読む (上部分 なども一語のまま)
+                if let Some(noun) = COMPOUND_NOUNS.iter().find(|noun| self.peek_str(noun.chars().count()) == **noun) {
+                    self.advance_by(noun.chars().count());
+                    tokens.push_back(Token::Noun(format!("{}{}", noun, self.read_identifier())));
+                    return true;
+                }
 
-                    // 日本語キーワードチェック (MUST come before particle check!)
-                    // This ensures ならば is matched before なが matched as a particle
-                    let mut matched_kw = false;
-                    for (kw, token_fn) in JAPANESE_KEYWORDS {
-                        let kw_len = kw.chars().count();
-                        if self.peek_str(kw_len) == *kw {
-                            tokens.push(token_fn());
-                            self.advance_by(kw_len);
-                            matched_kw = true;
-                            break;
-                        }
-                    }
-                    if matched_kw {
-                        continue;
+                // 日本語キーワードチェック (MUST come before particle check!)
+                // This ensures ならば is matched before なが matched as a particle
+                let mut matched_kw = false;
+                for (kw, token_fn) in JAPANESE_KEYWORDS {
+                    let kw_len = kw.chars().count();
+                    if self.peek_str(kw_len) == *kw {
+                        tokens.push_back(token_fn());
+                        self.advance_by(kw_len);
+                        matched_kw = true;
+                        break;
                     }
+                }
+                if matched_kw {
+                    return true;
+                }
 
-                    // 日本語助詞チェック (after keyword check)
-                    if self.peek_str(1) == "は" {
-                        tokens.push(Token::ParticleWa);
-                        self.advance();
-                        continue;
-                    }
-                    if self.peek_str(1) == "に" {
-                        tokens.push(Token::ParticleNi);
-                        self.advance();
-                        continue;
-                    }
-                    if self.peek_str(1) == "を" {
-                        tokens.push(Token::ParticleWo);
-                        self.advance();
-                        continue;
-                    }
-                    if self.peek_str(1) == "だ" {
-                        tokens.push(Token::ParticleDa);
-                        self.advance();
-                        continue;
-                    }
-                    if self.peek_str(1) == "な" {
-                        tokens.push(Token::ParticleNa);
-                        self.advance();
-                        continue;
-                    }
-                    if self.peek_str(1) == "の" {
-                        tokens.push(Token::ParticleNo);
-                        self.advance();
-                        continue;
-                    }
-                    if self.peek_str(1) == "と" {
-                        tokens.push(Token::ParticleTo);
-                        self.advance();
-                        continue;
-                    }
-                    if self.peek_str(1) == "が" {
-                        tokens.push(Token::ParticleGa);
-                        self.advance();
-                        continue;
-                    }
+                // 日本語助詞チェック (after keyword check)
+                if self.peek_str(1) == "は" {
+                    tokens.push_back(Token::ParticleWa);
+                    self.advance();
+                    return true;
+                }
+                if self.peek_str(1) == "に" {
+                    tokens.push_back(Token::ParticleNi);
+                    self.advance();
+                    return true;
+                }
+                if self.peek_str(1) == "を" {
+                    tokens.push_back(Token::ParticleWo);
+                    self.advance();
+                    return true;
+                }
+                if self.peek_str(1) == "だ" {
+                    tokens.push_back(Token::ParticleDa);
+                    self.advance();
+                    return true;
+                }
+                if self.peek_str(1) == "な" {
+                    tokens.push_back(Token::ParticleNa);
+                    self.advance();
+                    return true;
+                }
+                if self.peek_str(1) == "の" {
+                    tokens.push_back(Token::ParticleNo);
+                    self.advance();
+                    return true;
+                }
+                if self.peek_str(1) == "と" {
+                    tokens.push_back(Token::ParticleTo);
+                    self.advance();
+                    return true;
+                }
+                if self.peek_str(1) == "が" {
+                    tokens.push_back(Token::ParticleGa);
+                    self.advance();
+                    return true;
+                }
 
-                    // 既知の副詞チェック
-                    let mut matched_adv = false;
-                    for adverb in KNOWN_ADVERBS {
-                        let adv_len = adverb.chars().count();
-                        if self.peek_str(adv_len) == *adverb {
-                            tokens.push(Token::Adverb(adverb.to_string()));
-                            self.advance_by(adv_len);
-                            matched_adv = true;
-                            break;
-                        }
-                    }
-                    if matched_adv {
-                        continue;
+                // 既知の副詞チェック
+                let mut matched_adv = false;
+                for adverb in KNOWN_ADVERBS {
+                    let adv_len = adverb.chars().count();
+                    if self.peek_str(adv_len) == *adverb {
+                        tokens.push_back(Token::Adverb(adverb.to_string()));
+                        self.advance_by(adv_len);
+                        matched_adv = true;
+                        break;
                     }
+                }
+                if matched_adv {
+                    return true;
+                }
 
-                    // 既知の日本語動詞チェック
-                    let mut matched_verb = false;
-                    for verb in KNOWN_JP_VERBS {
-                        let verb_len = verb.chars().count();
-                        if self.peek_str(verb_len) == *verb {
-                            tokens.push(Token::Verb(verb.to_string()));
-                            self.advance_by(verb_len);
-                            matched_verb = true;
-                            break;
-                        }
-                    }
-                    if matched_verb {
-                        continue;
+                // 既知の日本語動詞チェック
+                let mut matched_verb = false;
+                for verb in KNOWN_JP_VERBS {
+                    let verb_len = verb.chars().count();
+                    if self.peek_str(verb_len) == *verb {
+                        tokens.push_back(Token::Verb(verb.to_string()));
+                        self.advance_by(verb_len);
+                        matched_verb = true;
+                        break;
                     }
+                }
+                if matched_verb {
+                    return true;
+                }
 
-                    // それ以外は名詞として読む
-                    let ident = self.read_identifier();
-                    if !ident.is_empty() {
-                        tokens.push(Token::Noun(ident));
-                    } else {
-                        // 未知の文字はスキップ
-                        self.advance();
-                    }
+                // それ以外は名詞として読む
+                let ident = self.read_identifier();
+                if !ident.is_empty() {
+                    // 真/偽 は単独の語のときだけ真偽値 (写真 などの一部は名詞のまま)
+                    tokens.push_back(match ident.as_str() {
+                        "真" => Token::Bool(true),
+                        "偽" => Token::Bool(false),
+                        _ => Token::Noun(ident),
+                    });
+                } else {
+                    // 未知の文字はスキップ
+                    self.advance();
                 }
             }
         }
+        true
+    }
+}
+
+/// トークンを1つずつ読む。入力の終わりで Token::EOF を返し、その後は None
+impl Iterator for Lexer {
+    type Item = Token;
 
-        tokens
+    fn next(&mut self) -> Option<Token> {
+        while self.pending.is_empty() && !self.finished {
+            self.skip_whitespace();
+            let token_start = self.pos;
+            let mut pending = std::mem::take(&mut self.pending);
+            self.finished = !self.scan_token(&mut pending);
+            self.pending = pending;
+            // 1回の読み取りで得たトークンは同じ開始位置を持つ
+            if !self.pending.is_empty() {
+                let span = self.span_at(token_start);
+                self.spans.extend(std::iter::repeat_n(span, self.pending.len()));
+            }
+        }
+        self.pending.pop_front()
     }
 }
 
+/// キーワードトークンの綴りを返す (日本語を優先)。キーワードでなければ None
+pub fn keyword_text(token: &Token) -> Option<&'static str> {
+    JAPANESE_KEYWORDS
+        .iter()
+        .chain(ENGLISH_KEYWORDS.iter())
+        .find(|(_, token_fn)| token_fn() == *token)
+        .map(|(kw, _)| *kw)
+}
+
 fn is_japanese_char(c: char) -> bool {
     let code = c as u32;
     (0x3040..=0x309F).contains(&code)  // ひらがな
@@ -736,6 +992,33 @@ fn is_japanese_char(c: char) -> bool {
         || (0x3400..=0x4DBF).contains(&code)  // CJK統合漢字拡張A
 }
 
+/// 全角数字 (０〜９) を半角に変換する
+fn full_width_digit(c: char) -> Option<char> {
+    ('０'..='９').contains(&c).then(|| char::from(b'0' + (c as u32 - '０' as u32) as u8))
+}
+
+/// ブロック文字列の整形: 開始直後と終了直前の空行を除き、共通のインデントを取り除く
+fn dedent_block(raw: &str) -> String {
+    let mut lines: Vec<&str> = raw.split('\n').collect();
+    if lines.first().is_some_and(|l| l.trim().is_empty()) {
+        lines.remove(0);
+    }
+    if lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+    let indent = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or_else(|| l.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -848,6 +1131,35 @@ mod tests {
         assert_eq!(tokens[0], Token::Distance { value: 5.0, unit: "km".to_string() });
     }
 
+    #[test]
+    fn test_scientific_notation() {
+        let tokens = Lexer::new("1e3 2.5e-2 4E+1").tokenize();
+        assert_eq!(tokens[0], Token::Number(1000.0));
+        assert_eq!(tokens[1], Token::Number(0.025));
+        assert_eq!(tokens[2], Token::Number(40.0));
+
+        // 指数の後の単位はそのまま距離として読む。数字の続かない e は指数ではない
+        let tokens = Lexer::new("1e3m 1m").tokenize();
+        assert_eq!(tokens[0], Token::Distance { value: 1000.0, unit: "m".to_string() });
+        assert_eq!(tokens[1], Token::Distance { value: 1.0, unit: "m".to_string() });
+    }
+
+    #[test]
+    fn test_newline_suppressed_inside_parens() {
+        let tokens = Lexer::new("F(\n  1,\n  2\n)\nX").tokenize();
+        let newlines = tokens.iter().filter(|t| **t == Token::Newline).count();
+        assert_eq!(newlines, 1);
+        assert_eq!(tokens[tokens.len() - 3], Token::Newline);
+    }
+
+    #[test]
+    fn test_percent_literal() {
+        let tokens = Lexer::new("50% ５０％ 12.5%").tokenize();
+        assert_eq!(tokens[0], Token::Percent { value: 50.0 });
+        assert_eq!(tokens[1], Token::Percent { value: 50.0 });
+        assert_eq!(tokens[2], Token::Percent { value: 12.5 });
+    }
+
     #[test]
     fn test_duration_literal_minutes() {
         let mut lexer = Lexer::new("5分後");
@@ -865,6 +1177,16 @@ mod tests {
         assert_eq!(tokens[3], Token::ParticleDe);     // で
     }
 
+    #[test]
+    fn test_spatial_search_order_keywords() {
+        let mut lexer = Lexer::new("人 を 近い順 に 探す\n人 を 徳の高い順 に 探す");
+        let tokens = lexer.tokenize();
+        assert_eq!(tokens[2], Token::KeywordNearestFirst);
+        assert_eq!(tokens[3], Token::ParticleNi);
+        assert_eq!(tokens[4], Token::Verb("探す".to_string()));
+        assert_eq!(tokens[8], Token::KeywordTokuFirst);
+    }
+
     // === AGN 2.0 (Social Layer) Tests ===
 
     #[test]
@@ -905,4 +1227,96 @@ mod tests {
         assert_eq!(tokens[4], Token::ParticleWo);
         assert_eq!(tokens[5], Token::KeywordUpdate);
     }
+
+    #[test]
+    fn test_token_spans() {
+        let mut lexer = Lexer::new("X は 10 だ\n  Y は 2 だ");
+        let (tokens, spans) = lexer.tokenize_with_spans();
+        assert_eq!(tokens.len(), spans.len());
+        assert_eq!(spans[0], Span { line: 1, column: 1 });
+        assert_eq!(spans[2], Span { line: 1, column: 5 });
+        // "Y" は2行目の3列目
+        assert_eq!(tokens[5], Token::Noun("Y".to_string()));
+        assert_eq!(spans[5], Span { line: 2, column: 3 });
+    }
+
+    #[test]
+    fn test_at_least_condition() {
+        let mut lexer = Lexer::new("徳 が 500 以上 の とき");
+        let tokens = lexer.tokenize();
+        assert_eq!(tokens[0], Token::KeywordToku);
+        assert_eq!(tokens[1], Token::ParticleGa);
+        assert_eq!(tokens[2], Token::Number(500.0));
+        assert_eq!(tokens[3], Token::KeywordAtLeast);
+        assert_eq!(tokens[4], Token::ParticleNo);
+        assert_eq!(tokens[5], Token::KeywordWhen);
+    }
+
+    #[test]
+    fn test_copula_variants() {
+        for code in ["X は 10 だった", "X は 10 です", "X は 10 でした"] {
+            let tokens = Lexer::new(code).tokenize();
+            assert_eq!(tokens[3], Token::ParticleDa, "{}", code);
+        }
+        let tokens = Lexer::new("X は 10 ではない").tokenize();
+        assert_eq!(tokens[3], Token::KeywordNotEqual);
+    }
+
+    #[test]
+    fn test_multiline_strings() {
+        // 通常の文字列: 改行とエスケープ
+        let tokens = Lexer::new("X は \"一行目\n二行目\\n三行目\" だ").tokenize();
+        assert_eq!(tokens[2], Token::String("一行目\n二行目\n三行目".to_string()));
+
+        // 三重引用符: 共通インデントを除去
+        let code = "投稿 は \"\"\"\n    今日は\n      良い天気\n    でした\n    \"\"\" だ";
+        let tokens = Lexer::new(code).tokenize();
+        assert_eq!(tokens[2], Token::String("今日は\n  良い天気\nでした".to_string()));
+        assert_eq!(tokens[3], Token::ParticleDa);
+    }
+
+    #[test]
+    fn test_keyword_text() {
+        assert_eq!(keyword_text(&Token::KeywordToku), Some("徳"));
+        assert_eq!(keyword_text(&Token::KeywordIf), Some("もし"));
+        assert_eq!(keyword_text(&Token::Noun("X".to_string())), None);
+    }
+
+    #[test]
+    fn test_compound_nouns_are_identifiers() {
+        let tokens = Lexer::new("上部 は 上部分 だ").tokenize();
+        assert_eq!(tokens[0], Token::Noun("上部".to_string()));
+        assert_eq!(tokens[2], Token::Noun("上部分".to_string()));
+        assert_eq!(tokens[3], Token::ParticleDa);
+
+        let tokens = Lexer::new("徳を送る(A, B, 1)").tokenize();
+        assert_eq!(tokens[0], Token::Noun("徳を送る".to_string()));
+        assert_eq!(tokens[1], Token::LParen);
+        assert_eq!(keyword_text(&tokens[0]), None);
+    }
+
+    #[test]
+    fn test_backtick_identifier() {
+        let tokens = Lexer::new("`徳` は 10 だ").tokenize();
+        assert_eq!(tokens[0], Token::Noun("徳".to_string()));
+        assert_eq!(tokens[1], Token::ParticleWa);
+        assert_eq!(tokens[2], Token::Number(10.0));
+    }
+
+    #[test]
+    fn test_iterator_matches_tokenize() {
+        let code = "甲 は (1,\n 2) だ\nもし 甲 > -3 ならば\n  \"hi\" を 画面 に 表示する\nおわり";
+        let expected = Lexer::new(code).tokenize();
+
+        let mut lexer = Lexer::new(code);
+        let streamed: Vec<Token> = lexer.by_ref().collect();
+        assert_eq!(streamed, expected);
+        assert_eq!(lexer.spans.len(), streamed.len());
+        // EOF の後は何も返さない
+        assert_eq!(lexer.next(), None);
+
+        // 必要な分だけ読む
+        let first: Vec<Token> = Lexer::new(code).take(2).collect();
+        assert_eq!(first, expected[..2]);
+    }
 }
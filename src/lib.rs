@@ -5,6 +5,7 @@ pub mod symbol_table;
 pub mod interpreter;
 pub mod normalizer;
 pub mod type_inferencer;
+pub mod formatter;
 pub mod ai_analyzer;
 pub mod codegen;
 pub mod compiler;
@@ -12,9 +13,12 @@ pub mod memory;
 pub mod ai_runtime;
 pub mod web_generator;
 #[cfg(not(target_arch = "wasm32"))]
+pub mod benchmark;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod native_window;
 pub mod graphics;
 pub mod utils;
+pub mod messages;
 // Eeyo: P2P通信層
 pub mod p2p;
 pub mod bridge;
@@ -48,7 +52,7 @@ pub async fn run_script(source: String, _canvas_id: Option<String>) -> Result<()
 
     // 2. Parse
     let mut lexer = crate::lexer::Lexer::new(&source);
-    let tokens = lexer.tokenize();
+    let (tokens, spans) = lexer.tokenize_with_spans();
     
     // Debug: Log first 20 tokens
     log::info!("[AGN Debug] First 20 tokens:");
@@ -56,7 +60,7 @@ pub async fn run_script(source: String, _canvas_id: Option<String>) -> Result<()
         log::info!("  Token {}: {:?}", i, tok);
     }
     
-    let mut parser = crate::parser::Parser::new(tokens);
+    let mut parser = crate::parser::Parser::with_spans(tokens, spans);
     let program = match parser.parse() {
         Ok(p) => p,
         Err(e) => return Err(JsValue::from_str(&format!("Parse Error: {}", e))),
@@ -109,34 +113,34 @@ pub async fn handle_event(target: String, event_type: String) {
     };
 
     if let Some(interpreter) = interpreter_opt {
-         let handler_body = {
-             let handlers = interpreter.event_handlers.lock().unwrap();
-             handlers.get(&(target.clone(), event_type.clone())).cloned()
-         };
-
-         if let Some(body) = handler_body {
-             log::info!("[AGN] Handling event: {} on {}", event_type, target);
-             
-             // Execute handler body
-             // Need to push context? Maybe.
-             // If handler uses "self", we should push target to context stack.
-             {
-                 let mut stack = interpreter.context_stack.lock().unwrap();
-                 stack.push(target.clone());
-             }
-             
-             interpreter.execute_statements(&body).await;
-             
-             {
-                 let mut stack = interpreter.context_stack.lock().unwrap();
-                 stack.pop();
-             }
-         } else {
-             log::warn!("[AGN] No handler found for event: {} on {}", event_type, target);
-         }
+         // 間引き (… 間隔 で) と "self" のコンテキストは dispatch_event が扱う
+         interpreter.dispatch_event(&target, &event_type).await;
+    }
+}
+
+/// セッション状態を保存 (JSON)。ページ再読み込み前に localStorage 等へ退避する
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn eeyo_save_session() -> Result<String, JsValue> {
+    let guard = GLOBAL_INTERPRETER.lock().unwrap();
+    match guard.as_ref() {
+        Some(interpreter) => Ok(interpreter.serialize_session()),
+        None => Err(JsValue::from_str("セッションがありません")),
     }
 }
 
+/// 保存したセッション状態を復元 (インタプリタがなければ作成する)
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn eeyo_load_session(data: &str) -> Result<(), JsValue> {
+    use crate::bridge::std_bridge::{StdP2PBridge, StdUIManager};
+    let mut guard = GLOBAL_INTERPRETER.lock().unwrap();
+    let interpreter = guard.get_or_insert_with(|| {
+        crate::interpreter::Interpreter::with_bridges(Arc::new(StdP2PBridge), Arc::new(StdUIManager))
+    });
+    interpreter.restore_session(data).map_err(|e| JsValue::from_str(&e))
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(start)]
 pub fn main_js() {
@@ -203,6 +207,14 @@ pub async fn eeyo_search_nearby(max_distance: f64) -> Result<String, JsValue> {
         .map_err(|e| JsValue::from_str(&format!("JSON変換エラー: {}", e)))
 }
 
+/// ピアをブロック（検索結果から除外）
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn eeyo_block_peer(peer_id: &str) {
+    log::info!("[Eeyo WASM] ピアをブロック: {}", peer_id);
+    crate::p2p::agn_block_peer(peer_id);
+}
+
 /// 徳スコアを取得
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
@@ -285,6 +297,7 @@ pub fn eeyo_parse_secure_packet(packet_bytes: &[u8]) -> Option<String> {
             "nonce": packet.nonce,
             "timestamp": packet.timestamp,
             "sender_public_key": hex::encode(packet.sender_public_key),
+            "peer_id": crate::p2p::peer_id_from_pubkey(&packet.sender_public_key),
             "signature": hex::encode(packet.signature),
         });
         Some(json.to_string())
@@ -297,39 +310,41 @@ pub fn eeyo_parse_secure_packet(packet_bytes: &[u8]) -> Option<String> {
 // 徳フィード (Social Toku Feed) API
 // ------------------------------------------------------------
 
+/// ソーシャルイベントを伝搬し、セッション中のインタプリタのリスナーに通知する
+/// (eeyo_publish_social_event / eeyo_simulate_gossip 共通)
+pub async fn broadcast_and_trigger(
+    manager: &crate::p2p::P2PManager,
+    interpreter: Option<&crate::interpreter::Interpreter>,
+    event: &crate::p2p::SocialTokuEvent,
+) {
+    manager.broadcast_social_event(event.clone());
+    if let Some(interpreter) = interpreter {
+        interpreter.trigger_social_event(event).await;
+    }
+}
+
+/// セッション中のインタプリタを複製して取得 (await中にロックを保持しないため)
+#[cfg(target_arch = "wasm32")]
+fn session_interpreter() -> Option<crate::interpreter::Interpreter> {
+    GLOBAL_INTERPRETER.lock().unwrap().clone()
+}
+
 /// フィードイベントをシミュレーション（デモ用）
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub fn eeyo_simulate_gossip() -> Result<String, JsValue> {
-    let manager = crate::p2p::P2PManager::new();
+    let manager = crate::p2p::agn_p2p_manager();
     let events = manager.simulate_incoming_gossip();
     
     // イベントフック: P2Pイベントをインタプリタに通知
-    // Lock and clone interpreter
-    let interpreter_opt = {
-        let guard = GLOBAL_INTERPRETER.lock().unwrap();
-        guard.clone()
-    };
-
-    if let Some(interpreter) = interpreter_opt {
-        for event in &events {
-            let type_str = match event.event_type {
-                crate::p2p::SocialEventType::HelpGiven => "HelpGiven",
-                crate::p2p::SocialEventType::ThankYou => "ThankYou",
-                crate::p2p::SocialEventType::PassedBy => "PassedBy",
-                crate::p2p::SocialEventType::TokuSent { .. } => "TokuSent",
-            };
-            
-            let interpreter = interpreter.clone();
-            let from_id = event.actor_id.clone();
-            let to_id = event.target_id.clone();
-            let type_string = type_str.to_string();
-            
-            // Spawn async task to handle event
-            wasm_bindgen_futures::spawn_local(async move {
-                interpreter.trigger_event(&type_string, &from_id, &to_id).await;
-            });
-        }
+    for event in &events {
+        let interpreter = session_interpreter();
+        let event = event.clone();
+        
+        // Spawn async task to handle event
+        wasm_bindgen_futures::spawn_local(async move {
+            broadcast_and_trigger(manager, interpreter.as_ref(), &event).await;
+        });
     }
     
     serde_json::to_string(&events)
@@ -344,23 +359,26 @@ pub fn eeyo_publish_social_event(
     target_id: &str, 
     message: Option<String>
 ) -> Result<String, JsValue> {
-    use crate::p2p::{SocialTokuEvent, SocialEventType, P2PManager};
+    use crate::p2p::{SocialTokuEvent, SocialEventType};
     
-    let event_type = match event_type_str {
-        "help_given" | "助けた" => SocialEventType::HelpGiven,
-        "thank_you" | "ありがとう" => SocialEventType::ThankYou,
-        "passed_by" | "すれ違い" => SocialEventType::PassedBy,
-        _ => return Err(JsValue::from_str("不明なイベントタイプ")),
-    };
+    if event_type_str.is_empty() {
+        return Err(JsValue::from_str("イベントタイプが空です"));
+    }
+    // 既知の名前以外はアプリ独自のイベント (Custom) として扱う
+    let event_type = SocialEventType::from_name(event_type_str);
     
     let mut event = SocialTokuEvent::new("current_user", target_id, event_type);
     if let Some(msg) = message {
         event = event.with_message(&msg);
     }
     
-    // イベントをブロードキャスト（シミュレーション）
-    let manager = P2PManager::new();
-    manager.broadcast_social_event(event.clone());
+    // イベントをブロードキャスト（シミュレーション）し、スクリプトのリスナーにも通知する
+    let manager = crate::p2p::agn_p2p_manager();
+    let interpreter = session_interpreter();
+    let published = event.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        broadcast_and_trigger(manager, interpreter.as_ref(), &published).await;
+    });
     
     serde_json::to_string(&event)
         .map_err(|e| JsValue::from_str(&format!("JSON変換エラー: {}", e)))
@@ -370,8 +388,15 @@ pub fn eeyo_publish_social_event(
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub fn eeyo_get_bond(from: &str, to: &str) -> Result<String, JsValue> {
-    let rel = crate::p2p::agn_get_bond(from, to);
-    serde_json::to_string(&rel)
+    Ok(crate::p2p::agn_get_bond(from, to).to_json())
+}
+
+/// ユーザーの絆一覧を取得（JSON形式: [[相手のID, 関係性], ...]）
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn eeyo_list_bonds(user_id: &str) -> Result<String, JsValue> {
+    let bonds = crate::p2p::agn_bonds_for(user_id);
+    serde_json::to_string(&bonds)
         .map_err(|e| JsValue::from_str(&format!("JSON変換エラー: {}", e)))
 }
 
@@ -389,3 +414,53 @@ pub fn eeyo_deepen_bond(from: &str, to: &str, amount: u32) {
 pub fn eeyo_has_bond(from: &str, to: &str) -> bool {
     crate::p2p::agn_has_bond(from, to)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::bridge::p2p::P2PBridge;
+    use crate::p2p::{P2PManager, SocialEventType, SocialTokuEvent};
+
+    #[tokio::test]
+    async fn test_published_event_runs_listener() {
+        let p2p = Arc::new(crate::bridge::mock::MockP2PBridge::new());
+        let interpreter = crate::interpreter::Interpreter::with_bridges(
+            p2p.clone(),
+            Arc::new(crate::bridge::mock::MockUIManager),
+        );
+        let code = r#"
+            on Event(ThankYou) from Alice to Bob {
+                Bob.徳 に 5 を 増やす
+            }
+        "#;
+        let program = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+        interpreter.execute(&program).await;
+
+        let manager = P2PManager::new();
+        let before = p2p.get_toku("Bob");
+        let event = SocialTokuEvent::new("Alice", "Bob", SocialEventType::from_name("ありがとう"));
+        broadcast_and_trigger(&manager, Some(&interpreter), &event).await;
+
+        assert_eq!(p2p.get_toku("Bob"), before + 5);
+        assert_eq!(manager.get_all_feed_events().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_publishes_accumulate_in_global_feed() {
+        let manager = crate::p2p::agn_p2p_manager();
+        let first = SocialTokuEvent::new("feed_a", "feed_b", SocialEventType::ThankYou);
+        let second = SocialTokuEvent::new("feed_b", "feed_a", SocialEventType::HelpGiven);
+
+        broadcast_and_trigger(manager, None, &first).await;
+        broadcast_and_trigger(crate::p2p::agn_p2p_manager(), None, &second).await;
+        // 受信済みのイベントは重複しない
+        broadcast_and_trigger(manager, None, &first).await;
+
+        let feed = crate::p2p::agn_get_all_feed_events().await;
+        assert_eq!(feed.iter().filter(|e| e.id == first.id).count(), 1);
+        assert_eq!(feed.iter().filter(|e| e.id == second.id).count(), 1);
+    }
+}
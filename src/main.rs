@@ -5,11 +5,13 @@
 
 use std::env;
 use std::fs;
-use std::time::Instant;
+use std::sync::Arc;
 
 use agn::lexer::Lexer;
 use agn::parser::Parser;
 use agn::interpreter::Interpreter;
+use agn::benchmark::Benchmark;
+use agn::bridge::std_bridge::{StdP2PBridge, StdUIManager};
 use agn::normalizer::Normalizer;
 use agn::type_inferencer::TypeInferencer;
 use agn::compiler::{self, Compiler};
@@ -25,11 +27,17 @@ fn print_usage() {
     println!("  --compile, -c    Compile to native binary");
     println!("  --run-compiled   Compile and run the binary");
     println!("  --emit-ir        Output LLVM IR only");
+    println!("  --check          Parse and type-check without running (exit 1 on errors)");
     println!("  --verbose, -v    Show detailed output");
     println!("  --tokens         Show tokens");
     println!("  --ast            Show AST");
+    println!("  --emit-ast-json  Output the AST as JSON only");
+    println!("  --format         Print the source in canonical format");
     println!("  --types          Show type inference");
-    println!("  --benchmark      Run benchmark comparison");
+    println!("  --no-vsync       Disable vsync in the native window (Mailbox/Immediate)");
+    println!("  --benchmark      Compare interpreter, native and wasm backends");
+    println!("  --benchmark-iterations N");
+    println!("                   Run each backend N times (mean/median/stddev)");
     println!("  --help, -h       Show this help");
 }
 
@@ -47,11 +55,19 @@ async fn main() {
     let verbose = args.contains(&"--verbose".to_string()) || args.contains(&"-v".to_string());
     let show_tokens = args.contains(&"--tokens".to_string());
     let show_ast = args.contains(&"--ast".to_string());
+    let emit_ast_json = args.contains(&"--emit-ast-json".to_string());
+    let check_only = args.contains(&"--check".to_string());
+    let format_only = args.contains(&"--format".to_string());
     let show_types = args.contains(&"--types".to_string()) || verbose;
     let compile_mode = args.contains(&"--compile".to_string()) || args.contains(&"-c".to_string());
     let run_compiled = args.contains(&"--run-compiled".to_string());
     let emit_ir = args.contains(&"--emit-ir".to_string());
-    let benchmark = args.contains(&"--benchmark".to_string());
+    let vsync = !args.contains(&"--no-vsync".to_string());
+    let benchmark_iterations = args.iter()
+        .position(|r| r == "--benchmark-iterations")
+        .and_then(|idx| args.get(idx + 1))
+        .and_then(|n| n.parse::<usize>().ok());
+    let benchmark = args.contains(&"--benchmark".to_string()) || benchmark_iterations.is_some();
     
     // ターゲット指定
     let target = if args.contains(&"--target".to_string()) {
@@ -69,11 +85,15 @@ async fn main() {
         compiler::Target::Native
     };
     
-    // ソースファイルを探す
+    // ソースファイルを探す (オプションの値は除外)
     let source_file = args.iter()
+        .enumerate()
         .skip(1)
-        .find(|arg| !arg.starts_with('-'))
-        .cloned();
+        .find(|(i, arg)| {
+            !arg.starts_with('-')
+                && !matches!(args[i - 1].as_str(), "--target" | "--benchmark-iterations")
+        })
+        .map(|(_, arg)| arg.clone());
     
     let code = if let Some(ref file) = source_file {
         match fs::read_to_string(file) {
@@ -91,30 +111,75 @@ X を 並列で 表示する
 "計算完了" を 表示する"#.to_string()
     };
 
-    println!("=== AGN (Antigravity-Native) Phase 3 ===\n");
+    // チェックモード: 診断だけを出して終了コードで結果を返す
+    if check_only {
+        let diagnostics = compiler::check(&code);
+        for diagnostic in &diagnostics {
+            eprintln!("{}", diagnostic);
+        }
+        if !diagnostics.is_empty() {
+            std::process::exit(1);
+        }
+        println!("OK");
+        return;
+    }
+
+    // 整形モード: 解析した AST を正規化したソースとして出力する
+    if format_only {
+        let (normalized, _corrections) = Normalizer::new().normalize(&code);
+        let mut lexer = Lexer::with_comments(&normalized);
+        match Parser::new(lexer.tokenize()).parse() {
+            Ok(program) => print!("{}", agn::formatter::format_program(&program)),
+            Err(e) => {
+                eprintln!("Parse error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // --emit-ast-json では標準出力を JSON だけにする
+    if !emit_ast_json {
+        println!("=== AGN (Antigravity-Native) Phase 3 ===\n");
+    }
 
     // 1. 正規化
     let normalizer = Normalizer::new();
-    let (normalized_code, corrections) = normalizer.normalize(&code);
+    let (normalized_code, corrections, position_map) = normalizer.normalize_with_map(&code);
     
-    if !corrections.is_empty() && (verbose || !compile_mode) {
+    if !corrections.is_empty() && (verbose || !compile_mode) && !emit_ast_json {
         println!("{}", normalizer.format_corrections(&corrections));
     }
 
+    // 閉じ忘れたブロックがあれば挿入位置を提案する (字下げを見るため正規化前のコードで)
+    for suggestion in normalizer.suggest_block_closers(&code) {
+        eprintln!("[SUGGEST] {}", suggestion);
+    }
+
+    let output_name = source_file
+        .as_ref()
+        .map(|f| {
+            std::path::Path::new(f)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("program")
+                .to_string()
+        })
+        .unwrap_or_else(|| "program".to_string());
+    let output_dir = std::path::Path::new("./output");
+
+    // ベンチマークモード: 全バックエンドで実行して比較
+    if benchmark {
+        println!("=== Benchmark ===");
+        let mut bench = Benchmark::new(output_dir, &output_name);
+        bench.set_iterations(benchmark_iterations.unwrap_or(1));
+        let result = bench.run(&normalized_code).await;
+        println!("{}", result.to_table());
+        return;
+    }
+
     // コンパイルモード
-    if compile_mode || run_compiled || emit_ir || benchmark || target == compiler::Target::Wasm {
-        let output_name = source_file
-            .as_ref()
-            .map(|f| {
-                std::path::Path::new(f)
-                    .file_stem()
-                    .and_then(|s| s.to_str())
-                    .unwrap_or("program")
-                    .to_string()
-            })
-            .unwrap_or_else(|| "program".to_string());
-        
-        let output_dir = std::path::Path::new("./output");
+    if compile_mode || run_compiled || emit_ir || target == compiler::Target::Wasm {
         let mut compiler_instance = Compiler::new(output_dir);
         compiler_instance.set_verbose(verbose);
         compiler_instance.set_target(target.clone());
@@ -139,41 +204,10 @@ X を 並列で 表示する
                     println!("{}", result.ir_content);
                 }
                 
-                if run_compiled || benchmark {
+                if run_compiled {
                     println!("\n=== Native Execution ===");
-                    let native_start = Instant::now();
                     match result.run() {
-                        Ok(output) => {
-                            print!("{}", output);
-                            let _native_duration = native_start.elapsed();
-                            print!("{}", output);
-                            let native_duration = native_start.elapsed();
-                            
-                            if benchmark {
-                                // インタプリタでも実行して比較
-                                println!("\n=== Interpreter Execution ===");
-                                let mut lexer = Lexer::new(&normalized_code);
-                                let tokens = lexer.tokenize();
-                                let mut parser = Parser::new(tokens);
-                                
-                                if let Ok(program) = parser.parse() {
-                                    let interp_start = Instant::now();
-                                    let interpreter = Interpreter::new();
-                                    interpreter.execute(&program).await;
-                                    let interp_duration = interp_start.elapsed();
-                                    
-                                    println!("\n=== Benchmark Results ===");
-                                    println!("  Native:      {:?}", native_duration);
-                                    println!("  Interpreter: {:?}", interp_duration);
-                                    
-                                    if native_duration < interp_duration {
-                                        let speedup = interp_duration.as_nanos() as f64 
-                                            / native_duration.as_nanos() as f64;
-                                        println!("  Speedup:     {:.2}x faster", speedup);
-                                    }
-                                }
-                            }
-                        }
+                        Ok(output) => print!("{}", output),
                         Err(e) => eprintln!("Execution error: {}", e),
                     }
                 }
@@ -190,7 +224,9 @@ X を 並列で 表示する
     // インタプリタモード
     // 2. 字句解析
     let mut lexer = Lexer::new(&normalized_code);
-    let tokens = lexer.tokenize();
+    // 実行時エラーに行・列を付けるため位置情報付きで解析する (位置は正規化前のソースに戻す)
+    let (tokens, spans) = lexer.tokenize_with_spans();
+    let spans = position_map.spans_to_original(spans);
     
     if show_tokens || verbose {
         println!("=== Tokens ===");
@@ -201,9 +237,17 @@ X を 並列で 表示する
     }
 
     // 3. 構文解析
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::with_spans(tokens, spans);
     match parser.parse() {
         Ok(program) => {
+            if emit_ast_json {
+                match serde_json::to_string_pretty(&program) {
+                    Ok(json) => println!("{}", json),
+                    Err(e) => eprintln!("Error serializing AST: {}", e),
+                }
+                return;
+            }
+
             if show_ast || verbose {
                 println!("=== AST ===");
                 for stmt in &program.statements {
@@ -230,8 +274,16 @@ X を 並列で 表示する
             }
 
             // 6. 実行
-            if target == compiler::Target::NativeWindow {
+            // ウィンドウや GPU が使えない環境ではコンソールモードで実行する
+            let native = if target == compiler::Target::NativeWindow {
                 println!("=== Native Window Mode ===");
+                native_window::init_native_window(vsync)
+                    .map_err(|e| eprintln!("[Native] ウィンドウを開けませんでした ({})。コンソールモードで実行します", e))
+                    .ok()
+            } else {
+                None
+            };
+            if let Some(window) = native {
                 let (tx, rx) = std::sync::mpsc::channel();
                 
                 {
@@ -246,7 +298,7 @@ X を 並列で 表示する
                 std::thread::spawn(move || {
                     let rt = tokio::runtime::Runtime::new().unwrap();
                     rt.block_on(async {
-                       let interpreter = Interpreter::with_symbol_table(symbol_table);
+                       let interpreter = Interpreter::with_symbol_table(symbol_table, Arc::new(StdP2PBridge), Arc::new(StdUIManager));
                        interpreter.execute(&program_clone).await;
                     });
                 });
@@ -282,16 +334,19 @@ X を 並列で 表示する
                 // Most simple interpreters don't need async locks unless they do IO inside lock.
                 // Let's update main.rs assuming I fix types later or now.
                 // I'll update main.rs to use the same type as interpreter expects.
-                native_window::run_native_window(rx, symbol_table_for_window);
+                native_window::run_native_window(window, rx, symbol_table_for_window);
                 return;
             }
 
             println!("=== Output ===");
-            let interpreter = Interpreter::new();
+            let interpreter = Interpreter::with_bridges(Arc::new(StdP2PBridge), Arc::new(StdUIManager));
             interpreter.execute(&program).await;
         }
         Err(e) => {
-            eprintln!("Parse error: {}", e);
+            match parser.current_span() {
+                Some(span) => eprintln!("Parse error: {}: {}", span, e),
+                None => eprintln!("Parse error: {}", e),
+            }
             eprintln!("\nOriginal code:");
             for (i, line) in code.lines().enumerate() {
                 eprintln!("  {}: {}", i + 1, line);
@@ -99,11 +99,12 @@ impl MemoryManager {
             match &alloc.var_type {
                 InferredType::Number => stats.stack_allocations += 1,
                 InferredType::String => stats.heap_allocations += 1,
-                InferredType::Unknown => stats.unknown += 1,
+                InferredType::Unknown | InferredType::Any => stats.unknown += 1,
                 // Eeyo: 空間・時間型はスタック割当
                 InferredType::Distance { .. } => stats.stack_allocations += 1,
                 InferredType::Duration { .. } => stats.stack_allocations += 1,
                 InferredType::Bond => stats.stack_allocations += 1, // Bond is metadata/ref
+                InferredType::Bool => stats.stack_allocations += 1,
             }
         }
         
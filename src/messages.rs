@@ -0,0 +1,146 @@
+//! AGN Messages - エラーメッセージのカタログ
+//! パーサーのエラー (英語) をロケールに応じて日本語に変換する
+
+use crate::utils::Locale;
+
+/// 期待される要素名の対訳 (英語 -> 日本語)
+const TERMS: &[(&str, &str)] = &[
+    ("verb", "動詞"),
+    ("noun", "名詞"),
+    ("expression", "式"),
+    ("variable name", "変数名"),
+    ("rule name", "ルール名"),
+    ("action name", "アクション名"),
+    ("action verb", "動作の動詞"),
+    ("parameter name", "引数名"),
+    ("element name", "要素名"),
+    ("component noun", "コンポーネント名"),
+    ("modifier noun", "修飾語"),
+    ("style variable", "スタイル変数"),
+    ("style variable or string", "スタイル変数または文字列"),
+    ("event type name", "イベント種別"),
+    ("duration number", "時間の数値"),
+    ("comparison operator", "比較演算子"),
+    ("AI verb", "AI動詞"),
+    ("AI Verb after option", "オプションの後にAI動詞"),
+    ("comma or closing parenthesis", "',' または ')'"),
+    ("filter field", "検索条件の項目"),
+    ("filter value", "検索条件の値"),
+];
+
+/// 字句解析はされるが、その位置では使えないキーワードの説明 (キーワード, 英語, 日本語)
+const UNSUPPORTED_KEYWORDS: &[(&str, &str, &str)] = &[
+    (
+        "かつ",
+        "it can only join conditions, e.g. 'もし A > 1 かつ B > 2 ならば'",
+        "条件をつなぐときだけ使えます (例: 'もし A > 1 かつ B > 2 ならば')",
+    ),
+    (
+        "break",
+        "it can only be used inside a loop ('repeat' or 'while')",
+        "ループ ('繰り返す' や 'の間') の中でだけ使えます",
+    ),
+    (
+        "continue",
+        "it can only be used inside a loop ('repeat' or 'while')",
+        "ループ ('繰り返す' や 'の間') の中でだけ使えます",
+    ),
+    (
+        "やめる",
+        "it can only be used inside a loop, e.g. '3 回 繰り返す ... やめる ... おわり'",
+        "ループの中でだけ使えます (例: '3 回 繰り返す ... やめる ... おわり')",
+    ),
+    (
+        "つぎへ",
+        "it can only be used inside a loop, e.g. '3 回 繰り返す ... つぎへ ... おわり'",
+        "ループの中でだけ使えます (例: '3 回 繰り返す ... つぎへ ... おわり')",
+    ),
+    (
+        "の間",
+        "it can only end a loop condition, e.g. 'X < 10 の間 繰り返す'",
+        "ループの条件の後でだけ使えます (例: 'X < 10 の間 繰り返す')",
+    ),
+    (
+        "または",
+        "it can only join conditions, e.g. 'もし A > 1 または B > 2 ならば'",
+        "条件をつなぐときだけ使えます (例: 'もし A > 1 または B > 2 ならば')",
+    ),
+    ("付ける", "it is reserved and not supported yet", "予約語ですがまだ使えません"),
+    (
+        "ランク",
+        "use it as a property (X.ランク) or a call (ランク(...))",
+        "プロパティ (X.ランク) か呼び出し (ランク(...)) として使ってください",
+    ),
+    (
+        "より遠い",
+        "spatial search only supports 'より近い' for now",
+        "空間検索は今のところ 'より近い' のみ対応しています",
+    ),
+];
+
+/// 未対応キーワードのエラーメッセージ (英語)。対象外のキーワードなら None
+pub fn unsupported_keyword(keyword: &str) -> Option<String> {
+    UNSUPPORTED_KEYWORDS
+        .iter()
+        .find(|(kw, _, _)| *kw == keyword)
+        .map(|(kw, en, _)| format!("Keyword '{}' is not supported here: {}", kw, en))
+}
+
+/// パーサーのエラーメッセージをロケールに合わせて変換する
+/// 日本語以外のロケールでは元の英語メッセージをそのまま返す
+pub fn localize_parse_error(message: &str, locale: Locale) -> String {
+    if locale != Locale::Ja {
+        return message.to_string();
+    }
+
+    if let Some(rest) = message.strip_prefix("Reserved keyword '") {
+        if let Some((kw, _)) = rest.split_once('\'') {
+            return format!("'{}' は予約語のため変数名に使えません。別の名前を使うか `{}` のように囲んでください", kw, kw);
+        }
+    }
+    if let Some(rest) = message.strip_prefix("Keyword '") {
+        let keyword = rest.split_once('\'').map(|(kw, _)| kw);
+        if let Some((kw, _, ja)) = UNSUPPORTED_KEYWORDS.iter().find(|(kw, _, _)| Some(*kw) == keyword) {
+            return format!("'{}' はここでは使えません: {}", kw, ja);
+        }
+    }
+    if let Some(token) = message.strip_prefix("Unexpected token after expression: ") {
+        return format!("式の後に予期しないトークンがあります: {}", token);
+    }
+    if let Some(token) = message.strip_prefix("Unexpected token: ") {
+        return format!("予期しないトークンです: {}", token);
+    }
+    if let Some(rest) = message.strip_prefix("Expected ") {
+        // "Expected X, got Y" / "Expected X"
+        let (expected, got) = match rest.rsplit_once(", got ") {
+            Some((expected, got)) => (expected, Some(got)),
+            None => (rest, None),
+        };
+        let expected = TERMS
+            .iter()
+            .find(|(en, _)| *en == expected)
+            .map(|(_, ja)| *ja)
+            .unwrap_or(expected);
+        return match got {
+            Some(got) => format!("{} が必要です (実際: {})", expected, got),
+            None => format!("{} が必要です", expected),
+        };
+    }
+    message.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_localize_parse_error() {
+        assert_eq!(localize_parse_error("Expected 'は'", Locale::Ja), "'は' が必要です");
+        assert_eq!(localize_parse_error("Expected verb", Locale::Ja), "動詞 が必要です");
+        assert_eq!(
+            localize_parse_error("Expected expression, got Newline", Locale::Ja),
+            "式 が必要です (実際: Newline)"
+        );
+        assert_eq!(localize_parse_error("Expected verb", Locale::En), "Expected verb");
+    }
+}
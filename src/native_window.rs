@@ -1,25 +1,43 @@
 use winit::event::{Event, WindowEvent, ElementState, MouseButton};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use winit::window::{Window, WindowBuilder};
 use crate::symbol_table::{SymbolTable, Value};
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::Receiver;
 use crate::interpreter::RuntimeMessage;
 use crate::graphics::state::State;
 
-pub fn run_native_window(rx: Receiver<RuntimeMessage>, symbol_table: Arc<Mutex<SymbolTable>>) {
-    let event_loop = EventLoop::new().unwrap();
+/// 初期化済みのウィンドウ (イベントループ開始前)
+pub struct NativeWindow {
+    event_loop: EventLoop<()>,
+    window: Arc<Window>,
+    state: State,
+}
+
+/// ウィンドウと wgpu を初期化する
+/// ディスプレイや GPU のない環境ではエラーを返す (呼び出し側でコンソールモードに切り替える)
+pub fn init_native_window(vsync: bool) -> Result<NativeWindow, String> {
+    let event_loop = EventLoop::new().map_err(|e| format!("Failed to create event loop: {}", e))?;
     let window = WindowBuilder::new()
         .with_title("AGN Native Window (wgpu)")
         .build(&event_loop)
-        .unwrap();
+        .map_err(|e| format!("Failed to create window: {}", e))?;
 
     let window = Arc::new(window);
     // Initialize wgpu State
-    let mut state = pollster::block_on(State::new(window.clone()));
+    let state = pollster::block_on(State::new(window.clone(), vsync))?;
+
+    Ok(NativeWindow { event_loop, window, state })
+}
+
+pub fn run_native_window(native: NativeWindow, rx: Receiver<RuntimeMessage>, symbol_table: Arc<Mutex<SymbolTable>>) {
+    let NativeWindow { event_loop, window, mut state } = native;
 
     println!("[Native] Window started with wgpu backend.");
 
+    // 表示する で指定されたルートコンポーネント (未指定なら自動で選ぶ)
+    let mut shown_root: Option<String> = None;
+
     event_loop.run(move |event, elwt| {
         elwt.set_control_flow(ControlFlow::Poll);
 
@@ -33,35 +51,12 @@ pub fn run_native_window(rx: Receiver<RuntimeMessage>, symbol_table: Arc<Mutex<S
                     WindowEvent::Resized(physical_size) => {
                         state.resize(*physical_size);
                     }
+                    WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                        // 新しい物理サイズは続く Resized で届く
+                        state.set_scale_factor(*scale_factor);
+                    }
                     WindowEvent::RedrawRequested => {
-                        let table = symbol_table.lock().unwrap();
-                        // Find a root component? Or check specific variable "Root" or "Card" or just iterate components?
-                        // For demo, we might look for "カード" (Card) or "Window" or generic iteration.
-                        // Ideally we pass the whole table or specific root.
-                        // Let's assume there is a variable named "Screen" or we just render the first component found?
-                        // "カード" is used in the demo.
-                        // Let's iterate and find the first Value::Component for now.
-                        let mut root: Option<Value> = None;
-                        for (k, v) in &table.symbols {
-                            if let Value::Component { .. } = v {
-                                // If it has children and is top level?
-                                // Let's simplify: Render variable "カード" if exists.
-                                if k == "カード" || k == "Card" {
-                                    root = Some(v.clone());
-                                    break;
-                                }
-                            }
-                        }
-                        // If no card, maybe render "Window"? 
-                        // If root is still None, take ANY component.
-                        if root.is_none() {
-                             for (_, v) in &table.symbols {
-                                 if let Value::Component { .. } = v {
-                                     root = Some(v.clone());
-                                     break;
-                                 }
-                             }
-                        }
+                        let root = find_root_component(&symbol_table.lock().unwrap(), shown_root.as_deref());
                         
                         match state.render(root.as_ref()) {
                             Ok(_) => {}
@@ -123,12 +118,32 @@ pub fn run_native_window(rx: Receiver<RuntimeMessage>, symbol_table: Arc<Mutex<S
                         RuntimeMessage::Animate(anim) => {
                             state.animation_controller.add_animation(anim);
                         },
-                        RuntimeMessage::RegisterEvent(target, event, anims) => {
+                        RuntimeMessage::RegisterEvent(target, event, anims, interval) => {
                             println!("[Native] Registered {} event for {}", event, target);
-                            state.register_event(target, event, anims);
+                            state.register_event(target, event, anims, interval);
                         },
                         RuntimeMessage::LoadImage(target, path) => {
                             state.load_image(target, path);
+                            state.mark_layout_dirty();
+                        }
+                        RuntimeMessage::SetText(target, text) => {
+                            // シンボルテーブルは更新済み。次の描画で反映される
+                            println!("[Native] Text of {} -> {}", target, text);
+                            state.mark_layout_dirty();
+                        }
+                        RuntimeMessage::Display(text, anchor) => {
+                            state.show_text(text, anchor);
+                        }
+                        RuntimeMessage::ShowComponent(name) => {
+                            println!("[Native] Showing {}", name);
+                            shown_root = Some(name);
+                            state.mark_layout_dirty();
+                        }
+                        RuntimeMessage::SaveScreenshot(path) => {
+                            let root = find_root_component(&symbol_table.lock().unwrap(), shown_root.as_deref());
+                            if let Err(e) = state.save_screenshot(root.as_ref(), &path) {
+                                eprintln!("[Native] {}", e);
+                            }
                         }
                     }
                 }
@@ -142,3 +157,34 @@ pub fn run_native_window(rx: Receiver<RuntimeMessage>, symbol_table: Arc<Mutex<S
         }
     }).unwrap();
 }
+
+/// 描画するルートコンポーネントを探す
+/// 表示する で指定されたコンポーネントがあればそれを優先する
+fn find_root_component(table: &SymbolTable, shown: Option<&str>) -> Option<Value> {
+    if let Some(value @ Value::Component { .. }) = shown.and_then(|name| table.lookup(name)) {
+        return Some(value.clone());
+    }
+    // Find a root component? Or check specific variable "Root" or "Card" or just iterate components?
+    // For demo, we might look for "カード" (Card) or "Window" or generic iteration.
+    // "カード" is used in the demo.
+    let mut root: Option<Value> = None;
+    for (k, v) in &table.symbols {
+        if let Value::Component { .. } = v {
+            // Render variable "カード" if exists.
+            if k == "カード" || k == "Card" {
+                root = Some(v.clone());
+                break;
+            }
+        }
+    }
+    // If root is still None, take ANY component.
+    if root.is_none() {
+         for v in table.symbols.values() {
+             if let Value::Component { .. } = v {
+                 root = Some(v.clone());
+                 break;
+             }
+         }
+    }
+    root
+}
@@ -2,9 +2,10 @@
 //! 曖昧な入力を正規化し、補正ログを出力する
 
 use std::fmt;
+use crate::lexer::{Lexer, Span, Token};
 
 /// 補正の種類
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum CorrectionType {
     /// 句読点の除去
     PunctuationRemoved(char),
@@ -14,6 +15,8 @@ pub enum CorrectionType {
     ParticleCorrection { from: String, to: String },
     /// 全角スペースの変換
     FullWidthSpace,
+    /// 半角カタカナ → 全角カタカナ
+    HalfWidthKatakana { from: String, to: String },
 }
 
 impl fmt::Display for CorrectionType {
@@ -23,6 +26,7 @@ impl fmt::Display for CorrectionType {
             CorrectionType::VerbAlias { from, to } => write!(f, "Verb alias: {} → {}", from, to),
             CorrectionType::ParticleCorrection { from, to } => write!(f, "Particle: {} → {}", from, to),
             CorrectionType::FullWidthSpace => write!(f, "Full-width space → half-width"),
+            CorrectionType::HalfWidthKatakana { from, to } => write!(f, "Half-width katakana: {} → {}", from, to),
         }
     }
 }
@@ -50,6 +54,235 @@ impl fmt::Display for LineCorrection {
     }
 }
 
+/// 1行内の列範囲 (1始まり、end は含まない)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColumnRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 補正箇所ごとの元のソース上の位置と正規化後の位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorrectionSpan {
+    pub line: usize,
+    pub correction: CorrectionType,
+    pub original: ColumnRange,
+    pub normalized: ColumnRange,
+}
+
+/// 正規化後の位置から元のソース上の位置への対応表
+#[derive(Debug, Clone, Default)]
+pub struct PositionMap {
+    /// 補正箇所 (出現ごと)
+    pub corrections: Vec<CorrectionSpan>,
+    /// 行ごとの 正規化後の列 -> 元の列 (0始まりの添字)
+    columns: Vec<Vec<usize>>,
+}
+
+impl PositionMap {
+    /// 正規化後のソース上の位置を元のソース上の位置へ戻す
+    pub fn to_original(&self, span: Span) -> Span {
+        let Some(columns) = span.line.checked_sub(1).and_then(|i| self.columns.get(i)) else {
+            return span;
+        };
+        let column = match columns.get(span.column.saturating_sub(1)) {
+            Some(&original) => original + 1,
+            // 行末より後ろは、元の行末からの差分で対応させる
+            None => columns.last().map_or(span.column, |&last| last + 1 + span.column - columns.len()),
+        };
+        Span { line: span.line, column }
+    }
+
+    /// 字句解析の位置情報をまとめて元のソース上の位置に戻す
+    pub fn spans_to_original(&self, spans: Vec<Span>) -> Vec<Span> {
+        spans.into_iter().map(|span| self.to_original(span)).collect()
+    }
+}
+
+/// 位置を追跡する1文字 (削除された文字も墓標として残す)
+#[derive(Debug, Clone)]
+struct TrackedChar {
+    ch: char,
+    /// 元の行での列 (0始まり)
+    origin: usize,
+    /// 最後にこの文字を生成・削除した補正 (出現ごとの番号)
+    tag: Option<usize>,
+    deleted: bool,
+}
+
+/// 補正の出現を記録しながら1行を書き換える
+struct TrackedLine {
+    chars: Vec<TrackedChar>,
+    /// 出現ごとの (補正, 元の列範囲)
+    occurrences: Vec<(CorrectionType, ColumnRange)>,
+}
+
+impl TrackedLine {
+    fn new(line: &str) -> Self {
+        let chars = line
+            .chars()
+            .enumerate()
+            .map(|(origin, ch)| TrackedChar { ch, origin, tag: None, deleted: false })
+            .collect();
+        Self { chars, occurrences: Vec::new() }
+    }
+
+    fn text(&self) -> String {
+        self.chars.iter().filter(|c| !c.deleted).map(|c| c.ch).collect()
+    }
+
+    /// from の出現をすべて to に置き換える (str::replace と同じく左から重ならずに)。置き換えたら true
+    fn replace_all(&mut self, from: &str, to: &str, correction: CorrectionType) -> bool {
+        let pattern: Vec<char> = from.chars().collect();
+        let mut replaced = false;
+        let mut i = 0;
+        while i < self.chars.len() {
+            let Some(matched) = self.match_at(i, &pattern) else {
+                i += 1;
+                continue;
+            };
+            i = self.replace_matched(&matched, to, correction.clone());
+            replaced = true;
+        }
+        replaced
+    }
+
+    /// 条件を満たす文字の連続をそれぞれ convert で置き換え、行われた補正を返す
+    fn replace_runs(
+        &mut self,
+        is_target: fn(char) -> bool,
+        convert: fn(&str) -> String,
+        correction: fn(String, String) -> CorrectionType,
+    ) -> Vec<CorrectionType> {
+        let mut applied = Vec::new();
+        let mut i = 0;
+        while i < self.chars.len() {
+            let run: Vec<usize> = (i..self.chars.len())
+                .filter(|&index| !self.chars[index].deleted)
+                .take_while(|&index| is_target(self.chars[index].ch))
+                .collect();
+            if self.chars[i].deleted || run.is_empty() {
+                i += 1;
+                continue;
+            }
+            let from: String = run.iter().map(|&index| self.chars[index].ch).collect();
+            let to = convert(&from);
+            let kind = correction(from, to.clone());
+            i = self.replace_matched(&run, &to, kind.clone());
+            applied.push(kind);
+        }
+        applied
+    }
+
+    /// 一致した文字を削除して直後に to を挿入し、出現を記録する。挿入後の次の添字を返す
+    fn replace_matched(&mut self, matched: &[usize], to: &str, correction: CorrectionType) -> usize {
+        let tag = self.occurrences.len();
+        let origin_start = self.chars[matched[0]].origin;
+        let origin_end = self.chars[*matched.last().unwrap()].origin + 1;
+        self.occurrences.push((correction, ColumnRange { start: origin_start + 1, end: origin_end + 1 }));
+
+        for &index in matched {
+            self.chars[index].deleted = true;
+            self.chars[index].tag = Some(tag);
+        }
+        let insert_at = matched.last().unwrap() + 1;
+        let inserted: Vec<TrackedChar> = to
+            .chars()
+            .map(|ch| TrackedChar { ch, origin: origin_start, tag: Some(tag), deleted: false })
+            .collect();
+        let count = inserted.len();
+        self.chars.splice(insert_at..insert_at, inserted);
+        insert_at + count
+    }
+
+    /// start から削除済みの文字を飛ばして pattern に一致すれば、一致した文字の添字を返す
+    fn match_at(&self, start: usize, pattern: &[char]) -> Option<Vec<usize>> {
+        if pattern.is_empty() || self.chars[start].deleted {
+            return None;
+        }
+        let mut matched = Vec::with_capacity(pattern.len());
+        let mut index = start;
+        for &expected in pattern {
+            while index < self.chars.len() && self.chars[index].deleted {
+                index += 1;
+            }
+            if index >= self.chars.len() || self.chars[index].ch != expected {
+                return None;
+            }
+            matched.push(index);
+            index += 1;
+        }
+        Some(matched)
+    }
+
+    /// 前後の空白を削除する (補正としては記録しない)
+    fn trim(&mut self) {
+        for c in self.chars.iter_mut().filter(|c| !c.deleted) {
+            if !c.ch.is_whitespace() {
+                break;
+            }
+            c.deleted = true;
+        }
+        for c in self.chars.iter_mut().rev().filter(|c| !c.deleted) {
+            if !c.ch.is_whitespace() {
+                break;
+            }
+            c.deleted = true;
+        }
+    }
+
+    /// 補正箇所の位置と 正規化後の列 -> 元の列 の対応を求める
+    fn finish(self, line_number: usize) -> (Vec<CorrectionSpan>, Vec<usize>) {
+        let columns: Vec<usize> = self.chars.iter().filter(|c| !c.deleted).map(|c| c.origin).collect();
+        let spans = self
+            .occurrences
+            .into_iter()
+            .enumerate()
+            .filter_map(|(tag, (correction, original))| {
+                let first = self.chars.iter().position(|c| c.tag == Some(tag))?;
+                let start = self.chars[..first].iter().filter(|c| !c.deleted).count() + 1;
+                let len = self.chars.iter().filter(|c| c.tag == Some(tag) && !c.deleted).count();
+                Some(CorrectionSpan {
+                    line: line_number,
+                    correction,
+                    original,
+                    normalized: ColumnRange { start, end: start + len },
+                })
+            })
+            .collect();
+        (spans, columns)
+    }
+}
+
+/// 閉じられていないブロックの補完提案
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSuggestion {
+    /// ブロックを開いた語 (もし / 繰り返す / ルール / { など)
+    pub opener: String,
+    pub opened_at: Span,
+    /// 補うべき閉じ語 (おわり / end / })
+    pub closer: &'static str,
+    /// この行の前に挿入する (ファイル末尾なら最終行 + 1)
+    pub insert_line: usize,
+}
+
+impl fmt::Display for BlockSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: '{}' のブロックが閉じられていません。{}行目の前に '{}' を追加してください",
+            self.opened_at, self.opener, self.insert_line, self.closer
+        )
+    }
+}
+
+/// 開いているブロック
+struct OpenBlock {
+    opener: String,
+    opened_at: Span,
+    closer: &'static str,
+}
+
 /// 動詞エイリアスマッピング
 /// 注意: 順序が重要。部分一致を避けるため、長い文字列を先に配置
 const VERB_ALIASES: &[(&str, &str)] = &[
@@ -76,6 +309,40 @@ const PARTICLE_CORRECTIONS: &[(&str, &str)] = &[
     ("が", "は"),  // 主語マーカーの統一（文脈による）
 ];
 
+/// 半角カタカナ (U+FF66〜U+FF9F) と対応する全角文字 (同じ順序)
+const HALF_WIDTH_KATAKANA: &str = "ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝﾞﾟ";
+const FULL_WIDTH_KATAKANA: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+
+fn is_half_width_katakana(c: char) -> bool {
+    ('\u{FF66}'..='\u{FF9F}').contains(&c)
+}
+
+/// 半角カタカナを全角に変換する (濁点・半濁点は直前の文字と合成する)
+fn to_full_width_katakana(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        let full = HALF_WIDTH_KATAKANA
+            .chars()
+            .position(|h| h == c)
+            .and_then(|i| FULL_WIDTH_KATAKANA.chars().nth(i))
+            .unwrap_or(c);
+        let combined = match (result.chars().last(), full) {
+            (Some('ウ'), '゛') => Some('ヴ'),
+            (Some(base @ ('カ'..='ト' | 'ハ'..='ホ')), '゛') => char::from_u32(base as u32 + 1),
+            (Some(base @ 'ハ'..='ホ'), '゜') => char::from_u32(base as u32 + 2),
+            _ => None,
+        };
+        match combined {
+            Some(voiced) => {
+                result.pop();
+                result.push(voiced);
+            }
+            None => result.push(full),
+        }
+    }
+    result
+}
+
 /// 除去する句読点
 const PUNCTUATION: &[char] = &['。', '、', '！', '？', '．', '，'];
 
@@ -95,48 +362,62 @@ impl Normalizer {
 
     /// コード全体を正規化
     pub fn normalize(&self, code: &str) -> (String, Vec<LineCorrection>) {
+        let (normalized, corrections, _) = self.normalize_with_map(code);
+        (normalized, corrections)
+    }
+
+    /// コード全体を正規化し、補正箇所を元のソースの位置へ戻すための対応表も返す
+    pub fn normalize_with_map(&self, code: &str) -> (String, Vec<LineCorrection>, PositionMap) {
         let mut normalized_lines = Vec::new();
         let mut all_corrections = Vec::new();
+        let mut map = PositionMap::default();
 
         for (i, line) in code.lines().enumerate() {
-            let (normalized, correction) = self.normalize_line(i + 1, line);
+            let (normalized, correction, tracked) = self.normalize_line(i + 1, line);
+            let (spans, columns) = tracked.finish(i + 1);
+            map.corrections.extend(spans);
+            map.columns.push(columns);
             normalized_lines.push(normalized);
             if !correction.corrections.is_empty() {
                 all_corrections.push(correction);
             }
         }
 
-        (normalized_lines.join("\n"), all_corrections)
+        (normalized_lines.join("\n"), all_corrections, map)
     }
 
     /// 1行を正規化
-    fn normalize_line(&self, line_number: usize, line: &str) -> (String, LineCorrection) {
+    fn normalize_line(&self, line_number: usize, line: &str) -> (String, LineCorrection, TrackedLine) {
         let original = line.to_string();
-        let mut result = line.to_string();
+        let mut tracked = TrackedLine::new(line);
         let mut corrections = Vec::new();
 
+        // 0. 半角カタカナ → 全角カタカナ (IMEの癖でキーワードと一致しなくなるため最初に行う)
+        corrections.extend(tracked.replace_runs(is_half_width_katakana, to_full_width_katakana, |from, to| {
+            CorrectionType::HalfWidthKatakana { from, to }
+        }));
+
         // 1. 全角スペース → 半角スペース
-        if result.contains('　') {
-            result = result.replace('　', " ");
+        if tracked.replace_all("　", " ", CorrectionType::FullWidthSpace) {
             corrections.push(CorrectionType::FullWidthSpace);
         }
 
         // 2. 句読点の除去
         for &punct in PUNCTUATION {
-            if result.contains(punct) {
-                result = result.replace(punct, "");
-                corrections.push(CorrectionType::PunctuationRemoved(punct));
+            let correction = CorrectionType::PunctuationRemoved(punct);
+            if tracked.replace_all(&punct.to_string(), "", correction.clone()) {
+                corrections.push(correction);
             }
         }
 
         // 3. 動詞エイリアスの解決
         for (from, to) in VERB_ALIASES {
-            if result.contains(from) {
-                result = result.replace(from, to);
-                corrections.push(CorrectionType::VerbAlias {
-                    from: from.to_string(),
-                    to: to.to_string(),
-                });
+            let correction = CorrectionType::VerbAlias {
+                from: from.to_string(),
+                to: to.to_string(),
+            };
+            if tracked.replace_all(from, to, correction.clone()) {
+                corrections.push(correction);
             }
         }
 
@@ -145,19 +426,20 @@ impl Normalizer {
         for (from, to) in PARTICLE_CORRECTIONS {
             let pattern = format!(" {} ", from);
             let replacement = format!(" {} ", to);
-            if result.contains(&pattern) {
-                // 「だ」で終わる代入文のみ補正
-                if result.trim().ends_with("だ") {
-                    result = result.replace(&pattern, &replacement);
-                    corrections.push(CorrectionType::ParticleCorrection {
-                        from: from.to_string(),
-                        to: to.to_string(),
-                    });
-                }
+            // 「だ」で終わる代入文のみ補正
+            let text = tracked.text();
+            if text.contains(&pattern) && text.trim().ends_with("だ") {
+                let correction = CorrectionType::ParticleCorrection {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                };
+                tracked.replace_all(&pattern, &replacement, correction.clone());
+                corrections.push(correction);
             }
         }
 
-        let normalized = result.trim().to_string();
+        tracked.trim();
+        let normalized = tracked.text();
         
         (
             normalized.clone(),
@@ -167,9 +449,87 @@ impl Normalizer {
                 normalized,
                 corrections,
             },
+            tracked,
         )
     }
 
+    /// ブロックの開始 (もし / 繰り返す / ルール / { など) と閉じ語の数を数え、
+    /// 閉じられていないブロックに閉じ語の挿入位置を提案する
+    /// 挿入位置は、開始行と同じかそれより浅い字下げで始まる最初の行 (なければ末尾)
+    pub fn suggest_block_closers(&self, code: &str) -> Vec<BlockSuggestion> {
+        let lines: Vec<&str> = code.lines().collect();
+        let (tokens, spans) = Lexer::new(code).tokenize_with_spans();
+        let mut stack: Vec<OpenBlock> = Vec::new();
+
+        for (token, span) in tokens.iter().zip(spans.iter().copied()) {
+            let word = || {
+                let line = lines.get(span.line - 1).copied().unwrap_or("");
+                line.chars().skip(span.column - 1).take_while(|c| !c.is_whitespace() && *c != '(').collect::<String>()
+            };
+            // on <対象> click の click は同じ見出しの一部なので数えない
+            let after_on = stack.last().is_some_and(|top| top.opener == "on" && top.opened_at.line == span.line);
+            match token {
+                Token::KeywordClick | Token::KeywordDrag | Token::KeywordWhen if after_on => {}
+                // 日本語の の間 は後ろの 繰り返す で数える
+                Token::KeywordWhile if word() != "while" => {}
+                Token::KeywordIf
+                | Token::KeywordRepeat
+                | Token::KeywordWhile
+                | Token::KeywordRule
+                | Token::KeywordAction
+                | Token::KeywordOn
+                | Token::KeywordInside
+                | Token::KeywordClick
+                | Token::KeywordDrag
+                | Token::KeywordWhen
+                | Token::KeywordAfter => {
+                    let opener = word();
+                    let closer = if opener.starts_with(|c: char| c.is_ascii_alphabetic()) { "end" } else { "おわり" };
+                    stack.push(OpenBlock { opener, opened_at: span, closer });
+                }
+                Token::Verb(v) if v == "繰り返す" => {
+                    stack.push(OpenBlock { opener: v.clone(), opened_at: span, closer: "おわり" });
+                }
+                Token::LBrace => {
+                    // 同じ行の ルール X { / on Event(...) { は波括弧で閉じる
+                    if let Some(top) = stack.last_mut() {
+                        if top.opened_at.line == span.line && top.closer != "}" {
+                            top.closer = "}";
+                            continue;
+                        }
+                    }
+                    stack.push(OpenBlock { opener: "{".to_string(), opened_at: span, closer: "}" });
+                }
+                Token::KeywordEnd => {
+                    if let Some(index) = stack.iter().rposition(|b| b.closer != "}") {
+                        stack.remove(index);
+                    }
+                }
+                Token::RBrace => {
+                    if let Some(index) = stack.iter().rposition(|b| b.closer == "}") {
+                        stack.remove(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let indent = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+        stack
+            .into_iter()
+            .map(|block| {
+                let opener_indent = indent(lines[block.opened_at.line - 1]);
+                let insert_line = lines
+                    .iter()
+                    .enumerate()
+                    .skip(block.opened_at.line)
+                    .find(|(_, line)| !line.trim().is_empty() && indent(line) <= opener_indent)
+                    .map_or(lines.len() + 1, |(i, _)| i + 1);
+                BlockSuggestion { opener: block.opener, opened_at: block.opened_at, closer: block.closer, insert_line }
+            })
+            .collect()
+    }
+
     /// 補正ログを整形して出力
     pub fn format_corrections(&self, corrections: &[LineCorrection]) -> String {
         if corrections.is_empty() {
@@ -235,4 +595,90 @@ mod tests {
         let (normalized, _) = normalizer.normalize("X が 10 だ");
         assert_eq!(normalized, "X は 10 だ");
     }
+
+    #[test]
+    fn test_correction_spans_map_to_original() {
+        let normalizer = Normalizer::new();
+        let (normalized, _, map) = normalizer.normalize_with_map("  X を たす。\nY を ひょうじする");
+        assert_eq!(normalized, "X を 足す\nY を 表示する");
+
+        // 「たす」(元の7-8列) → 「足す」(正規化後の5-6列)
+        let alias = &map.corrections[1];
+        assert_eq!(alias.correction, CorrectionType::VerbAlias { from: "たす".to_string(), to: "足す".to_string() });
+        assert_eq!(alias.original, ColumnRange { start: 7, end: 9 });
+        assert_eq!(alias.normalized, ColumnRange { start: 5, end: 7 });
+
+        // 削除された句読点は正規化後の幅0の範囲になる
+        let punct = &map.corrections[0];
+        assert_eq!(punct.correction, CorrectionType::PunctuationRemoved('。'));
+        assert_eq!(punct.original, ColumnRange { start: 9, end: 10 });
+        assert_eq!(punct.normalized, ColumnRange { start: 7, end: 7 });
+
+        // 2行目: 「ひょうじする」(6文字) → 「表示する」(4文字)
+        let second = &map.corrections[2];
+        assert_eq!(second.line, 2);
+        assert_eq!(second.original, ColumnRange { start: 5, end: 11 });
+        assert_eq!(second.normalized, ColumnRange { start: 5, end: 9 });
+
+        // 正規化後の位置から元の位置へ (先頭の空白2文字ぶんずれる)
+        assert_eq!(map.to_original(Span { line: 1, column: 1 }), Span { line: 1, column: 3 });
+        assert_eq!(map.to_original(Span { line: 2, column: 1 }), Span { line: 2, column: 1 });
+    }
+
+    #[test]
+    fn test_half_width_katakana() {
+        let normalizer = Normalizer::new();
+        let (normalized, corrections) = normalizer.normalize("ﾃｽﾄ は \"ｶﾞﾊﾟｳﾞ\" だ");
+        assert_eq!(normalized, "テスト は \"ガパヴ\" だ");
+        assert_eq!(corrections.len(), 1);
+        assert_eq!(corrections[0].corrections[1], CorrectionType::HalfWidthKatakana {
+            from: "ｶﾞﾊﾟｳﾞ".to_string(),
+            to: "ガパヴ".to_string(),
+        });
+
+        // 正規化後は全角の識別子として解析できる
+        let tokens = crate::lexer::Lexer::new(&normalized).tokenize();
+        let program = crate::parser::Parser::new(tokens).parse().unwrap();
+        assert!(matches!(
+            &program.statements[0],
+            crate::parser::Statement::Assignment { target: crate::parser::Expr::Variable(name), .. } if name == "テスト"
+        ));
+    }
+
+    #[test]
+    fn test_missing_end_suggestion() {
+        let normalizer = Normalizer::new();
+        let code = "もし X と等しい 1 ならば\n    X を 表示する\nY は 2 だ\n3 回 繰り返す\n    Y を 表示する\nおわり";
+        let suggestions = normalizer.suggest_block_closers(code);
+
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].opener, "もし");
+        assert_eq!(suggestions[0].opened_at, Span { line: 1, column: 1 });
+        assert_eq!(suggestions[0].closer, "おわり");
+        assert_eq!(suggestions[0].insert_line, 3);
+        assert!(suggestions[0].to_string().contains("3行目の前に 'おわり'"));
+
+        // 英語の if と波括弧のブロック
+        let suggestions = normalizer.suggest_block_closers("rule Boost {\n increase priority by 10\n\nif X equals 1 then\n X を 表示する");
+        let closers: Vec<&str> = suggestions.iter().map(|s| s.closer).collect();
+        assert_eq!(closers, vec!["}", "end"]);
+
+        // 閉じられていれば提案なし
+        assert!(normalizer.suggest_block_closers("もし X と等しい 1 ならば\n X を 表示する\nおわり").is_empty());
+    }
+
+    #[test]
+    fn test_no_suggestion_for_closed_blocks() {
+        let normalizer = Normalizer::new();
+        let valid = [
+            "on Button click\n  X を 表示する\nend",
+            "on Button click\n  if X equals 1 then\n    repeat 3 times\n      X を 表示する\n    end\n  end\nend",
+            "Button を 押したとき\n  もし X と等しい 1 ならば\n    X を 表示する\n  おわり\nおわり",
+            "while X < 3\n  X に 1 を 足す\nend\nX < 3 の間 繰り返す\n  X に 1 を 足す\nおわり",
+        ];
+        for code in valid {
+            assert!(crate::parser::Parser::new(Lexer::new(code)).parse().is_ok(), "{}", code);
+            assert!(normalizer.suggest_block_closers(code).is_empty(), "{}", code);
+        }
+    }
 }
@@ -3,11 +3,13 @@
 //!
 //! Phase 13: 「ええよ」SNSのための空間通信基盤
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use ed25519_dalek::{Signer, Verifier, VerifyingKey, Signature};
 use rand::Rng;
+use crate::utils::{Clock, SystemClock};
+use crate::parser::{Condition, Expr, SpatialFilter};
 
 /// ビーコンタイプ（ユーザーの状態を表す）
 #[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
@@ -443,8 +445,12 @@ pub enum TokuReason {
     Thanked,
     /// 推薦された
     Recommended,
+    /// 近接証明付きの親切 (confidence: 0.0 ~ 1.0)
+    KindnessVerified { confidence: f32 },
     /// ペナルティ
     Penalty,
+    /// 徳の送付 (送り手は減算、受け手は加算)
+    Transfer,
     /// 初期値
     Initial,
 }
@@ -465,7 +471,7 @@ pub struct TokuEvent {
 /// ユーザー間の関係性（絆 / Bond）
 /// 
 /// 「ええよ」における継続的な関係を表現
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Relationship {
     /// 絆レベル (0 = 初対面, 1+ = 絆あり)
     pub level: u32,
@@ -513,15 +519,20 @@ impl Relationship {
 
     /// 絆を深める (助け合い成功時)
     pub fn deepen(&mut self, amount: u32) {
+        self.deepen_with(amount, &BondConfig::default());
+    }
+
+    /// 成長設定に従って絆を深める
+    pub fn deepen_with(&mut self, amount: u32, config: &BondConfig) {
         self.help_count += 1;
-        self.strength = (self.strength + amount).min(1000);
+        self.strength = (self.strength + amount).min(config.max_strength);
         self.last_interaction = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
             .as_secs();
         
-        // レベルアップ判定 (strength 100 ごとにレベル+1)
-        let new_level = (self.strength / 100).max(1);
+        // レベルアップ判定 (strength_per_level ごとにレベル+1)
+        let new_level = (self.strength / config.strength_per_level.max(1)).max(1);
         if new_level > self.level {
             self.level = new_level;
         }
@@ -531,6 +542,73 @@ impl Relationship {
     pub fn has_bond(&self) -> bool {
         self.level > 0
     }
+
+    /// JSON表現 (eeyo_get_bond と同じ形式: level, strength, help_count ...)
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+impl std::fmt::Display for Relationship {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "絆(レベル{}, 強さ{})", self.level, self.strength)
+    }
+}
+
+/// 絆の成長設定
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BondConfig {
+    /// 絆の強さの上限
+    pub max_strength: u32,
+    /// レベルが1上がるのに必要な強さ
+    pub strength_per_level: u32,
+}
+
+impl Default for BondConfig {
+    fn default() -> Self {
+        Self {
+            max_strength: 1000,
+            strength_per_level: 100,
+        }
+    }
+}
+
+/// 徳スコアから称号 (ランク) を決める段位表
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankLadder {
+    /// (必要な徳, 称号) をしきい値の昇順で保持
+    tiers: Vec<(u32, String)>,
+}
+
+impl RankLadder {
+    pub fn new(tiers: Vec<(u32, String)>) -> Self {
+        let mut tiers = tiers;
+        tiers.sort_by_key(|(threshold, _)| *threshold);
+        Self { tiers }
+    }
+
+    /// 到達した中で最も高いしきい値の称号 (どれにも届かなければ最下段)
+    pub fn rank_for(&self, score: u32) -> &str {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|(threshold, _)| score >= *threshold)
+            .or(self.tiers.first())
+            .map(|(_, name)| name.as_str())
+            .unwrap_or("")
+    }
+}
+
+impl Default for RankLadder {
+    fn default() -> Self {
+        // 一般 / 徳人 の境界は従来の `score > 1000` と同じ
+        Self::new(vec![
+            (0, "一般".to_string()),
+            (300, "協力者".to_string()),
+            (1001, "徳人".to_string()),
+            (5000, "聖人".to_string()),
+        ])
+    }
 }
 
 /// 徳スコアマネージャ
@@ -541,6 +619,8 @@ pub struct TokuManager {
     relationships: Arc<Mutex<HashMap<(String, String), Relationship>>>,
     /// イベント履歴
     events: Arc<Mutex<Vec<TokuEvent>>>,
+    /// 絆の成長設定
+    bond_config: Arc<Mutex<BondConfig>>,
 }
 
 impl TokuManager {
@@ -554,9 +634,19 @@ impl TokuManager {
             scores: Arc::new(Mutex::new(HashMap::new())),
             relationships: Arc::new(Mutex::new(HashMap::new())),
             events: Arc::new(Mutex::new(Vec::new())),
+            bond_config: Arc::new(Mutex::new(BondConfig::default())),
         }
     }
 
+    /// 絆の成長設定を差し替える (レベルアップの速さ・強さの上限)
+    pub fn set_bond_config(&self, config: BondConfig) {
+        *self.bond_config.lock().unwrap() = config;
+    }
+
+    pub fn bond_config(&self) -> BondConfig {
+        *self.bond_config.lock().unwrap()
+    }
+
     /// 徳スコアを取得（未登録なら初期値）
     pub fn get_score(&self, user_id: &str) -> u32 {
         let scores = self.scores.lock().unwrap();
@@ -607,6 +697,95 @@ impl TokuManager {
         log::info!("[Toku] {} の徳スコア: {} → {} (ペナルティ)", user_id, current, new_score);
     }
 
+    /// 徳スコアを厳格に減算する。残高を超える場合はスコアを変えずにエラー
+    /// 成功時は減算後のスコアを返す
+    pub fn try_subtract_toku(&self, user_id: &str, amount: u32, reason: TokuReason) -> Result<u32, String> {
+        let (current, new_score) = Self::debit_strict(&mut self.scores.lock().unwrap(), user_id, amount)?;
+        self.events.lock().unwrap().push(TokuEvent {
+            user_id: user_id.to_string(),
+            delta: -(amount as i32),
+            reason,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        });
+
+        log::info!("[Toku] {} の徳スコア: {} → {}", user_id, current, new_score);
+        Ok(new_score)
+    }
+
+    /// 残高の範囲内でのみ減算する (減算前, 減算後)
+    fn debit_strict(scores: &mut HashMap<String, u32>, user_id: &str, amount: u32) -> Result<(u32, u32), String> {
+        let current = *scores.get(user_id).unwrap_or(&Self::INITIAL_SCORE);
+        let new_score = current
+            .checked_sub(amount)
+            .ok_or_else(|| format!("徳が足りません: {} の徳 {} < {}", user_id, current, amount))?;
+        scores.insert(user_id.to_string(), new_score);
+        Ok((current, new_score))
+    }
+
+    /// 徳を送る。送り手と受け手のスコアを同じロックの中で更新し、絆を深める
+    /// 送り手の徳が足りない・受け手が上限を超える場合は何も変更せずエラー
+    /// この送付で初めて絆ができた (初対面) ときは Ok(true)
+    pub fn transfer_toku(&self, from: &str, to: &str, amount: u32) -> Result<bool, String> {
+        if from == to {
+            return Err(format!("自分自身には徳を送れません: {}", from));
+        }
+        {
+            let mut scores = self.scores.lock().unwrap();
+            // 上限で切り捨てると送った分の徳が消えるため、受け取れない送付は拒否する
+            let receiver = *scores.get(to).unwrap_or(&Self::INITIAL_SCORE);
+            let credited = receiver
+                .checked_add(amount)
+                .filter(|score| *score <= Self::MAX_SCORE)
+                .ok_or_else(|| format!("徳の上限を超えます: {} の徳 {} + {} > {}", to, receiver, amount, Self::MAX_SCORE))?;
+            Self::debit_strict(&mut scores, from, amount)?;
+            scores.insert(to.to_string(), credited);
+
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let mut events = self.events.lock().unwrap();
+            events.push(TokuEvent { user_id: from.to_string(), delta: -(amount as i32), reason: TokuReason::Transfer, timestamp });
+            events.push(TokuEvent { user_id: to.to_string(), delta: amount as i32, reason: TokuReason::Transfer, timestamp });
+        }
+        let first_met = self.deepen_bond(from, to, 1);
+
+        log::info!("[Toku] {} -> {}: {} 徳を送付", from, to, amount);
+        Ok(first_met)
+    }
+
+    /// ユーザーの徳イベント履歴 (古い順)
+    pub fn history(&self, user_id: &str) -> Vec<TokuEvent> {
+        let events = self.events.lock().unwrap();
+        events.iter().filter(|e| e.user_id == user_id).cloned().collect()
+    }
+
+    /// これまでに得た徳の累計 (初期値 + 履歴中の加算の合計)。徳を使っても減らない
+    pub fn total_earned(&self, user_id: &str) -> u32 {
+        let earned: u32 = self
+            .history(user_id)
+            .iter()
+            .filter(|e| e.delta > 0)
+            .map(|e| e.delta as u32)
+            .sum();
+        Self::INITIAL_SCORE.saturating_add(earned)
+    }
+
+    /// 徳スコア上位 n 人（スコア降順、同点はID順）
+    pub fn top_scores(&self, n: usize) -> Vec<(String, u32)> {
+        let scores = self.scores.lock().unwrap();
+        let mut ranking: Vec<(String, u32)> = scores
+            .iter()
+            .map(|(id, score)| (id.clone(), *score))
+            .collect();
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranking.truncate(n);
+        ranking
+    }
+
     /// ユーザーIDをハッシュ化（8バイト）
     pub fn hash_user_id(user_id: &str) -> [u8; 8] {
         // 簡易ハッシュ（本番では SHA-256 などを使用）
@@ -627,6 +806,104 @@ impl TokuManager {
             .unwrap_or_default()
     }
 
+    /// ユーザーが起点 (from) の関係性一覧 (相手のID順)
+    pub fn bonds_for(&self, user_id: &str) -> Vec<(String, Relationship)> {
+        let rels = self.relationships.lock().unwrap();
+        let mut bonds: Vec<(String, Relationship)> = rels
+            .iter()
+            .filter(|((from, _), _)| from == user_id)
+            .map(|((_, to), rel)| (to.clone(), rel.clone()))
+            .collect();
+        bonds.sort_by(|a, b| a.0.cmp(&b.0));
+        bonds
+    }
+
+    /// 絆を深める (なければ新しい絆を作成)
+    /// 初めて絆ができた (レベル 0 → 1) ときは true (初対面)
+    pub fn deepen_bond(&self, from: &str, to: &str, amount: u32) -> bool {
+        let mut rels = self.relationships.lock().unwrap();
+        let key = (from.to_string(), to.to_string());
+        let was_bonded = rels.get(&key).is_some_and(Relationship::has_bond);
+        
+        let rel = rels.entry(key).or_insert_with(Relationship::new_bond);
+        rel.deepen_with(amount, &self.bond_config());
+        
+        log::info!("[Bond] {} ⇔ {}: Level {} (Strength {}, Helped {} times)", 
+            from, to, rel.level, rel.strength, rel.help_count);
+
+        let first_met = !was_bonded && rel.has_bond();
+        if first_met && rel.first_met == 0 {
+            // update_relationship で先に作られた関係は出会った日時を持たない
+            rel.first_met = rel.last_interaction;
+        }
+        first_met
+    }
+
+    /// 絆のある相手 (from 側から見た to) の集合
+    fn bonded_partners(rels: &HashMap<(String, String), Relationship>, user_id: &str) -> std::collections::BTreeSet<String> {
+        rels.iter()
+            .filter(|((from, _), rel)| from == user_id && rel.has_bond())
+            .map(|((_, to), _)| to.clone())
+            .collect()
+    }
+
+    /// a と b の共通の絆の相手 (ID順)
+    pub fn mutual_bonds(&self, a: &str, b: &str) -> Vec<String> {
+        let rels = self.relationships.lock().unwrap();
+        let partners_a = Self::bonded_partners(&rels, a);
+        let partners_b = Self::bonded_partners(&rels, b);
+        partners_a.intersection(&partners_b).cloned().collect()
+    }
+
+    /// 友達の友達のうち、まだ絆のない相手を提案する
+    /// 共通の友達が多い順 (同数はID順) に最大 max 人
+    pub fn suggest_bonds(&self, user_id: &str, max: usize) -> Vec<String> {
+        let rels = self.relationships.lock().unwrap();
+        let friends = Self::bonded_partners(&rels, user_id);
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for friend in &friends {
+            for candidate in Self::bonded_partners(&rels, friend) {
+                if candidate != user_id && !friends.contains(&candidate) {
+                    *counts.entry(candidate).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut ranked: Vec<(String, usize)> = counts.into_iter().collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranked.into_iter().take(max).map(|(id, _)| id).collect()
+    }
+
+    /// 絆グラフを GraphViz (DOT) 形式で出力する (デバッグ・可視化用)
+    /// ノードはユーザー、辺は関係性で強さを重みとする
+    pub fn to_dot(&self) -> String {
+        let rels = self.relationships.lock().unwrap();
+        let quote = |id: &str| format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""));
+
+        let mut users = std::collections::BTreeSet::new();
+        let mut edges: Vec<(&String, &String, &Relationship)> = Vec::new();
+        for ((from, to), rel) in rels.iter() {
+            users.insert(from.as_str());
+            users.insert(to.as_str());
+            edges.push((from, to, rel));
+        }
+        edges.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+        let mut dot = String::from("digraph bonds {\n");
+        for user in users {
+            dot.push_str(&format!("    {};\n", quote(user)));
+        }
+        for (from, to, rel) in edges {
+            dot.push_str(&format!(
+                "    {} -> {} [label=\"{}\", weight={}];\n",
+                quote(from), quote(to), rel.strength, rel.strength
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
     /// 関係性を更新（強さを加算）
     pub fn update_relationship(&self, from: &str, to: &str, delta: i32) {
         let mut rels = self.relationships.lock().unwrap();
@@ -634,8 +911,9 @@ impl TokuManager {
         
         let rel = rels.entry(key).or_default();
         
-        // 強さを更新 (0 ~ 1000)
-        let new_strength = (rel.strength as i32 + delta).max(0).min(1000) as u32;
+        // 強さを更新 (0 ~ 上限)
+        let max_strength = self.bond_config().max_strength as i32;
+        let new_strength = (rel.strength as i32 + delta).max(0).min(max_strength) as u32;
         rel.strength = new_strength;
         
         // 最終接触時刻を更新
@@ -668,16 +946,41 @@ pub fn agn_subtract_toku(user_id: &str, amount: u32) {
     TOKU_MANAGER.subtract_toku(user_id, amount, TokuReason::Penalty);
 }
 
+/// AGNから呼び出される徳スコア減算関数 (残高不足ならエラー)
+pub fn agn_try_subtract_toku(user_id: &str, amount: u32) -> Result<u32, String> {
+    TOKU_MANAGER.try_subtract_toku(user_id, amount, TokuReason::Penalty)
+}
+
+/// AGNから呼び出される徳送付関数
+pub fn agn_transfer_toku(from: &str, to: &str, amount: u32) -> Result<bool, String> {
+    TOKU_MANAGER.transfer_toku(from, to, amount)
+}
+
 /// AGNから呼び出される徳スコア取得関数
 pub fn agn_get_toku(user_id: &str) -> u32 {
     TOKU_MANAGER.get_score(user_id)
 }
 
+/// AGNから呼び出される徳ランキング取得関数
+pub fn agn_top_toku(n: usize) -> Vec<(String, u32)> {
+    TOKU_MANAGER.top_scores(n)
+}
+
 /// AGNから呼び出される関係性取得関数
 pub fn agn_get_bond(from: &str, to: &str) -> Relationship {
     TOKU_MANAGER.get_relationship(from, to)
 }
 
+/// AGNから呼び出される累計獲得徳の取得関数 (履歴ベースのランク用)
+pub fn agn_toku_earned(user_id: &str) -> u32 {
+    TOKU_MANAGER.total_earned(user_id)
+}
+
+/// AGNから呼び出される絆一覧取得関数 (友達画面用)
+pub fn agn_bonds_for(user_id: &str) -> Vec<(String, Relationship)> {
+    TOKU_MANAGER.bonds_for(user_id)
+}
+
 /// AGNから呼び出される絆レベル取得関数
 pub fn agn_get_bond_level(from: &str, to: &str) -> u32 {
     TOKU_MANAGER.get_relationship(from, to).level
@@ -688,16 +991,19 @@ pub fn agn_has_bond(from: &str, to: &str) -> bool {
     TOKU_MANAGER.get_relationship(from, to).has_bond()
 }
 
-/// AGNから呼び出される絆深化関数 (助け合い成功時)
-pub fn agn_deepen_bond(from: &str, to: &str, amount: u32) {
-    let mut rels = TOKU_MANAGER.relationships.lock().unwrap();
-    let key = (from.to_string(), to.to_string());
-    
-    let rel = rels.entry(key).or_insert_with(Relationship::new_bond);
-    rel.deepen(amount);
-    
-    log::info!("[Bond] {} ⇔ {}: Level {} (Strength {}, Helped {} times)", 
-        from, to, rel.level, rel.strength, rel.help_count);
+/// AGNから呼び出される絆深化関数 (助け合い成功時)。初対面なら true
+pub fn agn_deepen_bond(from: &str, to: &str, amount: u32) -> bool {
+    TOKU_MANAGER.deepen_bond(from, to, amount)
+}
+
+/// AGNから呼び出される絆グラフ出力関数 (DOT形式)
+pub fn agn_bond_graph_dot() -> String {
+    TOKU_MANAGER.to_dot()
+}
+
+/// AGNから呼び出される友達の友達の提案関数
+pub fn agn_suggest_bonds(user_id: &str, max: usize) -> Vec<String> {
+    TOKU_MANAGER.suggest_bonds(user_id, max)
 }
 
 /// AGNから呼び出される関係性更新関数 (後方互換)
@@ -706,7 +1012,7 @@ pub fn agn_update_bond(from: &str, to: &str, amount: i32) {
 }
 
 /// 検出されたピア情報
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetectedPeer {
     /// ユーザーID（ハッシュ化）
     pub peer_id: String,
@@ -716,12 +1022,41 @@ pub struct DetectedPeer {
     pub estimated_distance: f64,
     /// 信号強度（RSSI）
     pub rssi: i16,
-    /// 最終検出時刻
-    pub last_seen: Instant,
+    /// 最終検出時刻 (UNIXエポックからのミリ秒。セッションをまたいでも有効)
+    pub last_seen_ms: f64,
     /// 徳スコア
     pub toku_score: Option<u32>,
     /// カスタムペイロード
     pub payload: HashMap<String, String>,
+    /// 直近のRSSI履歴 (古い順、最大 RSSI_HISTORY_LEN 件)
+    pub rssi_history: Vec<i16>,
+    /// RSSIの指数移動平均 (未計測なら None)
+    pub rssi_ema: Option<f64>,
+}
+
+impl DetectedPeer {
+    /// 保持するRSSI履歴の件数
+    pub const RSSI_HISTORY_LEN: usize = 10;
+    /// 指数移動平均の平滑化係数 (大きいほど新しい値に追従する)
+    pub const RSSI_SMOOTHING: f64 = 0.3;
+
+    /// RSSIの計測値を追加し、移動平均を更新する
+    pub fn push_rssi(&mut self, rssi: i16) {
+        self.rssi = rssi;
+        self.rssi_history.push(rssi);
+        if self.rssi_history.len() > Self::RSSI_HISTORY_LEN {
+            self.rssi_history.remove(0);
+        }
+        self.rssi_ema = Some(match self.rssi_ema {
+            Some(prev) => prev + Self::RSSI_SMOOTHING * (rssi as f64 - prev),
+            None => rssi as f64,
+        });
+    }
+
+    /// 平滑化したRSSI (計測値がなければ最新のRSSI)
+    pub fn smoothed_rssi(&self) -> f64 {
+        self.rssi_ema.unwrap_or(self.rssi as f64)
+    }
 }
 
 /// ビーコン設定
@@ -752,8 +1087,74 @@ pub enum P2PState {
     Stopped,
 }
 
+impl P2PState {
+    /// この状態から `to` へ遷移できるか (同じ状態への遷移は初期化済みなら冪等として許可)
+    pub fn can_transition_to(&self, to: &P2PState) -> bool {
+        use P2PState::*;
+        if self == to {
+            return *self != Uninitialized;
+        }
+        matches!(
+            (self, to),
+            (Uninitialized, Stopped)
+                | (Stopped, Scanning)
+                | (Stopped, Broadcasting)
+                | (Scanning, Stopped)
+                | (Scanning, ScanningAndBroadcasting)
+                | (Broadcasting, Stopped)
+                | (Broadcasting, ScanningAndBroadcasting)
+                | (ScanningAndBroadcasting, Scanning)
+                | (ScanningAndBroadcasting, Broadcasting)
+        )
+    }
+}
+
+/// RSSIから距離を推定するモデル (対数距離経路損失モデル)
+///
+/// 計算式: distance = 10 ^ ((TxPower - RSSI) / (10 * n))
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceModel {
+    /// 1mでのRSSI基準値 (dBm)
+    pub tx_power: i16,
+    /// 環境係数 n (自由空間 = 2.0、屋内は2-4)
+    pub path_loss_exponent: f64,
+}
+
+impl DistanceModel {
+    /// 見通しの良い屋外 (自由空間)
+    pub fn free_space() -> Self {
+        Self { tx_power: -59, path_loss_exponent: 2.0 }
+    }
+
+    /// 壁や人による減衰がある屋内
+    pub fn indoor() -> Self {
+        Self { tx_power: -59, path_loss_exponent: 3.0 }
+    }
+
+    /// RSSIから距離 (m) を推定 (RSSIが0なら不明として -1.0)
+    pub fn estimate(&self, rssi: i16) -> f64 {
+        self.estimate_smoothed(rssi as f64)
+    }
+
+    /// 平滑化したRSSI (小数) から距離 (m) を推定
+    pub fn estimate_smoothed(&self, rssi: f64) -> f64 {
+        if rssi == 0.0 {
+            return -1.0; // 不明
+        }
+        let ratio = (self.tx_power as f64 - rssi) / (10.0 * self.path_loss_exponent);
+        10.0_f64.powf(ratio)
+    }
+}
+
+impl Default for DistanceModel {
+    fn default() -> Self {
+        Self::free_space()
+    }
+}
+
 /// P2P通信マネージャ
 /// BLE/Wi-Fi Awareの抽象化レイヤー
+#[derive(Clone)]
 pub struct P2PManager {
     /// 現在の状態
     state: Arc<Mutex<P2PState>>,
@@ -765,9 +1166,20 @@ pub struct P2PManager {
     peer_cache_ttl: Duration,
     /// フィードイベントのキャッシュ
     feed_cache: Arc<Mutex<Vec<SocialTokuEvent>>>,
+    /// RSSI -> 距離の推定モデル
+    distance_model: Arc<Mutex<DistanceModel>>,
+    /// 発信の自動停止時刻 (ミリ秒、duration_sec 指定時のみ)
+    broadcast_deadline: Arc<Mutex<Option<f64>>>,
+    /// 経過時間の基準となる時計
+    clock: Arc<Mutex<Arc<dyn Clock>>>,
+    /// ブロック中のピア (ユーザーIDハッシュ)
+    blocked_peers: Arc<Mutex<HashSet<String>>>,
 }
 
 impl P2PManager {
+    /// 発信期限を Clock で確認し直す最大間隔 (ミリ秒)
+    const BROADCAST_EXPIRY_POLL_MS: f64 = 100.0;
+
     pub fn new() -> Self {
         Self {
             state: Arc::new(Mutex::new(P2PState::Uninitialized)),
@@ -775,9 +1187,31 @@ impl P2PManager {
             current_beacon: Arc::new(Mutex::new(None)),
             peer_cache_ttl: Duration::from_secs(30),
             feed_cache: Arc::new(Mutex::new(Vec::new())),
+            distance_model: Arc::new(Mutex::new(DistanceModel::default())),
+            broadcast_deadline: Arc::new(Mutex::new(None)),
+            clock: Arc::new(Mutex::new(Arc::new(SystemClock))),
+            blocked_peers: Arc::new(Mutex::new(HashSet::new())),
         }
     }
 
+    /// 時計を差し替える (テストでは MockClock)
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.clock.lock().unwrap() = clock;
+    }
+
+    fn now_ms(&self) -> f64 {
+        self.clock.lock().unwrap().now_ms()
+    }
+
+    /// 距離推定モデルを設定 (屋内/屋外のプリセットなど)
+    pub fn set_distance_model(&self, model: DistanceModel) {
+        *self.distance_model.lock().unwrap() = model;
+    }
+
+    pub fn distance_model(&self) -> DistanceModel {
+        *self.distance_model.lock().unwrap()
+    }
+
     /// BLE/Wi-Fi Awareの初期化
     /// 
     /// # プラットフォーム対応
@@ -786,8 +1220,11 @@ impl P2PManager {
     /// - Linux: BlueZ
     /// - WASM: WebRTC フォールバック
     pub async fn initialize(&self) -> Result<(), String> {
-        let mut state = self.state.lock().unwrap();
-        
+        if *self.state.lock().unwrap() != P2PState::Uninitialized {
+            log::debug!("[P2P] 初期化済み");
+            return Ok(());
+        }
+
         // TODO: btleplugの初期化
         // #[cfg(not(target_arch = "wasm32"))]
         // {
@@ -796,26 +1233,34 @@ impl P2PManager {
         //         .map_err(|e| format!("BLE初期化エラー: {}", e))?;
         // }
         
+        self.transition(|_| Ok(P2PState::Stopped))?;
         log::info!("[P2P] 初期化完了");
-        *state = P2PState::Stopped;
         Ok(())
     }
 
-    /// ビーコンスキャン開始
-    pub async fn start_scanning(&self) -> Result<(), String> {
+    /// 現在の状態から `to` へ遷移できるか
+    pub fn can_transition(&self, to: &P2PState) -> bool {
+        self.state.lock().unwrap().can_transition_to(to)
+    }
+
+    /// 現在の状態から遷移先を決めて適用する。許可されていない遷移はエラー
+    fn transition(&self, next: impl FnOnce(&P2PState) -> Result<P2PState, String>) -> Result<(), String> {
         let mut state = self.state.lock().unwrap();
-        
-        match *state {
-            P2PState::Uninitialized => {
-                return Err("P2Pマネージャが初期化されていません".to_string());
-            }
-            P2PState::Broadcasting => {
-                *state = P2PState::ScanningAndBroadcasting;
-            }
-            _ => {
-                *state = P2PState::Scanning;
-            }
+        let to = next(&state)?;
+        if !state.can_transition_to(&to) {
+            return Err(format!("不正な状態遷移: {:?} -> {:?}", *state, to));
         }
+        *state = to;
+        Ok(())
+    }
+
+    /// ビーコンスキャン開始
+    pub async fn start_scanning(&self) -> Result<(), String> {
+        self.transition(|state| match state {
+            P2PState::Uninitialized => Err("P2Pマネージャが初期化されていません".to_string()),
+            P2PState::Broadcasting | P2PState::ScanningAndBroadcasting => Ok(P2PState::ScanningAndBroadcasting),
+            P2PState::Stopped | P2PState::Scanning => Ok(P2PState::Scanning),
+        })?;
         
         log::info!("[P2P] スキャン開始");
         
@@ -827,17 +1272,13 @@ impl P2PManager {
 
     /// ビーコンスキャン停止
     pub fn stop_scanning(&self) -> Result<(), String> {
-        let mut state = self.state.lock().unwrap();
-        
-        match *state {
-            P2PState::Scanning => {
-                *state = P2PState::Stopped;
-            }
-            P2PState::ScanningAndBroadcasting => {
-                *state = P2PState::Broadcasting;
-            }
-            _ => {}
-        }
+        self.transition(|state| match state {
+            P2PState::Scanning => Ok(P2PState::Stopped),
+            P2PState::ScanningAndBroadcasting => Ok(P2PState::Broadcasting),
+            // スキャンしていなければ何もしない (冪等)
+            P2PState::Stopped | P2PState::Broadcasting => Ok(state.clone()),
+            P2PState::Uninitialized => Err("P2Pマネージャが初期化されていません".to_string()),
+        })?;
         
         log::info!("[P2P] スキャン停止");
         Ok(())
@@ -845,25 +1286,25 @@ impl P2PManager {
 
     /// ビーコン発信開始
     pub async fn start_broadcasting(&self, config: BeaconConfig) -> Result<(), String> {
-        let mut state = self.state.lock().unwrap();
-        
-        match *state {
-            P2PState::Uninitialized => {
-                return Err("P2Pマネージャが初期化されていません".to_string());
-            }
-            P2PState::Scanning => {
-                *state = P2PState::ScanningAndBroadcasting;
-            }
-            _ => {
-                *state = P2PState::Broadcasting;
-            }
-        }
+        // 発信中に再度呼ばれた場合は状態を保ったまま設定だけ差し替える
+        self.transition(|state| match state {
+            P2PState::Uninitialized => Err("P2Pマネージャが初期化されていません".to_string()),
+            P2PState::Scanning | P2PState::ScanningAndBroadcasting => Ok(P2PState::ScanningAndBroadcasting),
+            P2PState::Stopped | P2PState::Broadcasting => Ok(P2PState::Broadcasting),
+        })?;
         
         // ビーコン設定を保存
         {
             let mut beacon = self.current_beacon.lock().unwrap();
             *beacon = Some(config.clone());
         }
+
+        // 発信時間が指定されていれば期限を設定し、経過後に自動停止する
+        *self.broadcast_deadline.lock().unwrap() =
+            config.duration_sec.map(|secs| self.now_ms() + secs as f64 * 1000.0);
+        if config.duration_sec.is_some() {
+            self.schedule_broadcast_expiry();
+        }
         
         log::info!("[P2P] ビーコン発信開始: {:?}", config.beacon_type);
         
@@ -873,24 +1314,57 @@ impl P2PManager {
         Ok(())
     }
 
-    /// ビーコン発信停止
-    pub fn stop_broadcasting(&self) -> Result<(), String> {
-        let mut state = self.state.lock().unwrap();
-        
-        match *state {
-            P2PState::Broadcasting => {
-                *state = P2PState::Stopped;
-            }
-            P2PState::ScanningAndBroadcasting => {
-                *state = P2PState::Scanning;
+    /// 注入された Clock で発信期限を待ち、経過したら expire_broadcast を呼ぶタスクを起動する
+    fn schedule_broadcast_expiry(&self) {
+        let manager = self.clone();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move { manager.wait_broadcast_expiry().await });
+        }
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move { manager.wait_broadcast_expiry().await });
+    }
+
+    /// 期限までの残り時間を Clock から求めて待つ。期限が消えたら (停止済み) 抜ける
+    async fn wait_broadcast_expiry(&self) {
+        loop {
+            let Some(deadline) = *self.broadcast_deadline.lock().unwrap() else {
+                return;
+            };
+            let remaining = deadline - self.now_ms();
+            if remaining <= 0.0 {
+                self.expire_broadcast();
+                return;
             }
-            _ => {}
+            crate::utils::sleep(remaining.min(Self::BROADCAST_EXPIRY_POLL_MS) as u64).await;
         }
+    }
+
+    /// 発信期限を過ぎていれば発信を停止する。停止した場合 true
+    pub fn expire_broadcast(&self) -> bool {
+        let expired = matches!(*self.broadcast_deadline.lock().unwrap(), Some(deadline) if self.now_ms() >= deadline);
+        if expired {
+            log::info!("[P2P] 発信時間が経過したため停止");
+            let _ = self.stop_broadcasting();
+        }
+        expired
+    }
+
+    /// ビーコン発信停止
+    pub fn stop_broadcasting(&self) -> Result<(), String> {
+        self.transition(|state| match state {
+            P2PState::Broadcasting => Ok(P2PState::Stopped),
+            P2PState::ScanningAndBroadcasting => Ok(P2PState::Scanning),
+            // 発信していなければ何もしない (冪等)
+            P2PState::Stopped | P2PState::Scanning => Ok(state.clone()),
+            P2PState::Uninitialized => Err("P2Pマネージャが初期化されていません".to_string()),
+        })?;
         
         {
             let mut beacon = self.current_beacon.lock().unwrap();
             *beacon = None;
         }
+        *self.broadcast_deadline.lock().unwrap() = None;
         
         log::info!("[P2P] ビーコン発信停止");
         Ok(())
@@ -899,14 +1373,18 @@ impl P2PManager {
     /// 近くのピアを取得（距離でフィルタ）
     pub fn get_nearby_peers(&self, max_distance: f64) -> Vec<DetectedPeer> {
         let peers = self.detected_peers.lock().unwrap();
-        let now = Instant::now();
+        let now = self.now_ms();
+        let ttl_ms = self.peer_cache_ttl.as_secs_f64() * 1000.0;
+        let blocked = self.blocked_peers.lock().unwrap();
         
         peers.values()
             .filter(|p| {
                 // TTLチェック
-                now.duration_since(p.last_seen) < self.peer_cache_ttl &&
+                now - p.last_seen_ms < ttl_ms &&
                 // 距離フィルタ
-                p.estimated_distance <= max_distance
+                p.estimated_distance <= max_distance &&
+                // ブロック中のピアは除外
+                !blocked.contains(&p.peer_id)
             })
             .cloned()
             .collect()
@@ -920,19 +1398,110 @@ impl P2PManager {
             .collect()
     }
 
-    /// RSSIから距離を推定（簡易版）
-    /// 
-    /// 計算式: distance = 10 ^ ((TxPower - RSSI) / (10 * n))
-    /// - TxPower: -59 (1mでのRSSI基準値)
-    /// - n: 2.0 (環境係数、屋内は2-4)
+    /// RSSIから距離を推定（簡易版、自由空間モデル n = 2.0）
     pub fn estimate_distance_from_rssi(rssi: i16, tx_power: i16) -> f64 {
-        if rssi == 0 {
-            return -1.0; // 不明
+        DistanceModel { tx_power, ..DistanceModel::free_space() }.estimate(rssi)
+    }
+
+    /// 検出したピアを記録する (距離は平滑化したRSSIと設定中のモデルで推定)
+    pub fn observe_peer(&self, peer_id: &str, beacon_type: BeaconType, rssi: i16) {
+        self.upsert_peer(peer_id, beacon_type, rssi, None);
+    }
+
+    /// ピアを追加または更新する (徳スコアは指定された場合のみ上書き)
+    fn upsert_peer(&self, peer_id: &str, beacon_type: BeaconType, rssi: i16, toku_score: Option<u32>) {
+        let mut peers = self.detected_peers.lock().unwrap();
+        let peer = peers.entry(peer_id.to_string()).or_insert_with(|| DetectedPeer {
+            peer_id: peer_id.to_string(),
+            beacon_type,
+            estimated_distance: -1.0,
+            rssi,
+            last_seen_ms: self.now_ms(),
+            toku_score: None,
+            payload: HashMap::new(),
+            rssi_history: Vec::new(),
+            rssi_ema: None,
+        });
+        peer.push_rssi(rssi);
+        peer.beacon_type = beacon_type;
+        peer.estimated_distance = self.distance_model().estimate_smoothed(peer.smoothed_rssi());
+        peer.last_seen_ms = self.now_ms();
+        if toku_score.is_some() {
+            peer.toku_score = toku_score;
         }
-        
-        let n = 2.0; // 環境係数
-        let ratio = (tx_power as f64 - rssi as f64) / (10.0 * n);
-        10.0_f64.powf(ratio)
+    }
+
+    /// 受信したビーコンをピアキャッシュに反映する (BLE層からの取り込み口)
+    /// 距離・最終検出時刻・RSSI履歴・徳を更新する。ブロック中のピアのビーコンは破棄して false を返す
+    pub fn receive_beacon(&self, packet: &EeyoBeaconPacket, rssi: i16) -> bool {
+        let peer_id = user_id_hex(&packet.user_id);
+        if self.is_blocked(&peer_id) {
+            log::debug!("[P2P] ブロック中のピアからのビーコンを破棄: {}", peer_id);
+            return false;
+        }
+
+        self.upsert_peer(&peer_id, packet.beacon_type, rssi, Some(packet.toku_score as u32));
+        true
+    }
+
+    /// 署名付きビーコンを受信する (信頼できる取り込み口)
+    /// 署名とタイムスタンプを検証し、不正なパケットやブロック中のピアは false。ピアIDは公開鍵から導出する
+    pub fn receive_secure_beacon(&self, bytes: &[u8], rssi: i16) -> bool {
+        let Some(packet) = EeyoSecurePacket::from_bytes(bytes) else {
+            log::warn!("[Security] 検証に失敗したビーコンを破棄");
+            return false;
+        };
+        let peer_id = peer_id_from_pubkey(&packet.sender_public_key);
+        if self.is_blocked(&peer_id) {
+            log::debug!("[P2P] ブロック中のピアからのビーコンを破棄: {}", peer_id);
+            return false;
+        }
+
+        self.upsert_peer(&peer_id, packet.beacon_type, rssi, Some(packet.toku_score as u32));
+        true
+    }
+
+    /// 受信したバイト列をデコードして receive_beacon に渡す
+    pub fn receive_beacon_bytes(&self, bytes: &[u8], rssi: i16) -> Result<bool, String> {
+        let packet = EeyoBeaconPacket::from_bytes(bytes)?;
+        Ok(self.receive_beacon(&packet, rssi))
+    }
+
+    /// ピアをブロックする (検索結果から除外し、ビーコンを破棄する)
+    pub fn block(&self, peer_id: &str) {
+        self.blocked_peers.lock().unwrap().insert(peer_id.to_string());
+        log::info!("[P2P] ピアをブロック: {}", peer_id);
+    }
+
+    /// ピアのブロックを解除する
+    pub fn unblock(&self, peer_id: &str) {
+        self.blocked_peers.lock().unwrap().remove(peer_id);
+        log::info!("[P2P] ピアのブロックを解除: {}", peer_id);
+    }
+
+    pub fn is_blocked(&self, peer_id: &str) -> bool {
+        self.blocked_peers.lock().unwrap().contains(peer_id)
+    }
+
+    /// 近接証明 (Proof-of-Kindness) を評価し、信頼度に応じて actor に徳を付与する
+    /// 付与した徳の量を返す
+    pub fn record_kindness(&self, actor: &str, target: &str, rssi_log: &[i16]) -> u32 {
+        Self::record_kindness_to(&TOKU_MANAGER, actor, target, rssi_log)
+    }
+
+    fn record_kindness_to(toku: &TokuManager, actor: &str, target: &str, rssi_log: &[i16]) -> u32 {
+        let confidence = ProximityVerifier::verify_presence(rssi_log);
+        let amount = kindness_award(confidence);
+        log::info!("[Kindness] {} -> {}: confidence {:.2}, +{} 徳", actor, target, confidence, amount);
+        if amount > 0 {
+            toku.add_toku(actor, amount, TokuReason::KindnessVerified { confidence });
+        }
+        amount
+    }
+
+    /// IDでピアを取得
+    pub fn get_peer(&self, peer_id: &str) -> Option<DetectedPeer> {
+        self.detected_peers.lock().unwrap().get(peer_id).cloned()
     }
 
     /// 現在の状態を取得
@@ -940,6 +1509,23 @@ impl P2PManager {
         self.state.lock().unwrap().clone()
     }
 
+    /// ピアキャッシュをJSONで書き出す (WASMの再読み込みをまたいで保持する用)
+    pub fn serialize_peers(&self) -> String {
+        let peers: Vec<DetectedPeer> = self.detected_peers.lock().unwrap().values().cloned().collect();
+        serde_json::to_string(&peers).unwrap_or_else(|e| {
+            log::warn!("[P2P] ピアキャッシュのシリアライズに失敗: {}", e);
+            "[]".to_string()
+        })
+    }
+
+    /// serialize_peers の結果からピアキャッシュを復元する (既存のキャッシュは置き換える)
+    /// TTLは last_seen_ms で判定されるため、期限切れのピアは検索結果に現れない
+    pub fn restore_peers(&self, data: &str) -> Result<(), String> {
+        let peers: Vec<DetectedPeer> = serde_json::from_str(data).map_err(|e| format!("不正なピアキャッシュ: {}", e))?;
+        *self.detected_peers.lock().unwrap() = peers.into_iter().map(|p| (p.peer_id.clone(), p)).collect();
+        Ok(())
+    }
+
     /// ピア情報を手動で追加（テスト用）
     #[cfg(test)]
     pub fn add_mock_peer(&self, peer: DetectedPeer) {
@@ -965,6 +1551,35 @@ pub enum SocialEventType {
     TokuSent { amount: u32 },
     /// すれ違い
     PassedBy,
+    /// 初対面 (二人の間に初めて絆ができた)
+    FirstMet,
+    /// アプリ独自のイベント (名前でリスナーに振り分ける)
+    Custom(String),
+}
+
+impl SocialEventType {
+    /// イベント名から種類を決める。既知の名前以外は Custom
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "HelpGiven" | "help_given" | "助けた" => SocialEventType::HelpGiven,
+            "ThankYou" | "thank_you" | "ありがとう" => SocialEventType::ThankYou,
+            "PassedBy" | "passed_by" | "すれ違い" => SocialEventType::PassedBy,
+            "FirstMet" | "first_met" | "初対面" => SocialEventType::FirstMet,
+            other => SocialEventType::Custom(other.to_string()),
+        }
+    }
+
+    /// リスナー (on Event(...)) の振り分けに使うイベント名
+    pub fn name(&self) -> &str {
+        match self {
+            SocialEventType::HelpGiven => "HelpGiven",
+            SocialEventType::ThankYou => "ThankYou",
+            SocialEventType::TokuSent { .. } => "TokuSent",
+            SocialEventType::PassedBy => "PassedBy",
+            SocialEventType::FirstMet => "FirstMet",
+            SocialEventType::Custom(name) => name,
+        }
+    }
 }
 
 /// ソーシャル徳イベント
@@ -1039,10 +1654,14 @@ impl P2PManager {
         self.add_feed_event(event);
     }
     
-    /// フィードにイベントを追加
+    /// フィードにイベントを追加 (同じIDのイベントは重複して追加しない)
     pub fn add_feed_event(&self, event: SocialTokuEvent) {
         // TokuManagerのイベントリストとは別に、UI表示用のフィードを管理する想定
         let mut cache = self.feed_cache.lock().unwrap();
+        if cache.iter().any(|e| e.id == event.id) {
+            log::debug!("[Gossip] 受信済みのイベントを無視: {}", event.id);
+            return;
+        }
         cache.insert(0, event); // 最新を先頭に
         if cache.len() > 100 {
             cache.pop();
@@ -1093,34 +1712,84 @@ impl Default for P2PManager {
 static P2P_MANAGER: once_cell::sync::Lazy<P2PManager> = 
     once_cell::sync::Lazy::new(P2PManager::new);
 
+/// グローバルのP2Pマネージャ (WASM API からフィードや受信済みイベントを共有する)
+pub fn agn_p2p_manager() -> &'static P2PManager {
+    &P2P_MANAGER
+}
+
 /// AGNから呼び出される空間検索関数
-pub async fn agn_spatial_search(max_distance: f64, filters: &[(String, String)]) -> Vec<DetectedPeer> {
+pub async fn agn_spatial_search(max_distance: f64, filters: &[SpatialFilter]) -> Vec<DetectedPeer> {
     let mut results = P2P_MANAGER.get_nearby_peers(max_distance);
     
     // フィルタ適用
-    for (key, value) in filters {
-        if key == "状態" || key == "status" {
-            let beacon_type = match value.as_str() {
-                "暇" | "idle" => BeaconType::Idle,
-                "助けて" | "help" => BeaconType::NeedHelp,
-                "観光中" | "touring" => BeaconType::Touring,
-                _ => BeaconType::Custom(0x00),
-            };
-            results.retain(|p| p.beacon_type == beacon_type);
-        }
-    }
+    results.retain(|p| filters.iter().all(|f| peer_matches_filter(p, f)));
     
     results
 }
 
-/// AGNから呼び出されるビーコン発信関数
-pub async fn agn_broadcast_beacon(beacon_type_str: &str, duration_sec: Option<u64>) -> Result<(), String> {
-    let beacon_type = match beacon_type_str {
+/// AGNの状態名からビーコンタイプへ
+fn beacon_type_from_str(name: &str) -> BeaconType {
+    match name {
         "暇" | "idle" => BeaconType::Idle,
         "助けて" | "help" => BeaconType::NeedHelp,
         "観光中" | "touring" => BeaconType::Touring,
         _ => BeaconType::Custom(0x00), // カスタムタイプのデフォルト
-    };
+    }
+}
+
+/// ピアが空間検索フィルターに一致するか
+/// 状態はビーコンタイプ、徳はしきい値との比較 (徳が不明なピアは除外)。比較値は評価済みのリテラル
+pub fn peer_matches_filter(peer: &DetectedPeer, filter: &SpatialFilter) -> bool {
+    match filter.field.as_str() {
+        "状態" | "status" => match &filter.condition {
+            Condition::Equals(_, Expr::String(v) | Expr::Variable(v)) => peer.beacon_type == beacon_type_from_str(v),
+            _ => true,
+        },
+        "徳" | "toku" => {
+            let Some(toku) = peer.toku_score.map(|t| t as f64) else {
+                return false;
+            };
+            match &filter.condition {
+                Condition::Equals(_, Expr::Number(n)) => toku == *n,
+                Condition::NotEquals(_, Expr::Number(n)) => toku != *n,
+                Condition::GreaterThan(_, Expr::Number(n)) => toku > *n,
+                Condition::LessThan(_, Expr::Number(n)) => toku < *n,
+                Condition::GreaterOrEqual(_, Expr::Number(n)) => toku >= *n,
+                _ => true,
+            }
+        }
+        _ => true,
+    }
+}
+
+/// ユーザーIDハッシュをピアIDとして使う16進文字列へ
+pub fn user_id_hex(user_id: &[u8; 8]) -> String {
+    hex::encode(user_id)
+}
+
+/// 署名付きビーコンの送信者公開鍵からピアIDを導出する (鍵が同じなら常に同じID)
+pub fn peer_id_from_pubkey(public_key: &[u8; 32]) -> String {
+    hex::encode(public_key)
+}
+
+/// AGNから呼び出される距離推定モデル設定関数 (屋内/屋外のプリセットなど)
+pub fn agn_set_distance_model(model: DistanceModel) {
+    P2P_MANAGER.set_distance_model(model);
+}
+
+/// AGNから呼び出されるピアブロック関数
+pub fn agn_block_peer(peer_id: &str) {
+    P2P_MANAGER.block(peer_id);
+}
+
+/// AGNから呼び出されるピア取得関数
+pub fn agn_find_peer(peer_id: &str) -> Option<DetectedPeer> {
+    P2P_MANAGER.get_peer(peer_id)
+}
+
+/// AGNから呼び出されるビーコン発信関数
+pub async fn agn_broadcast_beacon(beacon_type_str: &str, duration_sec: Option<u64>) -> Result<(), String> {
+    let beacon_type = beacon_type_from_str(beacon_type_str);
     
     let config = BeaconConfig {
         beacon_type,
@@ -1199,6 +1868,26 @@ impl ProximityVerifier {
     }
 }
 
+/// 親切1回あたりの徳 (信頼度が十分高い場合)
+pub const KINDNESS_AWARD: u32 = 10;
+
+/// 信頼度から付与する徳を決める
+/// 0.8以上は満額、0.5以上は信頼度に比例、それ未満 (なりすましの疑い) は0
+pub fn kindness_award(confidence: f32) -> u32 {
+    if confidence >= 0.8 {
+        KINDNESS_AWARD
+    } else if confidence >= 0.5 {
+        (KINDNESS_AWARD as f32 * confidence).round() as u32
+    } else {
+        0
+    }
+}
+
+/// AGNから呼び出される親切記録関数 (近接証明に応じて徳を付与)
+pub fn agn_record_kindness(actor: &str, target: &str, rssi_log: Vec<i16>) -> u32 {
+    P2P_MANAGER.record_kindness(actor, target, &rssi_log)
+}
+
 /// AGNから呼び出される近接証明検証関数
 pub fn agn_verify_presence(rssi_history: Vec<i16>) -> f32 {
     ProximityVerifier::verify_presence(&rssi_history)
@@ -1219,6 +1908,81 @@ mod tests {
         assert_eq!(manager.get_state(), P2PState::Uninitialized);
     }
 
+    fn beacon(duration_sec: Option<u64>) -> BeaconConfig {
+        BeaconConfig { beacon_type: BeaconType::Idle, interval_ms: 100, duration_sec, payload: HashMap::new() }
+    }
+
+    #[test]
+    fn test_state_transition_table() {
+        use P2PState::*;
+        assert!(Uninitialized.can_transition_to(&Stopped));
+        assert!(!Uninitialized.can_transition_to(&Scanning));
+        assert!(!Uninitialized.can_transition_to(&Uninitialized));
+        assert!(Stopped.can_transition_to(&Broadcasting));
+        assert!(!Stopped.can_transition_to(&ScanningAndBroadcasting));
+        assert!(Scanning.can_transition_to(&ScanningAndBroadcasting));
+        assert!(!Scanning.can_transition_to(&Broadcasting));
+        assert!(ScanningAndBroadcasting.can_transition_to(&ScanningAndBroadcasting));
+        assert!(!ScanningAndBroadcasting.can_transition_to(&Stopped));
+        assert!(!Broadcasting.can_transition_to(&Uninitialized));
+    }
+
+    #[tokio::test]
+    async fn test_state_transitions() {
+        let manager = P2PManager::new();
+        assert!(manager.start_scanning().await.is_err());
+        assert!(manager.start_broadcasting(beacon(None)).await.is_err());
+        assert!(manager.stop_scanning().is_err());
+        assert!(manager.stop_broadcasting().is_err());
+        assert!(manager.can_transition(&P2PState::Stopped));
+
+        manager.initialize().await.unwrap();
+        // 停止中の停止は冪等
+        assert!(manager.stop_scanning().is_ok());
+        assert!(manager.stop_broadcasting().is_ok());
+        assert_eq!(manager.get_state(), P2PState::Stopped);
+
+        manager.start_scanning().await.unwrap();
+        assert_eq!(manager.get_state(), P2PState::Scanning);
+        manager.start_broadcasting(beacon(None)).await.unwrap();
+        assert_eq!(manager.get_state(), P2PState::ScanningAndBroadcasting);
+
+        // 発信中の再発信は冪等 (スキャンを止めない)
+        manager.start_broadcasting(beacon(None)).await.unwrap();
+        assert_eq!(manager.get_state(), P2PState::ScanningAndBroadcasting);
+        manager.start_scanning().await.unwrap();
+        assert_eq!(manager.get_state(), P2PState::ScanningAndBroadcasting);
+
+        manager.stop_scanning().unwrap();
+        assert_eq!(manager.get_state(), P2PState::Broadcasting);
+        assert!(!manager.can_transition(&P2PState::Scanning));
+        manager.stop_broadcasting().unwrap();
+        assert_eq!(manager.get_state(), P2PState::Stopped);
+        manager.stop_broadcasting().unwrap();
+        assert_eq!(manager.get_state(), P2PState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_stops_after_duration() {
+        let clock = Arc::new(crate::utils::MockClock::new(0.0));
+        let manager = P2PManager::new();
+        manager.set_clock(clock.clone());
+        manager.initialize().await.unwrap();
+
+        manager.start_broadcasting(beacon(Some(1))).await.unwrap();
+        assert_eq!(manager.get_state(), P2PState::Broadcasting);
+
+        clock.advance(999.0);
+        assert!(!manager.expire_broadcast());
+        assert_eq!(manager.get_state(), P2PState::Broadcasting);
+        clock.advance(1.0);
+        // 状態の読み出しだけでは停止しない
+        assert_eq!(manager.get_state(), P2PState::Broadcasting);
+        assert!(manager.expire_broadcast());
+        assert_eq!(manager.get_state(), P2PState::Stopped);
+        assert!(manager.current_beacon.lock().unwrap().is_none());
+    }
+
     #[test]
     fn test_rssi_distance_estimation() {
         // 1mでの理論値（TxPower = -59, RSSI = -59 → distance = 1m）
@@ -1230,6 +1994,67 @@ mod tests {
         assert!((distance_10m - 10.0).abs() < 1.0);
     }
 
+    #[test]
+    fn test_distance_model_presets() {
+        // 同じRSSIでも屋内 (n=3) は自由空間 (n=2) より近いと推定される
+        let free_space = DistanceModel::free_space().estimate(-79);
+        let indoor = DistanceModel::indoor().estimate(-79);
+        assert!((free_space - 10.0).abs() < 0.01);
+        assert!((indoor - 10.0_f64.powf(20.0 / 30.0)).abs() < 0.01);
+        assert!(indoor < free_space);
+        assert_eq!(DistanceModel::indoor().estimate(0), -1.0);
+    }
+
+    #[test]
+    fn test_observed_peer_uses_distance_model() {
+        let manager = P2PManager::new();
+        manager.observe_peer("peer1", BeaconType::Idle, -79);
+        // 自由空間では約10m
+        assert!(manager.get_nearby_peers(5.0).is_empty());
+
+        manager.set_distance_model(DistanceModel::indoor());
+        manager.observe_peer("peer1", BeaconType::Idle, -79);
+        // 屋内モデルでは約4.6m
+        assert_eq!(manager.get_nearby_peers(5.0).len(), 1);
+    }
+
+    #[test]
+    fn test_rssi_smoothing_converges() {
+        let manager = P2PManager::new();
+        manager.observe_peer("peer1", BeaconType::Idle, -70);
+        let mut peer = manager.get_peer("peer1").unwrap();
+
+        // -70 を中心に ±10 で揺れる計測値
+        for i in 0..30 {
+            peer.push_rssi(if i % 2 == 0 { -60 } else { -80 });
+        }
+        assert!((peer.smoothed_rssi() + 70.0).abs() < 4.0);
+        assert_eq!(peer.rssi_history.len(), DetectedPeer::RSSI_HISTORY_LEN);
+
+        // 実際に離れた場合は新しい値に追従する
+        for _ in 0..30 {
+            peer.push_rssi(-90);
+        }
+        assert!((peer.smoothed_rssi() + 90.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_record_kindness_scales_with_confidence() {
+        let toku = TokuManager::new();
+        // 近距離で自然に揺らぐRSSI
+        let genuine = [-55, -52, -58, -54, -57, -53];
+        // 遠距離で完全に一定 (なりすましの疑い)
+        let spoofed = [-85, -85, -85, -85, -85, -85];
+
+        let genuine_award = P2PManager::record_kindness_to(&toku, "alice", "bob", &genuine);
+        let spoofed_award = P2PManager::record_kindness_to(&toku, "mallory", "bob", &spoofed);
+
+        assert_eq!(genuine_award, KINDNESS_AWARD);
+        assert_eq!(spoofed_award, 0);
+        assert!(toku.get_score("alice") > toku.get_score("mallory"));
+        assert_eq!(kindness_award(0.6), 6);
+    }
+
     #[test]
     fn test_nearby_peers_filter() {
         let manager = P2PManager::new();
@@ -1240,9 +2065,11 @@ mod tests {
             beacon_type: BeaconType::Idle,
             estimated_distance: 5.0,
             rssi: -65,
-            last_seen: Instant::now(),
+            last_seen_ms: crate::utils::now_ms(),
             toku_score: Some(100),
             payload: HashMap::new(),
+            rssi_history: Vec::new(),
+            rssi_ema: None,
         });
         
         manager.add_mock_peer(DetectedPeer {
@@ -1250,9 +2077,11 @@ mod tests {
             beacon_type: BeaconType::NeedHelp,
             estimated_distance: 15.0,
             rssi: -80,
-            last_seen: Instant::now(),
+            last_seen_ms: crate::utils::now_ms(),
             toku_score: Some(50),
             payload: HashMap::new(),
+            rssi_history: Vec::new(),
+            rssi_ema: None,
         });
         
         // 10m以内のピアをフィルタ
@@ -1265,6 +2094,102 @@ mod tests {
         assert_eq!(idle_peers.len(), 1);
     }
 
+    #[test]
+    fn test_blocked_peer_excluded_from_search() {
+        let manager = P2PManager::new();
+        let user_id = TokuManager::hash_user_id("mallory");
+        let peer_id = user_id_hex(&user_id);
+        let packet = EeyoBeaconPacket::new(BeaconType::Idle, 80, user_id).to_bytes();
+
+        assert!(manager.receive_beacon_bytes(&packet, -60).unwrap());
+        assert_eq!(manager.get_nearby_peers(100.0).len(), 1);
+
+        // ブロック後は検索結果から消え、新しいビーコンも破棄される
+        manager.block(&peer_id);
+        assert!(manager.get_nearby_peers(100.0).is_empty());
+        assert!(!manager.receive_beacon_bytes(&packet, -60).unwrap());
+
+        manager.unblock(&peer_id);
+        let nearby = manager.get_nearby_peers(100.0);
+        assert_eq!(nearby.len(), 1);
+        assert_eq!(nearby[0].toku_score, Some(80));
+    }
+
+    #[test]
+    fn test_receive_beacon_updates_single_peer() {
+        let clock = Arc::new(crate::utils::MockClock::new(0.0));
+        let manager = P2PManager::new();
+        manager.set_clock(clock.clone());
+        let user_id = TokuManager::hash_user_id("alice");
+
+        assert!(manager.receive_beacon(&EeyoBeaconPacket::new(BeaconType::Idle, 100, user_id), -70));
+        clock.advance(500.0);
+        assert!(manager.receive_beacon(&EeyoBeaconPacket::new(BeaconType::NeedHelp, 120, user_id), -60));
+
+        let peers = manager.get_nearby_peers(1000.0);
+        assert_eq!(peers.len(), 1);
+        let peer = &peers[0];
+        assert_eq!(peer.peer_id, user_id_hex(&user_id));
+        assert_eq!(peer.beacon_type, BeaconType::NeedHelp);
+        assert_eq!(peer.toku_score, Some(120));
+        assert_eq!(peer.rssi_history, vec![-70, -60]);
+        assert_eq!(peer.last_seen_ms, 500.0);
+    }
+
+    #[test]
+    fn test_peer_id_from_pubkey_is_stable() {
+        let alice = SecurityContext::new().verifying_key.to_bytes();
+        let bob = SecurityContext::new().verifying_key.to_bytes();
+
+        assert_eq!(peer_id_from_pubkey(&alice), peer_id_from_pubkey(&alice));
+        assert_ne!(peer_id_from_pubkey(&alice), peer_id_from_pubkey(&bob));
+        assert_eq!(peer_id_from_pubkey(&alice).len(), 64);
+    }
+
+    #[test]
+    fn test_receive_secure_beacon() {
+        let manager = P2PManager::new();
+        let context = SecurityContext::new();
+        let public_key = context.verifying_key.to_bytes();
+        let bytes = EeyoSecurePacket::new(BeaconType::Touring, 300, &public_key, &context.signing_key).to_bytes();
+
+        // 改ざんされたパケットは無視される
+        let mut tampered = bytes.clone();
+        tampered[4] ^= 0xFF;
+        assert!(!manager.receive_secure_beacon(&tampered, -60));
+        assert!(manager.get_nearby_peers(1000.0).is_empty());
+
+        assert!(manager.receive_secure_beacon(&bytes, -60));
+        let peer = manager.get_peer(&peer_id_from_pubkey(&public_key)).unwrap();
+        assert_eq!(peer.beacon_type, BeaconType::Touring);
+        assert_eq!(peer.toku_score, Some(300));
+    }
+
+    #[test]
+    fn test_peer_cache_roundtrip() {
+        let clock = Arc::new(crate::utils::MockClock::new(1_000_000.0));
+        let manager = P2PManager::new();
+        manager.set_clock(clock.clone());
+        manager.observe_peer("peer1", BeaconType::NeedHelp, -60);
+
+        let saved = manager.serialize_peers();
+
+        // 再読み込み後の新しいマネージャに復元する
+        let restored = P2PManager::new();
+        restored.set_clock(clock.clone());
+        restored.restore_peers(&saved).unwrap();
+
+        let peer = restored.get_peer("peer1").unwrap();
+        assert_eq!(peer.beacon_type, BeaconType::NeedHelp);
+        assert_eq!(peer.last_seen_ms, 1_000_000.0);
+        assert_eq!(restored.get_nearby_peers(100.0).len(), 1);
+
+        // TTL (30秒) を過ぎると検索結果から外れる
+        clock.advance(31_000.0);
+        assert!(restored.get_nearby_peers(100.0).is_empty());
+        assert!(restored.restore_peers("not json").is_err());
+    }
+
     // === ビーコンパケットテスト ===
 
     #[test]
@@ -1339,6 +2264,172 @@ mod tests {
         assert_eq!(manager.get_score("user1"), 50);
     }
 
+    #[test]
+    fn test_toku_manager_strict_subtract() {
+        let manager = TokuManager::new();
+        assert_eq!(manager.try_subtract_toku("user1", 40, TokuReason::Penalty), Ok(60));
+
+        // 残高を超える減算はエラーでスコアは変わらない
+        assert!(manager.try_subtract_toku("user1", 61, TokuReason::Penalty).is_err());
+        assert_eq!(manager.get_score("user1"), 60);
+    }
+
+    #[test]
+    fn test_toku_manager_transfer() {
+        let manager = TokuManager::new();
+        assert!(manager.transfer_toku("alice", "bob", 30).unwrap());
+        assert_eq!(manager.get_score("alice"), 70);
+        assert_eq!(manager.get_score("bob"), 130);
+        assert!(manager.get_relationship("alice", "bob").has_bond());
+        // 2回目以降は初対面ではない
+        assert!(!manager.transfer_toku("alice", "bob", 10).unwrap());
+        assert_eq!(manager.get_score("alice"), 60);
+
+        // 残高不足なら両者とも変わらない
+        assert!(manager.transfer_toku("alice", "bob", 500).is_err());
+        assert_eq!(manager.get_score("alice"), 60);
+        assert_eq!(manager.get_score("bob"), 140);
+    }
+
+    #[test]
+    fn test_toku_manager_transfer_to_receiver_near_cap() {
+        let manager = TokuManager::new();
+        manager.add_toku("bob", TokuManager::MAX_SCORE - TokuManager::INITIAL_SCORE - 10, TokuReason::HelpProvided);
+        let history_len = manager.history("alice").len() + manager.history("bob").len();
+
+        // 上限を超える送付は拒否され、徳は消えない
+        assert!(manager.transfer_toku("alice", "bob", 30).is_err());
+        assert_eq!(manager.get_score("alice"), TokuManager::INITIAL_SCORE);
+        assert_eq!(manager.get_score("bob"), TokuManager::MAX_SCORE - 10);
+        assert_eq!(manager.history("alice").len() + manager.history("bob").len(), history_len);
+
+        // ちょうど上限までなら送れる
+        manager.transfer_toku("alice", "bob", 10).unwrap();
+        assert_eq!(manager.get_score("alice"), TokuManager::INITIAL_SCORE - 10);
+        assert_eq!(manager.get_score("bob"), TokuManager::MAX_SCORE);
+    }
+
+    #[test]
+    fn test_toku_manager_top_scores() {
+        let manager = TokuManager::new();
+        manager.add_toku("alice", 10, TokuReason::HelpProvided);
+        manager.add_toku("bob", 300, TokuReason::HelpProvided);
+        manager.add_toku("carol", 50, TokuReason::HelpProvided);
+        manager.add_toku("dave", 50, TokuReason::HelpProvided);
+
+        let top = manager.top_scores(3);
+        assert_eq!(top, vec![
+            ("bob".to_string(), 400),
+            ("carol".to_string(), 150),
+            ("dave".to_string(), 150),
+        ]);
+    }
+
+    #[test]
+    fn test_toku_manager_bonds_for() {
+        let manager = TokuManager::new();
+        manager.update_relationship("alice", "carol", 30);
+        manager.update_relationship("alice", "bob", 10);
+        manager.update_relationship("bob", "alice", 5);
+
+        let bonds = manager.bonds_for("alice");
+        let ids: Vec<&str> = bonds.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["bob", "carol"]);
+        assert_eq!(bonds[1].1.strength, 30);
+        assert!(manager.bonds_for("dave").is_empty());
+    }
+
+    #[test]
+    fn test_deepen_bond_reports_first_meeting() {
+        let manager = TokuManager::new();
+        assert!(manager.deepen_bond("alice", "bob", 5));
+        assert!(!manager.deepen_bond("alice", "bob", 5));
+
+        // 強さだけ更新された関係 (レベル0) は最初の深化で初対面になる
+        manager.update_relationship("alice", "carol", 3);
+        assert!(manager.deepen_bond("alice", "carol", 1));
+        let rel = manager.get_relationship("alice", "carol");
+        assert_eq!(rel.level, 1);
+        assert_ne!(rel.first_met, 0);
+    }
+
+    #[test]
+    fn test_bond_config_levels_up_faster() {
+        let default_manager = TokuManager::new();
+        let fast_manager = TokuManager::new();
+        fast_manager.set_bond_config(BondConfig { strength_per_level: 50, ..BondConfig::default() });
+
+        // 新しい絆は強さ10から始まる
+        default_manager.deepen_bond("alice", "bob", 190);
+        fast_manager.deepen_bond("alice", "bob", 190);
+
+        assert_eq!(default_manager.get_relationship("alice", "bob").level, 2);
+        assert_eq!(fast_manager.get_relationship("alice", "bob").level, 4);
+
+        // 上限も設定に従う
+        fast_manager.set_bond_config(BondConfig { max_strength: 250, strength_per_level: 50 });
+        fast_manager.deepen_bond("alice", "bob", 500);
+        assert_eq!(fast_manager.get_relationship("alice", "bob").strength, 250);
+    }
+
+    #[test]
+    fn test_toku_manager_total_earned_ignores_spending() {
+        let manager = TokuManager::new();
+        manager.add_toku("Alice", 300, TokuReason::HelpProvided);
+        manager.transfer_toku("Alice", "Bob", 250).unwrap();
+        manager.subtract_toku("Alice", 50, TokuReason::Penalty);
+
+        assert_eq!(manager.get_score("Alice"), 100);
+        assert_eq!(manager.total_earned("Alice"), 400);
+        assert_eq!(manager.total_earned("Bob"), 350);
+        assert_eq!(manager.history("Alice").len(), 3);
+        assert_eq!(manager.total_earned("Nobody"), TokuManager::INITIAL_SCORE);
+    }
+
+    #[test]
+    fn test_rank_ladder_default_matches_legacy_boundary() {
+        let ladder = RankLadder::default();
+        assert_eq!(ladder.rank_for(1000), "協力者");
+        assert_eq!(ladder.rank_for(1001), "徳人");
+        assert_eq!(ladder.rank_for(10), "一般");
+        assert_eq!(RankLadder::new(vec![(50, "A".to_string())]).rank_for(0), "A");
+        assert_eq!(RankLadder::new(Vec::new()).rank_for(0), "");
+    }
+
+    #[test]
+    fn test_toku_manager_mutual_and_suggested_bonds() {
+        let manager = TokuManager::new();
+        // alice -> bob, carol / bob -> dave, erin / carol -> dave, alice
+        manager.deepen_bond("alice", "bob", 10);
+        manager.deepen_bond("alice", "carol", 10);
+        manager.deepen_bond("bob", "dave", 10);
+        manager.deepen_bond("bob", "erin", 10);
+        manager.deepen_bond("carol", "dave", 10);
+        manager.deepen_bond("carol", "alice", 10);
+        // 絆レベル0の関係は友達として数えない
+        manager.update_relationship("carol", "frank", 5);
+
+        assert_eq!(manager.mutual_bonds("bob", "carol"), vec!["dave".to_string()]);
+        assert!(manager.mutual_bonds("alice", "dave").is_empty());
+
+        // dave は bob と carol の2人経由、erin は bob 経由のみ
+        assert_eq!(manager.suggest_bonds("alice", 5), vec!["dave".to_string(), "erin".to_string()]);
+        assert_eq!(manager.suggest_bonds("alice", 1), vec!["dave".to_string()]);
+    }
+
+    #[test]
+    fn test_toku_manager_to_dot() {
+        let manager = TokuManager::new();
+        manager.update_relationship("alice", "bob", 30);
+        manager.update_relationship("bob", "carol", 7);
+
+        let dot = manager.to_dot();
+        assert!(dot.starts_with("digraph bonds {"));
+        assert!(dot.contains("\"alice\" -> \"bob\" [label=\"30\", weight=30];"));
+        assert!(dot.contains("\"bob\" -> \"carol\" [label=\"7\", weight=7];"));
+        assert!(dot.contains("    \"carol\";"));
+    }
+
     #[test]
     fn test_user_id_hash() {
         let hash1 = TokuManager::hash_user_id("user_abc");
@@ -1,17 +1,25 @@
 //! AGN Parser - 構文解析器
 //! 日本語SOV構文と英語SVO構文の両方を解析してASTを生成する
 
-use crate::lexer::Token;
+use crate::lexer::{keyword_text, Span, Token};
+use crate::messages::{localize_parse_error, unsupported_keyword};
+use crate::utils::Locale;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
 /// 式（値を表す）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Expr {
     Number(f64),
     String(String),
+    /// 真偽値リテラル (真/偽)
+    Bool(bool),
     Variable(String),
     // Eeyo: 空間・時間型 (Phase 13)
     Distance { value: f64, unit: String },
     Duration { value: f64, unit: String },
+    /// 百分率 (50%)。評価すると分数 (0.5) になる
+    Percent { value: f64 },
     // AGN 2.0: Property Access (User.Toku)
     PropertyAccess {
         target: Box<Expr>,
@@ -24,14 +32,21 @@ pub enum Expr {
         name: String,
         args: Vec<Expr>,
     },
+    /// 展開引数: アクション(...リスト) でリストを位置引数に展開
+    Spread(Box<Expr>),
+    /// 結果待ち: 待つ X (Pending を解決した値)
+    Await(Box<Expr>),
+    /// レコードリテラル: {言語: "en", 長さ: "短い"}
+    Record(Vec<(String, Expr)>),
 }
 
 /// 条件式
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Condition {
     Equals(Expr, Expr),
     GreaterThan(Expr, Expr),
     LessThan(Expr, Expr),
+    GreaterOrEqual(Expr, Expr), // 以上
     // Eeyo: 空間条件
     Nearer(Expr),   // より近い
     Farther(Expr),  // より遠い
@@ -39,25 +54,52 @@ pub enum Condition {
     HasBond(Expr, Expr), // A と B の間に 絆 がある
     // Truthy check
     Truthy(Expr),
+    /// A は B ではない
+    NotEquals(Expr, Expr),
+    /// A かつ B
+    And(Box<Condition>, Box<Condition>),
+    /// A または B
+    Or(Box<Condition>, Box<Condition>),
 }
 
 /// 空間検索フィルター
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SpatialFilter {
     pub field: String,       // "状態", "徳"
     pub condition: Condition,
 }
 
+/// 空間検索結果の並び順
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SearchOrder {
+    /// 近い順
+    Nearest,
+    /// 徳の高い順
+    HighestToku,
+}
+
 /// 文（実行単位）
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Statement {
-    /// 代入文: [ターゲット] は [値] だ / let X = 10
+    /// 代入文: [ターゲット] は [値] だ / let X = 10 (だった・です・でした も同義)
     Assignment { target: Expr, value: Expr },
+    /// 否定比較: [A] は [B] ではない
+    /// 代入ではなく比較として扱い、成り立たなければ実行時エラーとして報告する
+    Assert { condition: Condition },
     /// アセットロード: [ターゲット] は [パス] を 読み込む
     LoadAsset {
         target: Expr,
         path: Expr,
     },
+    /// 画面の保存: 画面 を [パス] に 保存する (PNG)
+    SaveScreen {
+        path: Expr,
+    },
+    /// チャンネル付きの記録: [値] を "チャンネル" に 記録する (ホスト側で絞り込める)
+    Log {
+        value: Expr,
+        channel: String,
+    },
     /// UIコンポーネント定義: [ターゲット] は [スタイル] な [コンポーネント] だ
     ComponentDefine {
         target: Expr,
@@ -70,6 +112,17 @@ pub enum Statement {
     UnaryOp { operand: Expr, verb: String },
     /// 非同期実行: [値] を 並列で [動詞]
     AsyncOp { operand: Expr, verb: String },
+    /// 並列実行: 結果 は テキスト を 並列で 要約する / X に 5 を 並列で 足す
+    /// 代入先は Pending となり、待つ で解決される
+    Parallel { inner: Box<Statement> },
+    /// 結果待ち: 待つ 結果 / 結果 を 待つ
+    Await { target: Expr },
+    /// 並列実行の取り消し: 取り消す 結果 / 結果 を 取り消す
+    Cancel { target: Expr },
+    /// 表示切替: ボタン を 隠す / ボタン を 見せる
+    SetVisibility { target: Expr, visible: bool },
+    /// 有効/無効切替: ボタン を 無効 に する / ボタン を 有効にする
+    SetEnabled { target: Expr, enabled: bool },
     /// 条件分岐: if [条件] then [処理] end
     IfStatement {
         condition: Condition,
@@ -81,6 +134,15 @@ pub enum Statement {
         count: Expr,
         body: Vec<Statement>,
     },
+    /// 条件付きループ: while [条件] [処理] end / [条件] の間 繰り返す [処理] おわり
+    WhileStatement {
+        condition: Condition,
+        body: Vec<Statement>,
+    },
+    /// ループを抜ける: break / やめる
+    Break,
+    /// 次の反復へ進む: continue / つぎへ
+    Continue,
     /// AI操作: [ターゲット] は [入力] を [オプション] に [動詞]
     AiOp {
         result: Expr,
@@ -97,6 +159,8 @@ pub enum Statement {
         target: Expr,
         event: String,
         body: Vec<Statement>,
+        /// 間引き間隔 (秒): on Button click 0.5秒 間隔 で ... end
+        interval: Option<f64>,
     },
     /// Phase 15: Event Listener: on Event(Type) from A to B
     EventListener {
@@ -124,6 +188,12 @@ pub enum Statement {
         target: Expr,
         body: Vec<Statement>,
     },
+    /// 束縛: [ラベル] の 文字 を [変数] に 束縛する (変数の変更で表示を更新)
+    Bind {
+        target: Expr,
+        property: String,
+        variable: String,
+    },
     /// レイアウト: [リスト] を [方向] に 置く
     Layout {
         target: Expr,
@@ -136,6 +206,10 @@ pub enum Statement {
         result: Expr,
         max_distance: Expr,
         filters: Vec<SpatialFilter>,
+        /// 並び順: ... を 近い順 に 探す / 徳の高い順 に 探す
+        order: Option<SearchOrder>,
+        /// 最大件数: ... を 3人 探す (None = 無制限)
+        limit: Option<Expr>,
     },
     /// ビーコン発信: ビーコン を 発信する ... おわり
     BeaconBroadcast {
@@ -181,40 +255,134 @@ pub enum Statement {
         name: String,
         args: Vec<Expr>,
     },
+    /// コメント (Lexer::with_comments 使用時のみ生成)
+    /// trailing なら直前の文と同じ行に書かれていた
+    Comment {
+        text: String,
+        trailing: bool,
+    },
+    /// ソース位置付きの文 (Parser::with_spans 使用時のみ生成)
+    Spanned {
+        span: Span,
+        inner: Box<Statement>,
+    },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Statement {
+    /// 位置情報を取り除いた中身の文を返す
+    pub fn unspanned(&self) -> &Statement {
+        match self {
+            Statement::Spanned { inner, .. } => inner.unspanned(),
+            other => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum LayoutDirection {
     Vertical,
     Horizontal,
 }
 
 /// プログラム全体
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     pub statements: Vec<Statement>,
 }
 
+/// 先読みで保持するトークン数 (peek の最大オフセットより大きくすること)
+const LOOKAHEAD: usize = 8;
+
 pub struct Parser {
-    tokens: Vec<Token>,
+    // トークンの供給元。必要な分だけ読み進める
+    source: Box<dyn Iterator<Item = Token>>,
+    // 直前のトークン + 現在位置からの先読み分 (buffer[0] は絶対位置 base)
+    buffer: VecDeque<Token>,
+    base: usize,
     pos: usize,
+    // トークンごとのソース位置 (空なら位置情報なし)
+    spans: Vec<Span>,
+    // エラーメッセージの言語 (既定は英語)
+    locale: Locale,
+    // 解析中のループの入れ子の深さ (break / continue の検査用)
+    loop_depth: usize,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0 }
+    /// トークン列から構築する (Vec でも Lexer などのイテレータでもよい)
+    pub fn new<I>(tokens: I) -> Self
+    where
+        I: IntoIterator<Item = Token>,
+        I::IntoIter: 'static,
+    {
+        let mut parser = Self {
+            source: Box::new(tokens.into_iter()),
+            buffer: VecDeque::new(),
+            base: 0,
+            pos: 0,
+            spans: Vec::new(),
+            locale: Locale::En,
+            loop_depth: 0,
+        };
+        parser.fill();
+        parser
+    }
+
+    /// ソース位置付きで解析する (各文が Statement::Spanned で包まれる)
+    pub fn with_spans<I>(tokens: I, spans: Vec<Span>) -> Self
+    where
+        I: IntoIterator<Item = Token>,
+        I::IntoIter: 'static,
+    {
+        Self { spans, ..Self::new(tokens) }
+    }
+
+    /// 解析が止まった位置 (位置情報付きで構築したときのみ)。構文エラーの報告用
+    pub fn current_span(&self) -> Option<Span> {
+        self.spans.get(self.pos).copied()
+    }
+
+    /// エラーメッセージのロケールを設定 (Locale::Ja で日本語)
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.locale = locale;
+    }
+
+    /// 現在位置から LOOKAHEAD 個先まで供給元から読み込む
+    fn fill(&mut self) {
+        while self.buffer.len() <= self.pos - self.base + LOOKAHEAD {
+            match self.source.next() {
+                Some(token) => self.buffer.push_back(token),
+                None => break,
+            }
+        }
     }
 
     fn current(&self) -> &Token {
-        self.tokens.get(self.pos).unwrap_or(&Token::EOF)
+        self.peek(0)
     }
 
     fn peek(&self, offset: usize) -> &Token {
-        self.tokens.get(self.pos + offset).unwrap_or(&Token::EOF)
+        debug_assert!(offset <= LOOKAHEAD, "peek beyond lookahead window");
+        self.buffer.get(self.pos - self.base + offset).unwrap_or(&Token::EOF)
+    }
+
+    /// 直前に読み進めたトークン
+    fn previous(&self) -> Option<&Token> {
+        if self.pos > self.base {
+            self.buffer.get(self.pos - self.base - 1)
+        } else {
+            None
+        }
     }
 
     fn advance(&mut self) {
         self.pos += 1;
+        // 直前の 1 トークンだけ残して読み終えた分を捨てる
+        while self.pos - self.base > 1 && !self.buffer.is_empty() {
+            self.buffer.pop_front();
+            self.base += 1;
+        }
+        self.fill();
     }
 
     fn skip_newlines(&mut self) {
@@ -260,6 +428,10 @@ impl Parser {
                 self.advance();
                 Expr::String(s)
             },
+            Token::Bool(b) => {
+                self.advance();
+                Expr::Bool(b)
+            },
             Token::Noun(name) => {
                 self.advance();
                 if matches!(self.current(), Token::LParen) {
@@ -270,7 +442,18 @@ impl Parser {
                             self.advance();
                             break;
                         }
-                        args.push(self.parse_expression()?);
+                        if matches!(self.current(), Token::Dot)
+                            && matches!(self.peek(1), Token::Dot)
+                            && matches!(self.peek(2), Token::Dot)
+                        {
+                            // skip ...
+                            self.advance();
+                            self.advance();
+                            self.advance();
+                            args.push(Expr::Spread(Box::new(self.parse_expression()?)));
+                        } else {
+                            args.push(self.parse_expression()?);
+                        }
                         
                         if matches!(self.current(), Token::Comma) {
                             self.advance();
@@ -291,6 +474,14 @@ impl Parser {
                 self.advance();
                 Expr::Duration { value, unit }
             },
+            Token::Percent { value } => {
+                self.advance();
+                Expr::Percent { value }
+            },
+            Token::Verb(ref v) if v == "待つ" => {
+                self.advance(); // skip 待つ
+                Expr::Await(Box::new(self.parse_expression()?))
+            }
             Token::KeywordBond => {
                 // bond(Expr, Expr)
                 self.advance(); // skip bond
@@ -315,12 +506,42 @@ impl Parser {
                 
                 Expr::Bond(Box::new(left), Box::new(right))
             }
-            _ => return Err(format!("Expected expression, got {:?}", token)),
+            Token::LBrace => self.parse_record_literal()?,
+            _ => return Err(self.unsupported_keyword_or(format!("Expected expression, got {:?}", token))),
         };
         
         Ok(expr)
     }
 
+    /// { キー: 値, ... } (キーは名詞または文字列)
+    fn parse_record_literal(&mut self) -> Result<Expr, String> {
+        self.advance(); // skip {
+        let mut fields = Vec::new();
+        loop {
+            if matches!(self.current(), Token::RBrace) {
+                self.advance();
+                break;
+            }
+            let key = match self.current() {
+                Token::Noun(k) | Token::String(k) => k.clone(),
+                other => return Err(format!("Expected record key, got {:?}", other)),
+            };
+            self.advance();
+            if !matches!(self.current(), Token::Colon) {
+                return Err(format!("Expected ':' after record key '{}', got {:?}", key, self.current()));
+            }
+            self.advance(); // skip :
+            fields.push((key, self.parse_expression()?));
+
+            if matches!(self.current(), Token::Comma) {
+                self.advance();
+            } else if !matches!(self.current(), Token::RBrace) {
+                return Err(format!("Expected ',' or '}}' in record, got {:?}", self.current()));
+            }
+        }
+        Ok(Expr::Record(fields))
+    }
+
     fn current_to_expr(&mut self) -> Result<Expr, String> {
         self.parse_expression()
     }
@@ -335,7 +556,9 @@ impl Parser {
                 break;
             }
 
-            let stmt = self.parse_statement()?;
+            let stmt = self
+                .parse_statement()
+                .map_err(|e| localize_parse_error(&e, self.locale))?;
             statements.push(stmt);
         }
 
@@ -345,6 +568,32 @@ impl Parser {
     fn parse_statement(&mut self) -> Result<Statement, String> {
         self.skip_newlines();
 
+        let span = self.spans.get(self.pos).copied();
+        let stmt = self.parse_statement_inner()?;
+        Ok(match span {
+            Some(span) => Statement::Spanned { span, inner: Box::new(stmt) },
+            None => stmt,
+        })
+    }
+
+    fn parse_statement_inner(&mut self) -> Result<Statement, String> {
+        if let Token::Comment(text) = self.current() {
+            let text = text.clone();
+            let trailing = !matches!(self.previous(), None | Some(Token::Newline));
+            self.advance();
+            return Ok(Statement::Comment { text, trailing });
+        }
+
+        // 予約語への代入 (徳 は 10 だ) は誤解析されるため明示的に拒否する
+        if matches!(self.peek(1), Token::ParticleWa) {
+            if let Some(kw) = keyword_text(self.current()) {
+                return Err(format!(
+                    "Reserved keyword '{}' cannot be used as a variable name; choose a different name or quote it as `{}`",
+                    kw, kw
+                ));
+            }
+        }
+
         // AGN 2.0: Rule Definition
         if matches!(self.current(), Token::KeywordRule) {
             return self.parse_rule_definition();
@@ -371,6 +620,32 @@ impl Parser {
              return self.parse_english_action_command();
         }
         
+        // 画面 を "out.png" に 保存する
+        if matches!(self.current(), Token::ScreenNoun) && matches!(self.peek(1), Token::ParticleWo) {
+            return self.parse_save_screen();
+        }
+
+        // break / やめる, continue / つぎへ
+        if matches!(self.current(), Token::KeywordBreak | Token::KeywordContinue) {
+            let (stmt, en, ja) = if matches!(self.current(), Token::KeywordBreak) {
+                (Statement::Break, "break", "やめる")
+            } else {
+                (Statement::Continue, "continue", "つぎへ")
+            };
+            if self.loop_depth == 0 {
+                let keyword = if self.locale == Locale::Ja { ja } else { en };
+                return Err(unsupported_keyword(keyword).unwrap_or_default());
+            }
+            self.advance();
+            return Ok(stmt);
+        }
+
+        // while X < 10 ... end / X < 10 の間 繰り返す ... おわり
+        // (日本語の見出しは代入やとき構文と同じ語で始まるので、それらより先に判定する)
+        if matches!(self.current(), Token::KeywordWhile) || self.is_japanese_while() {
+            return self.parse_while_statement();
+        }
+
         // === English SVO Patterns ===
         
         // English: show X to Screen (check before regular show)
@@ -427,6 +702,16 @@ impl Parser {
              return self.parse_expr_statement();
         }
 
+        // 日本語: 徳 が 500 以上 の とき ... おわり
+        if matches!(self.current(), Token::Noun(_) | Token::KeywordToku) && matches!(self.peek(1), Token::ParticleGa) {
+            return self.parse_when_statement();
+        }
+
+        // Eeyo: ビーコン を 発信する / [種類] の ビーコン を 発信する
+        if self.is_beacon_broadcast() {
+            return self.parse_beacon_broadcast();
+        }
+
         // 日本語: [名詞] に [値] を [動詞]
         if matches!(self.current(), Token::Noun(_)) && matches!(self.peek(1), Token::ParticleNi) {
             return self.parse_binary_op();
@@ -452,6 +737,13 @@ impl Parser {
              // println!("Debug: Noun+Wo detected. peek2={:?}", self.peek(2));
         }
 
+        // 日本語: [名詞] を 無効 に する / 有効 に する (逆順パターンより先に判定)
+        if matches!(self.current(), Token::Noun(_))
+           && matches!(self.peek(1), Token::ParticleWo)
+           && matches!(self.peek(2), Token::Noun(n) if n == "有効" || n == "無効") {
+             return self.parse_unary_or_async_op();
+        }
+
         // 日本語: [対象] を [ターゲット] に [動詞] (逆順: O を T に V)
         if matches!(self.current(), Token::Noun(_)) 
            && matches!(self.peek(1), Token::ParticleWo)
@@ -459,12 +751,31 @@ impl Parser {
              return self.parse_binary_op_reverse();
         }
 
+        // 日本語: 結果 を [式] とする
+        if matches!(self.current(), Token::KeywordResult) && matches!(self.peek(1), Token::ParticleWo) {
+            return self.parse_return();
+        }
+
         // 日本語: [値] を (並列で)? [動詞]
         if matches!(self.peek(1), Token::ParticleWo) {
             // Unary, Async, or Binary Op
             return self.parse_unary_or_async_op();
         }
         
+        // 日本語: 待つ [名詞]
+        if matches!(self.current(), Token::Verb(v) if v == "待つ") {
+            self.advance();
+            let target = self.current_to_expr()?;
+            return Ok(Statement::Await { target });
+        }
+
+        // 日本語: 取り消す [名詞]
+        if matches!(self.current(), Token::Verb(v) if v == "取り消す") {
+            self.advance();
+            let target = self.current_to_expr()?;
+            return Ok(Statement::Cancel { target });
+        }
+
         // 日本語: [数値] 回 繰り返す ... おわり
         if matches!(self.current(), Token::Number(_)) && matches!(self.peek(1), Token::KeywordTimes) {
             return self.parse_japanese_repeat();
@@ -503,6 +814,13 @@ impl Parser {
             }
         }
 
+        // [名詞] の [プロパティ] を [変数] に 束縛する
+        if matches!(self.current(), Token::Noun(_))
+           && matches!(self.peek(1), Token::ParticleNo)
+           && matches!(self.peek(6), Token::Verb(v) if v == "束縛する") {
+            return self.parse_bind();
+        }
+
         // Phase 11: [数値] 秒 かけて [プロパティ] を [値] にする / 深くする
         if matches!(self.current(), Token::Number(_)) && matches!(self.peek(1), Token::KeywordSeconds) {
              return self.parse_animate();
@@ -521,7 +839,7 @@ impl Parser {
             return self.parse_show_to_screen();
         }
 
-        Err(format!("Unexpected token: {:?}", self.current()))
+        Err(self.unsupported_keyword_or(format!("Unexpected token: {:?}", self.current())))
     }
 
     // === English SVO Parsers ===
@@ -530,7 +848,7 @@ impl Parser {
         // show X / print X
         let verb = match self.current() {
             Token::Verb(v) => v.clone(),
-            _ => return Err("Expected verb".to_string()),
+            _ => return Err(self.unsupported_keyword_or("Expected verb".to_string())),
         };
         self.advance(); // skip verb
         
@@ -571,7 +889,7 @@ impl Parser {
         // add X to Y / subtract X from Y
         let verb = match self.current() {
             Token::Verb(v) => v.clone(),
-            _ => return Err("Expected verb".to_string()),
+            _ => return Err(self.unsupported_keyword_or("Expected verb".to_string())),
         };
         self.advance(); // skip verb
         
@@ -656,7 +974,7 @@ impl Parser {
         self.advance(); // skip times
         
         // Parse body until "end"
-        let body = self.parse_block_until_end()?;
+        let body = self.parse_loop_body()?;
         
         Ok(Statement::RepeatStatement { count, body })
     }
@@ -667,6 +985,128 @@ impl Parser {
         // もし A と B の間に 絆 がある ならば ...
         self.advance(); // skip if / もし
         
+        let condition = self.parse_or_condition()?;
+        
+        // Expect "then" / "ならば"
+        if !matches!(self.current(), Token::KeywordThen) {
+            return Err("Expected 'then' or 'ならば'".to_string());
+        }
+        self.advance(); // skip then
+        
+        // Parse then block
+        let then_block = self.parse_block_until_end_or_else()?;
+        
+        // Check for else
+        let else_block = if matches!(self.current(), Token::KeywordElse) {
+            self.advance(); // skip else
+            if matches!(self.current(), Token::KeywordIf) {
+                // そうでなければもし ... : 残りの連鎖を入れ子の IfStatement として読む (おわり は共有)
+                Some(vec![self.parse_if_statement()?])
+            } else {
+                Some(self.parse_block_until_end()?)
+            }
+        } else {
+            None
+        };
+        
+        // "end" is consumed by parse_block_until_end
+        
+        Ok(Statement::IfStatement {
+            condition,
+            then_block,
+            else_block,
+        })
+    }
+    
+    fn parse_while_statement(&mut self) -> Result<Statement, String> {
+        // while X < 10 ... end
+        // X < 10 の間 繰り返す ... おわり
+        let english = matches!(self.current(), Token::KeywordWhile);
+        if english {
+            self.advance(); // skip while
+        }
+
+        let condition = self.parse_or_condition()?;
+
+        if !english {
+            // Expect "の間 繰り返す"
+            if !matches!(self.current(), Token::KeywordWhile) {
+                return Err(format!("Expected 'の間', got {:?}", self.current()));
+            }
+            self.advance(); // skip の間
+            if !matches!(self.current(), Token::Verb(v) if v == "繰り返す") {
+                return Err("Expected '繰り返す'".to_string());
+            }
+            self.advance(); // skip 繰り返す
+        }
+
+        let body = self.parse_loop_body()?;
+
+        Ok(Statement::WhileStatement { condition, body })
+    }
+
+    /// ループ本体を解析する (中では break / continue が使える)
+    fn parse_loop_body(&mut self) -> Result<Vec<Statement>, String> {
+        self.loop_depth += 1;
+        let body = self.parse_block_until_end();
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// ハンドラやルールの本体はループの外として解析する (外側のループは抜けられない)
+    fn outside_loop<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        let depth = std::mem::take(&mut self.loop_depth);
+        let result = parse(self);
+        self.loop_depth = depth;
+        result
+    }
+
+    /// 行内に の間 があれば日本語の条件付きループの見出し
+    fn is_japanese_while(&mut self) -> bool {
+        self.line_contains(&Token::KeywordWhile)
+    }
+
+    /// 現在位置から行末までに token があるか (先読みの範囲を超えて行末まで読み込む)
+    fn line_contains(&mut self, token: &Token) -> bool {
+        let mut index = self.pos - self.base;
+        loop {
+            if index == self.buffer.len() {
+                match self.source.next() {
+                    Some(next) => self.buffer.push_back(next),
+                    None => return false,
+                }
+            }
+            match &self.buffer[index] {
+                Token::Newline | Token::EOF => return false,
+                t if t == token => return true,
+                _ => index += 1,
+            }
+        }
+    }
+
+    /// もし A または B かつ C ならば (かつ は または より強く結合し、それぞれ左から順に結合)
+    fn parse_or_condition(&mut self) -> Result<Condition, String> {
+        let mut condition = self.parse_and_condition()?;
+        while matches!(self.current(), Token::KeywordOr) {
+            self.advance(); // skip または / or
+            let right = self.parse_and_condition()?;
+            condition = Condition::Or(Box::new(condition), Box::new(right));
+        }
+        Ok(condition)
+    }
+
+    fn parse_and_condition(&mut self) -> Result<Condition, String> {
+        let mut condition = self.parse_single_condition()?;
+        while matches!(self.current(), Token::KeywordAnd) {
+            self.advance(); // skip かつ / and
+            let right = self.parse_single_condition()?;
+            condition = Condition::And(Box::new(condition), Box::new(right));
+        }
+        Ok(condition)
+    }
+
+    /// もし の条件を1つ読む (かつ / または で区切られた各項)
+    fn parse_single_condition(&mut self) -> Result<Condition, String> {
         let condition = if matches!(self.peek(1), Token::ParticleTo) {
             // 日本語絆構文: [Expr] と [Expr] ... 絆 がある
             let left = self.parse_expression()?;
@@ -711,40 +1151,103 @@ impl Parser {
                     let right = self.current_to_expr()?;
                     Condition::LessThan(left, right)
                 }
-                Token::KeywordThen | Token::KeywordEnd | Token::Newline | Token::EOF => {
+                Token::ParticleWa => {
+                    // もし X は Y ではない ならば
+                    self.advance();
+                    let right = self.current_to_expr()?;
+                    if !matches!(self.current(), Token::KeywordNotEqual) {
+                        return Err(format!("Expected 'ではない', got {:?}", self.current()));
+                    }
+                    self.advance();
+                    Condition::NotEquals(left, right)
+                }
+                Token::KeywordThen | Token::KeywordAnd | Token::KeywordOr | Token::KeywordWhile | Token::KeywordEnd | Token::Newline | Token::EOF => {
                     // No operator: Truthy check (e.g. `if bond(A, B) then`)
                     Condition::Truthy(left)
                 }
                 _ => return Err(format!("Expected comparison operator, got {:?}", self.current())),
             }
         };
-        
-        // Expect "then" / "ならば"
-        if !matches!(self.current(), Token::KeywordThen) {
-            return Err("Expected 'then' or 'ならば'".to_string());
+        Ok(condition)
+    }
+
+    /// 字句解析はされるが、この位置では使えないキーワードなら説明的なエラーを、そうでなければ fallback を返す
+    fn unsupported_keyword_or(&self, fallback: String) -> String {
+        if matches!(self.current(), Token::KeywordRank) && matches!(self.peek(1), Token::Dot | Token::LParen) {
+            return fallback;
         }
-        self.advance(); // skip then
-        
-        // Parse then block
-        let then_block = self.parse_block_until_end_or_else()?;
-        
-        // Check for else
-        let else_block = if matches!(self.current(), Token::KeywordElse) {
-            self.advance(); // skip else
-            Some(self.parse_block_until_end()?)
+        keyword_text(self.current()).and_then(unsupported_keyword).unwrap_or(fallback)
+    }
+    
+    fn parse_when_statement(&mut self) -> Result<Statement, String> {
+        // [主語] が [値] 以上 の とき ... おわり
+        // 主語が「徳」のみの場合は自分の徳 (自分.徳) を指す
+        let left = if matches!(self.current(), Token::KeywordToku) {
+            self.advance();
+            Expr::PropertyAccess {
+                target: Box::new(Expr::Variable("自分".to_string())),
+                property: "徳".to_string(),
+            }
         } else {
-            None
+            self.parse_expression()?
         };
-        
-        // "end" is consumed by parse_block_until_end
-        
-        Ok(Statement::IfStatement {
-            condition,
-            then_block,
-            else_block,
-        })
+        self.advance(); // skip が
+
+        let right = self.parse_expression()?;
+        let condition = match self.current() {
+            Token::KeywordAtLeast => Condition::GreaterOrEqual(left, right),
+            Token::KeywordGreaterThan => Condition::GreaterThan(left, right),
+            Token::KeywordLessThan => Condition::LessThan(left, right),
+            Token::KeywordEquals => Condition::Equals(left, right),
+            _ => return Err(format!("Expected comparison (以上/より大きい/より小さい/と等しい), got {:?}", self.current())),
+        };
+        self.advance(); // skip comparison
+
+        if matches!(self.current(), Token::ParticleNo) {
+            self.advance();
+        }
+        if !matches!(self.current(), Token::KeywordWhen) {
+            return Err(format!("Expected 'とき', got {:?}", self.current()));
+        }
+        self.advance(); // skip とき
+
+        let then_block = self.parse_block_until_end()?;
+        Ok(Statement::IfStatement { condition, then_block, else_block: None })
     }
-    
+
+    fn is_beacon_broadcast(&self) -> bool {
+        let is_beacon = |t: &Token| matches!(t, Token::Noun(n) if n == "ビーコン");
+        let is_send = |t: &Token| matches!(t, Token::Verb(v) if v == "発信する");
+        (is_beacon(self.current()) && matches!(self.peek(1), Token::ParticleWo) && is_send(self.peek(2)))
+            || (matches!(self.current(), Token::Noun(_) | Token::String(_))
+                && matches!(self.peek(1), Token::ParticleNo)
+                && is_beacon(self.peek(2))
+                && matches!(self.peek(3), Token::ParticleWo)
+                && is_send(self.peek(4)))
+    }
+
+    fn parse_beacon_broadcast(&mut self) -> Result<Statement, String> {
+        // [種類] の ビーコン を 発信する (種類省略時は「暇」)
+        let beacon_type = match self.current().clone() {
+            Token::Noun(n) if n != "ビーコン" => {
+                self.advance(); // skip 種類
+                self.advance(); // skip の
+                n
+            }
+            Token::String(s) => {
+                self.advance();
+                self.advance();
+                s
+            }
+            _ => "暇".to_string(),
+        };
+        self.advance(); // skip ビーコン
+        self.advance(); // skip を
+        self.advance(); // skip 発信する
+
+        Ok(Statement::BeaconBroadcast { beacon_type, duration: None, payload: Vec::new() })
+    }
+
     fn parse_japanese_repeat(&mut self) -> Result<Statement, String> {
         // N 回 繰り返す ... おわり
         let count = self.current_to_expr()?;
@@ -762,7 +1265,7 @@ impl Parser {
         self.advance(); // skip 繰り返す
         
         // Parse body until "おわり"
-        let body = self.parse_block_until_end()?;
+        let body = self.parse_loop_body()?;
         
         Ok(Statement::RepeatStatement { count, body })
     }
@@ -774,6 +1277,22 @@ impl Parser {
         matches!(token, Token::Noun(n) if n == "縦並び" || n == "横並び")
     }
     
+    fn parse_save_screen(&mut self) -> Result<Statement, String> {
+        // 画面 を [パス] に 保存する
+        self.advance(); // skip 画面
+        self.advance(); // skip を
+        let path = self.parse_expression()?;
+        if !matches!(self.current(), Token::ParticleNi) {
+            return Err(format!("Expected 'に', got {:?}", self.current()));
+        }
+        self.advance(); // skip に
+        if !matches!(self.current(), Token::Verb(v) if v == "保存する") {
+            return Err(format!("Expected '保存する', got {:?}", self.current()));
+        }
+        self.advance(); // skip 保存する
+        Ok(Statement::SaveScreen { path })
+    }
+
     /// Screen出力を先読みでチェック
     fn look_for_screen_target(&self) -> bool {
         // show X to Screen パターンを検出
@@ -802,11 +1321,12 @@ impl Parser {
         } else {
             return Err("Expected 'click'".to_string());
         };
+        let interval = self.parse_event_interval();
         
         // Parse body until "end"
-        let body = self.parse_block_until_end()?;
+        let body = self.outside_loop(Self::parse_block_until_end)?;
         
-        Ok(Statement::EventHandler { target: Expr::Variable(target), event, body })
+        Ok(Statement::EventHandler { target: Expr::Variable(target), event, body, interval })
     }
     
     fn parse_show_to_screen(&mut self) -> Result<Statement, String> {
@@ -908,6 +1428,58 @@ impl Parser {
         Ok(Statement::Block { target: Expr::Variable(target), body })
     }
 
+    fn parse_return(&mut self) -> Result<Statement, String> {
+        self.advance(); // skip 結果
+        self.advance(); // skip を
+        let value = self.parse_expression()?;
+        // "とする" は と (助詞) + する に分かれて字句解析される
+        if matches!(self.current(), Token::Verb(v) if v == "とする") {
+            self.advance();
+        } else if matches!(self.current(), Token::ParticleTo)
+            && matches!(self.peek(1), Token::Verb(v) | Token::Noun(v) if v == "する") {
+            self.advance();
+            self.advance();
+        } else {
+            return Err("Expected 'とする'".to_string());
+        }
+        Ok(Statement::ReturnStatement { value })
+    }
+
+    fn parse_bind(&mut self) -> Result<Statement, String> {
+        // [ラベル] の [プロパティ] を [変数] に 束縛する
+        let target = match self.current() {
+            Token::Noun(n) => Expr::Variable(n.clone()),
+            _ => return Err("Expected component name".to_string()),
+        };
+        self.advance();
+        self.advance(); // skip の
+
+        let property = match self.current() {
+            Token::Noun(n) => n.clone(),
+            _ => return Err("Expected property noun".to_string()),
+        };
+        self.advance();
+
+        if !matches!(self.current(), Token::ParticleWo) {
+            return Err("Expected 'を'".to_string());
+        }
+        self.advance();
+
+        let variable = match self.current() {
+            Token::Noun(n) => n.clone(),
+            _ => return Err("Expected variable name".to_string()),
+        };
+        self.advance();
+
+        if !matches!(self.current(), Token::ParticleNi) {
+            return Err("Expected 'に'".to_string());
+        }
+        self.advance();
+        self.advance(); // skip 束縛する
+
+        Ok(Statement::Bind { target, property, variable })
+    }
+
     fn parse_layout(&mut self) -> Result<Statement, String> {
         // [名詞] を [方向] に 置く
         let target = match self.current() {
@@ -1038,12 +1610,13 @@ impl Parser {
         }
         self.advance();
 
-        let body = self.parse_block_until_end()?;
+        let body = self.outside_loop(Self::parse_block_until_end)?;
 
         Ok(Statement::EventHandler { 
             target: Expr::Variable("self".to_string()), // Implicit target (current component)
             event: "hover".to_string(), 
-            body 
+            body,
+            interval: None,
         })
     }
 
@@ -1063,10 +1636,11 @@ impl Parser {
             _ => return Err("Expected event type (押したとき/動かしたとき)".to_string()),
         };
         self.advance();
+        let interval = self.parse_event_interval();
         
-        let body = self.parse_block_until_end()?;
+        let body = self.outside_loop(Self::parse_block_until_end)?;
         
-        Ok(Statement::EventHandler { target, event, body })
+        Ok(Statement::EventHandler { target, event, body, interval })
     }
 
     // === Japanese SOV Parsers (existing) ===
@@ -1087,9 +1661,28 @@ impl Parser {
                 self.advance();
                 Ok(Statement::Assignment { target, value: expr })
             }
+            Token::KeywordNearer => self.parse_spatial_search(target, expr),
+            Token::KeywordNotEqual => {
+                // [値] ではない
+                self.advance();
+                Ok(Statement::Assert { condition: Condition::NotEquals(target, expr) })
+            }
             Token::ParticleWo => {
-                // [パス] を 読み込む OR [値] を 翻訳する/要約する
+                // [パス] を 読み込む OR [値] を (並列で)? 翻訳する/要約する
                 self.advance();
+                if matches!(self.current(), Token::Adverb(a) if a == "並列で") {
+                    self.advance();
+                    if !matches!(self.current(), Token::Verb(v) if v == "翻訳する" || v == "要約する" || v == "想像する") {
+                        return Err("Expected AI verb".to_string());
+                    }
+                    let verb = match self.current() {
+                        Token::Verb(v) => v.clone(),
+                        _ => unreachable!(),
+                    };
+                    self.advance();
+                    let op = Statement::AiOp { result: target, input: expr, verb, options: None };
+                    return Ok(Statement::Parallel { inner: Box::new(op) });
+                }
                 match self.current() {
                     Token::Verb(v) if v == "読み込む" => {
                         self.advance();
@@ -1178,7 +1771,9 @@ impl Parser {
                       Err("Expected 'だ' after component definition".to_string())
                  }
             }
-            _ => Err("Expected 'だ', 'を', or 'な' (or Component Name)".to_string())
+            // [名詞] は 待つ [値] (動詞で終わるため だ は省略可)
+            _ if matches!(expr, Expr::Await(_)) => Ok(Statement::Assignment { target, value: expr }),
+            _ => Err(self.unsupported_keyword_or("Expected 'だ', 'を', or 'な' (or Component Name)".to_string())),
         }
     }
 
@@ -1197,13 +1792,23 @@ impl Parser {
         }
         self.advance();
 
+        let is_async = matches!(self.current(), Token::Adverb(a) if a == "並列で");
+        if is_async {
+            self.advance();
+        }
+
         let verb = match self.current() {
             Token::Verb(v) => v.clone(),
-            _ => return Err("Expected verb".to_string()),
+            _ => return Err(self.unsupported_keyword_or("Expected verb".to_string())),
         };
         self.advance();
 
-        Ok(Statement::BinaryOp { target, operand, verb })
+        let op = Statement::BinaryOp { target, operand, verb };
+        if is_async {
+            Ok(Statement::Parallel { inner: Box::new(op) })
+        } else {
+            Ok(op)
+        }
     }
 
     fn parse_binary_op_reverse(&mut self) -> Result<Statement, String> {
@@ -1227,7 +1832,7 @@ impl Parser {
         // Get verb
         let verb = match self.current() {
             Token::Verb(v) => v.clone(),
-            _ => return Err("Expected verb".to_string()),
+            _ => return Err(self.unsupported_keyword_or("Expected verb".to_string())),
         };
         self.advance();
         
@@ -1251,6 +1856,22 @@ impl Parser {
         if is_async {
             self.advance();
         }
+
+        // [X] を 無効 に する / 有効 に する
+        if let Some(enabled) = self.parse_enabled_change() {
+            return Ok(Statement::SetEnabled { target: operand, enabled });
+        }
+
+        // [値] を "チャンネル" に 記録する
+        if let (Token::String(channel), Token::ParticleNi, Token::Verb(v)) = (self.current(), self.peek(1), self.peek(2)) {
+            if v == "記録する" {
+                let channel = channel.clone();
+                self.advance(); // skip チャンネル
+                self.advance(); // skip に
+                self.advance(); // skip 記録する
+                return Ok(Statement::Log { value: operand, channel });
+            }
+        }
         
         // Check for Target "画面 に" or "画面 の 中央 に" or "[Noun] に"
         if (matches!(self.current(), Token::ScreenNoun) || matches!(self.current(), Token::Noun(_))) {
@@ -1295,19 +1916,46 @@ impl Parser {
 
         let verb = match self.current() {
             Token::Verb(v) => v.clone(),
-            _ => return Err("Expected verb".to_string()),
+            _ => return Err(self.unsupported_keyword_or("Expected verb".to_string())),
         };
         self.advance();
         
         if let Some(tgt) = target {
              let target_expr = Expr::Variable(tgt);
              Ok(Statement::BinaryOp { target: target_expr, operand, verb })
+        } else if verb == "待つ" {
+            Ok(Statement::Await { target: operand })
+        } else if verb == "取り消す" {
+            Ok(Statement::Cancel { target: operand })
+        } else if verb == "隠す" || verb == "見せる" {
+            Ok(Statement::SetVisibility { target: operand, visible: verb == "見せる" })
         } else if is_async {
             Ok(Statement::AsyncOp { operand, verb })
         } else {
             Ok(Statement::UnaryOp { operand, verb })
         }
     }
+    /// 無効/有効 + (に する | にする) を読み取る。該当しなければ何も消費しない
+    fn parse_enabled_change(&mut self) -> Option<bool> {
+        let enabled = match self.current() {
+            Token::Noun(n) if n == "有効" => true,
+            Token::Noun(n) if n == "無効" => false,
+            _ => return None,
+        };
+        let is_suru = |t: &Token| matches!(t, Token::Verb(v) | Token::Noun(v) if v == "する");
+        if matches!(self.peek(1), Token::KeywordChange) {
+            self.advance();
+            self.advance();
+        } else if matches!(self.peek(1), Token::ParticleNi) && is_suru(self.peek(2)) {
+            self.advance();
+            self.advance();
+            self.advance();
+        } else {
+            return None;
+        }
+        Some(enabled)
+    }
+
     fn parse_timed_statement(&mut self) -> Result<Statement, String> {
         // [Time] 秒 後 に ... / [Time] 秒 かけて ...
         let duration = self.current_to_expr()?;
@@ -1392,7 +2040,7 @@ impl Parser {
                 Token::KeywordAccrue | Token::KeywordIncrease => "増やす".to_string(),
                 Token::KeywordDecrease => "減らす".to_string(),
                 Token::KeywordDeepen => "深くする".to_string(),
-                _ => return Err("Expected verb".to_string()),
+                _ => return Err(self.unsupported_keyword_or("Expected verb".to_string())),
             };
             self.advance();
             
@@ -1425,7 +2073,7 @@ impl Parser {
                  Token::KeywordDecrease => "減らす".to_string(),
                  Token::KeywordUpdate => "更新する".to_string(),
                  Token::KeywordDeepen => "深くする".to_string(),
-                 _ => return Err("Expected verb".to_string()),
+                 _ => return Err(self.unsupported_keyword_or("Expected verb".to_string())),
              };
              self.advance();
              
@@ -1445,7 +2093,7 @@ impl Parser {
              return Ok(Statement::ActionCall { name, args });
         }
         
-        Err(format!("Unexpected token after expression: {:?}", self.current()))
+        Err(self.unsupported_keyword_or(format!("Unexpected token after expression: {:?}", self.current())))
     }
     
     // === AGN 2.0 Parsers ===
@@ -1462,11 +2110,11 @@ impl Parser {
         // Optional {
         if matches!(self.current(), Token::LBrace) {
             self.advance();
-            let body = self.parse_block_until_brace_end()?;
+            let body = self.outside_loop(Self::parse_block_until_brace_end)?;
             // RBrace consumed by helper
             Ok(Statement::RuleDefinition { name, body })
         } else {
-            let body = self.parse_block_until_end()?;
+            let body = self.outside_loop(Self::parse_block_until_end)?;
             Ok(Statement::RuleDefinition { name, body })
         }
     }
@@ -1505,12 +2153,14 @@ impl Parser {
         }
         
         // Optional {
-        let body = if matches!(self.current(), Token::LBrace) {
-            self.advance();
-            self.parse_block_until_brace_end()?
-        } else {
-            self.parse_block_until_end()?
-        };
+        let body = self.outside_loop(|p| {
+            if matches!(p.current(), Token::LBrace) {
+                p.advance();
+                p.parse_block_until_brace_end()
+            } else {
+                p.parse_block_until_end()
+            }
+        })?;
         
         Ok(Statement::ActionDefinition { name, params, body })
     }
@@ -1566,7 +2216,7 @@ impl Parser {
         // Block
         let body = if matches!(self.current(), Token::LBrace) {
             self.advance();
-            self.parse_block_until_brace_end()?
+            self.outside_loop(Self::parse_block_until_brace_end)?
         } else {
             return Err("Expected '{' for event body".to_string());
         };
@@ -1596,11 +2246,125 @@ impl Parser {
              return Err("Expected event name (click, drag, etc.)".to_string());
         };
         self.advance();
+        let interval = self.parse_event_interval();
         
         // Block
-        let body = self.parse_block_until_end()?;
+        let body = self.outside_loop(Self::parse_block_until_end)?;
         
-        Ok(Statement::EventHandler { target, event, body })
+        Ok(Statement::EventHandler { target, event, body, interval })
+    }
+
+    /// 空間検索: [結果] は [距離] より近い 人 (で [項目] が [値] な 人)* を ([並び順] に)? ([件数] 人)? 探す
+    fn parse_spatial_search(&mut self, result: Expr, max_distance: Expr) -> Result<Statement, String> {
+        self.advance(); // skip より近い
+        if !matches!(self.current(), Token::KeywordPerson) {
+            return Err("Expected '人'".to_string());
+        }
+        self.advance();
+
+        let mut filters = Vec::new();
+        while matches!(self.current(), Token::ParticleDe) {
+            self.advance(); // skip で
+            let field = match self.current() {
+                Token::Noun(n) => n.clone(),
+                Token::KeywordToku => "徳".to_string(),
+                _ => return Err("Expected filter field".to_string()),
+            };
+            self.advance();
+            if !matches!(self.current(), Token::ParticleGa) {
+                return Err("Expected 'が'".to_string());
+            }
+            self.advance();
+
+            // 比較フィルター: 徳 が 500 以上 の 人
+            if matches!(self.peek(1), Token::KeywordAtLeast | Token::KeywordGreaterThan | Token::KeywordLessThan) {
+                let threshold = self.parse_primary()?;
+                let left = Expr::Variable(field.clone());
+                let condition = match self.current() {
+                    Token::KeywordAtLeast => Condition::GreaterOrEqual(left, threshold),
+                    Token::KeywordGreaterThan => Condition::GreaterThan(left, threshold),
+                    _ => Condition::LessThan(left, threshold),
+                };
+                self.advance();
+                if !matches!(self.current(), Token::ParticleNo) || !matches!(self.peek(1), Token::KeywordPerson) {
+                    return Err("Expected 'の 人'".to_string());
+                }
+                self.advance();
+                self.advance();
+                filters.push(SpatialFilter { field, condition });
+                continue;
+            }
+
+            let value = match self.current() {
+                Token::KeywordIdle => "暇".to_string(),
+                Token::Noun(n) => n.clone(),
+                _ => return Err("Expected filter value".to_string()),
+            };
+            self.advance();
+            if !matches!(self.current(), Token::ParticleNa) || !matches!(self.peek(1), Token::KeywordPerson) {
+                return Err("Expected 'な 人'".to_string());
+            }
+            self.advance();
+            self.advance();
+            filters.push(SpatialFilter {
+                field: field.clone(),
+                condition: Condition::Equals(Expr::Variable(field), Expr::String(value)),
+            });
+        }
+
+        if !matches!(self.current(), Token::ParticleWo) {
+            return Err("Expected 'を'".to_string());
+        }
+        self.advance();
+
+        let order = match self.current() {
+            Token::KeywordNearestFirst => Some(SearchOrder::Nearest),
+            Token::KeywordTokuFirst => Some(SearchOrder::HighestToku),
+            _ => None,
+        };
+        if order.is_some() {
+            self.advance();
+            if !matches!(self.current(), Token::ParticleNi) {
+                return Err("Expected 'に'".to_string());
+            }
+            self.advance();
+        }
+
+        let limit = if matches!(self.current(), Token::Number(_) | Token::Noun(_))
+            && matches!(self.peek(1), Token::KeywordPerson)
+        {
+            let limit = self.parse_primary()?;
+            self.advance(); // skip 人
+            Some(limit)
+        } else {
+            None
+        };
+
+        if !matches!(self.current(), Token::Verb(v) if v == "探す") {
+            return Err("Expected '探す'".to_string());
+        }
+        self.advance();
+
+        Ok(Statement::SpatialSearch { result, max_distance, filters, order, limit })
+    }
+
+    /// イベントの間引き指定: [数値] 秒 間隔 で (分 も可)。秒単位で返す
+    fn parse_event_interval(&mut self) -> Option<f64> {
+        let secs = match (self.current(), self.peek(1)) {
+            (Token::Number(n), Token::KeywordSeconds) => *n,
+            (Token::Duration { value, unit }, _) if unit == "分" => *value * 60.0,
+            _ => return None,
+        };
+        let unit_len = if matches!(self.current(), Token::Number(_)) { 2 } else { 1 };
+        if !matches!(self.peek(unit_len), Token::Noun(n) if n == "間隔")
+            || !matches!(self.peek(unit_len + 1), Token::ParticleDe)
+        {
+            return None;
+        }
+        for _ in 0..unit_len + 2 {
+            self.advance();
+        }
+        Some(secs)
     }
     
     fn parse_block_until_brace_end(&mut self) -> Result<Vec<Statement>, String> {
@@ -1763,6 +2527,68 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_spatial_search() {
+        let mut lexer = Lexer::new("近所 は 10m より近い 人 で 状態 が 暇 な 人 を 徳の高い順 に 探す\n周囲 は 5m より近い 人 を 探す");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        let stmts: Vec<&Statement> = program.statements.iter().map(|s| s.unspanned()).collect();
+
+        match stmts[0] {
+            Statement::SpatialSearch { max_distance, filters, order, .. } => {
+                assert!(matches!(max_distance, Expr::Distance { value, .. } if *value == 10.0));
+                assert_eq!(filters.len(), 1);
+                assert_eq!(filters[0].field, "状態");
+                assert_eq!(*order, Some(SearchOrder::HighestToku));
+            }
+            other => panic!("Expected spatial search, got {:?}", other),
+        }
+        assert!(matches!(stmts[1], Statement::SpatialSearch { order: None, limit: None, .. }));
+
+        let mut lexer = Lexer::new("近所 は 10m より近い 人 で 徳 が 500 以上 の 人 を 探す");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        match program.statements[0].unspanned() {
+            Statement::SpatialSearch { filters, .. } => {
+                assert_eq!(filters[0].field, "徳");
+                assert!(matches!(&filters[0].condition, Condition::GreaterOrEqual(_, Expr::Number(n)) if *n == 500.0));
+            }
+            other => panic!("Expected spatial search, got {:?}", other),
+        }
+
+        let mut lexer = Lexer::new("近所 は 10m より近い 人 を 近い順 に 3人 探す");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        assert!(matches!(
+            program.statements[0].unspanned(),
+            Statement::SpatialSearch { order: Some(SearchOrder::Nearest), limit: Some(Expr::Number(n)), .. } if *n == 3.0
+        ));
+    }
+
+    #[test]
+    fn test_parse_parallel_and_await() {
+        let mut lexer = Lexer::new("要旨 は テキスト を 並列で 要約する\nX に 5 を 並列で 足す\n待つ 要旨\nX を 待つ");
+        let tokens = lexer.tokenize();
+        let mut parser = Parser::new(tokens);
+        let program = parser.parse().unwrap();
+
+        let stmts: Vec<&Statement> = program.statements.iter().map(|s| s.unspanned()).collect();
+        assert_eq!(stmts.len(), 4);
+        match stmts[0] {
+            Statement::Parallel { inner } => assert!(matches!(inner.as_ref(), Statement::AiOp { verb, .. } if verb == "要約する")),
+            other => panic!("Expected parallel AI op, got {:?}", other),
+        }
+        match stmts[1] {
+            Statement::Parallel { inner } => assert!(matches!(inner.as_ref(), Statement::BinaryOp { verb, .. } if verb == "足す")),
+            other => panic!("Expected parallel binary op, got {:?}", other),
+        }
+        assert!(matches!(stmts[2], Statement::Await { target: Expr::Variable(name) } if name == "要旨"));
+        assert!(matches!(stmts[3], Statement::Await { target: Expr::Variable(name) } if name == "X"));
+
+        let mut lexer = Lexer::new("取り消す 要旨\nX を 取り消す");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        let stmts: Vec<&Statement> = program.statements.iter().map(|s| s.unspanned()).collect();
+        assert!(matches!(stmts[0], Statement::Cancel { target: Expr::Variable(name) } if name == "要旨"));
+        assert!(matches!(stmts[1], Statement::Cancel { target: Expr::Variable(name) } if name == "X"));
+    }
+
     // === Phase 4 Tests ===
     
     #[test]
@@ -1874,4 +2700,269 @@ mod tests {
             _ => panic!("Expected assignment"),
         }
     }
+
+    #[test]
+    fn test_program_serializes_to_json() {
+        let mut lexer = Lexer::new("X は 1 だ\nX を 表示する");
+        let mut parser = Parser::new(lexer.tokenize());
+        let program = parser.parse().unwrap();
+
+        let json = serde_json::to_value(&program).unwrap();
+        let kinds: Vec<&str> = json["statements"].as_array().unwrap().iter()
+            .map(|stmt| stmt.as_object().unwrap().keys().next().unwrap().as_str())
+            .collect();
+        assert_eq!(kinds, vec!["Assignment", "UnaryOp"]);
+        assert_eq!(json["statements"][0]["Assignment"]["target"]["Variable"], "X");
+    }
+
+    #[test]
+    fn test_parse_with_spans() {
+        let code = "X は 1 だ\nアクション 二倍(N)\n  Y は N だ\nおわり";
+        let mut lexer = Lexer::new(code);
+        let (tokens, spans) = lexer.tokenize_with_spans();
+        let mut parser = Parser::with_spans(tokens, spans);
+        let program = parser.parse().unwrap();
+
+        assert_eq!(program.statements.len(), 2);
+        match &program.statements[1] {
+            Statement::Spanned { span, inner } => {
+                assert_eq!(span.line, 2);
+                match inner.as_ref() {
+                    Statement::ActionDefinition { body, .. } => match &body[0] {
+                        Statement::Spanned { span, .. } => assert_eq!(span.line, 3),
+                        other => panic!("Expected spanned body, got {:?}", other),
+                    },
+                    other => panic!("Expected ActionDefinition, got {:?}", other),
+                }
+            }
+            other => panic!("Expected Spanned, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_conditional_beacon() {
+        let code = "徳 が 500 以上 の とき\n  ビーコン を 発信する\nおわり";
+        let mut lexer = Lexer::new(code);
+        let mut parser = Parser::new(lexer.tokenize());
+        let program = parser.parse().unwrap();
+
+        match &program.statements[0] {
+            Statement::IfStatement { condition: Condition::GreaterOrEqual(..), then_block, else_block: None } => {
+                match &then_block[0] {
+                    Statement::BeaconBroadcast { beacon_type, .. } => assert_eq!(beacon_type, "暇"),
+                    other => panic!("Expected BeaconBroadcast, got {:?}", other),
+                }
+            }
+            other => panic!("Expected IfStatement, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_error_locale() {
+        let parse_with = |locale: Locale| {
+            let mut lexer = Lexer::new("X を");
+            let mut parser = Parser::new(lexer.tokenize());
+            parser.set_locale(locale);
+            parser.parse().unwrap_err()
+        };
+        assert_eq!(parse_with(Locale::En), "Expected verb");
+        assert_eq!(parse_with(Locale::Ja), "動詞 が必要です");
+    }
+
+    #[test]
+    fn test_parse_copula_and_negation() {
+        let code = "X は 10 だった\nY は 20 です\nX は 5 ではない\nもし X は 5 ではない ならば\n  X を 表示する\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        assert!(matches!(&program.statements[0], Statement::Assignment { value: Expr::Number(n), .. } if *n == 10.0));
+        assert!(matches!(&program.statements[1], Statement::Assignment { value: Expr::Number(n), .. } if *n == 20.0));
+        assert!(matches!(
+            &program.statements[2],
+            Statement::Assert { condition: Condition::NotEquals(Expr::Variable(x), Expr::Number(n)) } if x == "X" && *n == 5.0
+        ));
+        assert!(matches!(
+            &program.statements[3],
+            Statement::IfStatement { condition: Condition::NotEquals(..), .. }
+        ));
+    }
+
+    #[test]
+    fn test_keyword_as_variable_diagnostic() {
+        let mut lexer = Lexer::new("徳 は 10 だ");
+        let err = Parser::new(lexer.tokenize()).parse().unwrap_err();
+        assert!(err.contains("Reserved keyword '徳'"), "{}", err);
+
+        let mut lexer = Lexer::new("徳 は 10 だ");
+        let mut parser = Parser::new(lexer.tokenize());
+        parser.set_locale(Locale::Ja);
+        assert_eq!(parser.parse().unwrap_err(), "'徳' は予約語のため変数名に使えません。別の名前を使うか `徳` のように囲んでください");
+
+        // バッククォートで囲めば識別子として代入できる
+        let mut lexer = Lexer::new("`徳` は 10 だ");
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+        assert!(matches!(&program.statements[0], Statement::Assignment { target: Expr::Variable(name), .. } if name == "徳"));
+
+        // キーワードで始まる複合名詞は予約語ではない
+        let program = Parser::new(Lexer::new("上部 は 1 だ\n徳を送る は 2 だ").tokenize()).parse().unwrap();
+        assert!(matches!(&program.statements[0], Statement::Assignment { target: Expr::Variable(name), .. } if name == "上部"));
+        assert!(matches!(&program.statements[1], Statement::Assignment { target: Expr::Variable(name), .. } if name == "徳を送る"));
+    }
+
+    #[test]
+    fn test_parse_multiline_call_arguments() {
+        let code = "Y は Mix(\n    1,\n    \"a\",\n    Z\n) だ\nY を 表示する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        assert_eq!(program.statements.len(), 2);
+        assert!(matches!(
+            &program.statements[0],
+            Statement::Assignment { value: Expr::Call { name, args }, .. } if name == "Mix" && args.len() == 3
+        ));
+    }
+
+    #[test]
+    fn test_parse_and_condition() {
+        let code = "もし A > 1 かつ B と等しい 2 and C ならば\n  A を 表示する\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let Statement::IfStatement { condition: Condition::And(left, right), .. } = &program.statements[0] else {
+            panic!("expected an and-condition, got {:?}", program.statements[0]);
+        };
+        assert!(matches!(right.as_ref(), Condition::Truthy(Expr::Variable(c)) if c == "C"));
+        assert!(matches!(
+            left.as_ref(),
+            Condition::And(a, b) if matches!(a.as_ref(), Condition::GreaterThan(..)) && matches!(b.as_ref(), Condition::Equals(..))
+        ));
+    }
+
+    #[test]
+    fn test_parse_or_condition_and_bool_literals() {
+        let code = "もし A と等しい 真 または bond(X, Y) かつ B と等しい false ならば\n  A を 表示する\nおわり";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        // かつ は または より強く結合する
+        let Statement::IfStatement { condition: Condition::Or(left, right), .. } = &program.statements[0] else {
+            panic!("expected an or-condition, got {:?}", program.statements[0]);
+        };
+        assert!(matches!(left.as_ref(), Condition::Equals(_, Expr::Bool(true))));
+        assert!(matches!(
+            right.as_ref(),
+            Condition::And(a, b) if matches!(a.as_ref(), Condition::Truthy(Expr::Bond(..)))
+                && matches!(b.as_ref(), Condition::Equals(_, Expr::Bool(false)))
+        ));
+    }
+
+    #[test]
+    fn test_parse_else_if_chain() {
+        // 2 分岐: もし ... そうでなければもし ... おわり
+        let code = "もし A と等しい 1 ならば\n  A を 表示する\nそうでなければもし A と等しい 2 ならば\n  B を 表示する\nおわり\nC を 表示する";
+        let program = Parser::new(Lexer::new(code)).parse().unwrap();
+        assert_eq!(program.statements.len(), 2);
+        let Statement::IfStatement { else_block: Some(else_block), .. } = &program.statements[0] else {
+            panic!("expected an if with else, got {:?}", program.statements[0]);
+        };
+        assert!(matches!(
+            else_block.as_slice(),
+            [Statement::IfStatement { condition: Condition::Equals(_, Expr::Number(n)), else_block: None, .. }] if *n == 2.0
+        ));
+
+        // 3 分岐 + 最後の そうでなければ
+        let code = "if A equals 1 then\n  show A\nelse if A equals 2 then\n  show B\nそうでなければ もし A と等しい 3 ならば\n  show C\nelse\n  show D\nend\nshow E";
+        let program = Parser::new(Lexer::new(code)).parse().unwrap();
+        assert_eq!(program.statements.len(), 2);
+        let Statement::IfStatement { else_block: Some(second), .. } = &program.statements[0] else {
+            panic!("expected an if with else, got {:?}", program.statements[0]);
+        };
+        let [Statement::IfStatement { else_block: Some(third), .. }] = second.as_slice() else {
+            panic!("expected a nested else-if, got {:?}", second);
+        };
+        let [Statement::IfStatement { then_block, else_block: Some(last), .. }] = third.as_slice() else {
+            panic!("expected a nested else-if, got {:?}", third);
+        };
+        assert_eq!(then_block.len(), 1);
+        assert!(matches!(last.as_slice(), [Statement::UnaryOp { .. }]));
+    }
+
+    #[test]
+    fn test_parse_while_statement() {
+        let code = "X は 10 ではない の間 繰り返す\n  X に 1 を 足す\nおわり\nwhile X < 20\n  add 1 to X\nend";
+        let program = Parser::new(Lexer::new(code)).parse().unwrap();
+        assert_eq!(program.statements.len(), 2);
+        assert!(matches!(
+            &program.statements[0],
+            Statement::WhileStatement { condition: Condition::NotEquals(Expr::Variable(x), _), body } if x == "X" && body.len() == 1
+        ));
+        assert!(matches!(
+            &program.statements[1],
+            Statement::WhileStatement { condition: Condition::LessThan(..), body } if body.len() == 1
+        ));
+
+        // 先読みの範囲より長い条件でも の間 を見つける
+        let condition = "A と等しい 0 かつ B と等しい 0 かつ C と等しい 0 かつ D と等しい 0 かつ B と等しい 0";
+        let japanese = Parser::new(Lexer::new(&format!("{} の間 繰り返す\n  A に 1 を 足す\nおわり", condition))).parse().unwrap();
+        let english = Parser::new(Lexer::new(&format!("while {}\n  A に 1 を 足す\nend", condition))).parse().unwrap();
+        assert!(matches!(&japanese.statements[0], Statement::WhileStatement { .. }));
+        assert_eq!(format!("{:?}", japanese), format!("{:?}", english));
+    }
+
+    #[test]
+    fn test_parse_break_and_continue() {
+        let code = "repeat 10 times\n  もし C と等しい 3 ならば\n    やめる\n  おわり\n  continue\nend";
+        let program = Parser::new(Lexer::new(code)).parse().unwrap();
+        let Statement::RepeatStatement { body, .. } = &program.statements[0] else {
+            panic!("expected a repeat, got {:?}", program.statements[0]);
+        };
+        assert!(matches!(&body[0], Statement::IfStatement { then_block, .. } if matches!(then_block.as_slice(), [Statement::Break])));
+        assert!(matches!(&body[1], Statement::Continue));
+    }
+
+    #[test]
+    fn test_break_outside_loop_is_rejected() {
+        let parse_with = |code: &str, locale: Locale| {
+            let mut parser = Parser::new(Lexer::new(code));
+            parser.set_locale(locale);
+            parser.parse()
+        };
+
+        let err = parse_with("X を 表示する\nbreak", Locale::En).unwrap_err();
+        assert!(err.starts_with("Keyword 'break' is not supported here"), "{}", err);
+        let err = parse_with("もし X と等しい 1 ならば\n  つぎへ\nおわり", Locale::Ja).unwrap_err();
+        assert_eq!(err, "'つぎへ' はここでは使えません: ループの中でだけ使えます (例: '3 回 繰り返す ... つぎへ ... おわり')");
+
+        // ループ内のルール本体からは外側のループを抜けられない
+        let err = parse_with("3 回 繰り返す\n  ルール R\n    やめる\n  おわり\nおわり", Locale::Ja).unwrap_err();
+        assert!(err.starts_with("'やめる' はここでは使えません"), "{}", err);
+
+        assert!(parse_with("X < 3 の間 繰り返す\n  やめる\nおわり", Locale::Ja).is_ok());
+    }
+
+    #[test]
+    fn test_parse_from_token_iterator() {
+        let code = "A は 1 だ\nもし A と等しい 1 ならば\n  A を 表示する\nおわり\n3 回 繰り返す\n  A を 表示する\nおわり";
+
+        // Lexer をそのまま渡しても、一括で読んだトークン列と同じ結果になる
+        let streamed = Parser::new(Lexer::new(code)).parse().unwrap();
+        let collected = Parser::new(Lexer::new(code).tokenize()).parse().unwrap();
+        assert_eq!(streamed.statements.len(), 3);
+        assert_eq!(format!("{:?}", streamed), format!("{:?}", collected));
+    }
+
+    #[test]
+    fn test_unsupported_keyword_errors() {
+        let parse_err = |code: &str| Parser::new(Lexer::new(code).tokenize()).parse().unwrap_err();
+
+        assert!(parse_err("かつ X を 表示する").starts_with("Keyword 'かつ' is not supported here"));
+        assert!(parse_err("X は 1 かつ 2 だ").starts_with("Keyword 'かつ' is not supported here"));
+        assert!(parse_err("Badge を X に 付ける").starts_with("Keyword '付ける' is not supported here"));
+        assert!(parse_err("ランク を 表示する").starts_with("Keyword 'ランク' is not supported here"));
+        assert!(parse_err("Near は 5m より遠い").starts_with("Keyword 'より遠い' is not supported here"));
+
+        let mut parser = Parser::new(Lexer::new("Badge を X に 付ける").tokenize());
+        parser.set_locale(Locale::Ja);
+        assert_eq!(parser.parse().unwrap_err(), "'付ける' はここでは使えません: 予約語ですがまだ使えません");
+    }
 }
@@ -4,10 +4,14 @@
 use std::collections::HashMap;
 
 /// 値の型
-#[derive(Debug, Clone)]
+/// 等価性は構造的に比較する。順序は数値同士・文字列同士のみ定義される
+/// 型変換は as_number (算術: 真偽値は 1/0) と is_truthy (条件: 数値は 0 以外が真) に集約する
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum Value {
     Number(f64),
     String(String),
+    /// 真偽値 (真/偽)
+    Bool(bool),
     /// 画像アセット（パス）
     Image(String),
     /// UIコンポーネント
@@ -17,12 +21,30 @@ pub enum Value {
         label: Option<String>,
         children: Vec<Value>,
         layout: Option<String>, // "vertical" or "horizontal"
+        /// 表示状態 (隠す / 見せる)
+        #[serde(default = "default_true")]
+        visible: bool,
+        /// 操作可能か (無効 に する / 有効 に する)
+        #[serde(default = "default_true")]
+        enabled: bool,
     },
     /// 絆 (ユーザー間の関係性)
     Bond(crate::p2p::Relationship),
+    /// リスト
+    List(Vec<Value>),
+    /// レコード (フィールド名 -> 値、定義順を保持)
+    Record(Vec<(String, Value)>),
+    /// 並列実行中の値 (待つ で解決されるハンドル)
+    Pending(u64),
+    /// 取り消された並列実行の結果
+    Cancelled,
     Nil,
 }
 
+fn default_true() -> bool {
+    true
+}
+
 impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -35,6 +57,7 @@ impl std::fmt::Display for Value {
                 }
             }
             Value::String(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", if *b { "真" } else { "偽" }),
             Value::Image(path) => write!(f, "[Image: {}]", path),
             Value::Component { style, ty, label, children, .. } => {
                 let count = children.len();
@@ -49,12 +72,106 @@ impl std::fmt::Display for Value {
                 
                 write!(f, "[{} {} '{}' ({} children)]", style, ty, content, count)
             }
-            Value::Bond(rel) => write!(f, "[Bond Lvl:{}, Str:{}, Help:{}]", rel.level, rel.strength, rel.help_count),
+            Value::Bond(rel) => write!(f, "{}", rel),
+            Value::List(items) => {
+                let parts: Vec<String> = items.iter().map(|v| v.to_string()).collect();
+                write!(f, "[{}]", parts.join(", "))
+            }
+            Value::Record(fields) => {
+                let parts: Vec<String> = fields.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+                write!(f, "{{{}}}", parts.join(", "))
+            }
+            Value::Pending(id) => write!(f, "<pending #{}>", id),
+            Value::Cancelled => write!(f, "<cancelled>"),
             Value::Nil => write!(f, "nil"),
         }
     }
 }
 
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            // その他は等しい場合のみ比較可能 (PartialEqと整合させる)
+            _ if self == other => Some(std::cmp::Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    /// 算術で使う数値 (真は 1、偽は 0)。数値に変換できない値は None
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    /// 条件として評価したときの真偽 (数値は 0 以外、文字列・リストは空でなければ真)
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Number(n) => *n != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Bool(b) => *b,
+            Value::Bond(rel) => rel.has_bond(),
+            Value::Component { .. } => true,
+            Value::Image(_) => true,
+            Value::List(items) => !items.is_empty(),
+            Value::Record(_) => true,
+            Value::Pending(_) => false,
+            Value::Cancelled => false,
+            Value::Nil => false,
+        }
+    }
+
+    /// レコードのフィールドを取得
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Record(fields) => fields.iter().find(|(k, _)| k == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// コンポーネントが表示状態か (コンポーネント以外は常に表示扱い)
+    pub fn is_visible(&self) -> bool {
+        !matches!(self, Value::Component { visible: false, .. })
+    }
+
+    /// コンポーネントが操作可能か (コンポーネント以外は常に有効扱い)
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Value::Component { enabled: false, .. })
+    }
+
+    /// ラベルが一致するコンポーネントを子孫まで含めて探す
+    pub fn find_component(&self, label: &str) -> Option<&Value> {
+        match self {
+            Value::Component { label: Some(l), .. } if l == label => Some(self),
+            Value::Component { children, .. } => children.iter().find_map(|c| c.find_component(label)),
+            _ => None,
+        }
+    }
+
+    /// ラベルが一致するコンポーネント (子孫を含む) に更新を適用し、適用数を返す
+    pub fn update_components(&mut self, label: &str, apply: &dyn Fn(&mut Value)) -> usize {
+        let mut count = 0;
+        if let Value::Component { label: Some(l), .. } = self {
+            if l == label {
+                apply(self);
+                count += 1;
+            }
+        }
+        if let Value::Component { children, .. } = self {
+            for child in children {
+                count += child.update_components(label, apply);
+            }
+        }
+        count
+    }
+}
+
 /// シンボルテーブル
 /// 変数の初登場時に自動登録し、O(1)でアクセス可能
 pub struct SymbolTable {
@@ -94,10 +211,47 @@ impl SymbolTable {
         }
     }
 
+    /// 全シンボル中のラベル一致コンポーネントへ更新を適用 (親の子として保持されたコピーも含む)
+    pub fn update_components(&mut self, label: &str, apply: &dyn Fn(&mut Value)) -> usize {
+        self.symbols.values_mut().map(|v| v.update_components(label, apply)).sum()
+    }
+
     /// シンボルの値を取得してクローン
     pub fn get_value(&self, name: &str) -> Value {
         self.symbols.get(name).cloned().unwrap_or(Value::Nil)
     }
+
+    /// 数値として取得 (未定義・型違いは None)
+    pub fn get_number(&self, name: &str) -> Option<f64> {
+        match self.symbols.get(name) {
+            Some(Value::Number(n)) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// 文字列として取得 (未定義・型違いは None)
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        match self.symbols.get(name) {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// 真偽値として取得 (未定義・型違いは None)
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.symbols.get(name) {
+            Some(Value::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// リストとして取得 (未定義・型違いは None)
+    pub fn get_list(&self, name: &str) -> Option<&[Value]> {
+        match self.symbols.get(name) {
+            Some(Value::List(items)) => Some(items),
+            _ => None,
+        }
+    }
 }
 
 impl Default for SymbolTable {
@@ -142,4 +296,90 @@ mod tests {
         table.register("X", Value::Number(10.0));
         assert!(table.contains("X"));
     }
+
+    #[test]
+    fn test_list_and_record_display() {
+        let record = Value::Record(vec![
+            ("名前".to_string(), Value::String("Alice".to_string())),
+            ("徳".to_string(), Value::Number(300.0)),
+        ]);
+        assert!(matches!(record.field("徳"), Some(Value::Number(n)) if *n == 300.0));
+        let list = Value::List(vec![record, Value::Number(1.0)]);
+        assert_eq!(list.to_string(), "[{名前: Alice, 徳: 300}, 1]");
+    }
+
+    #[test]
+    fn test_value_equality() {
+        assert_eq!(Value::Number(1.0), Value::Number(1.0));
+        assert_ne!(Value::Number(1.0), Value::String("1".to_string()));
+        assert_eq!(Value::Nil, Value::Nil);
+        assert_eq!(
+            Value::List(vec![Value::Number(1.0), Value::String("a".to_string())]),
+            Value::List(vec![Value::Number(1.0), Value::String("a".to_string())])
+        );
+        assert_ne!(
+            Value::Record(vec![("a".to_string(), Value::Number(1.0))]),
+            Value::Record(vec![("a".to_string(), Value::Number(2.0))])
+        );
+    }
+
+    #[test]
+    fn test_value_ordering() {
+        assert!(Value::Number(2.0) > Value::Number(1.0));
+        assert!(Value::String("あ".to_string()) < Value::String("い".to_string()));
+        // 異なる型や数値以外は比較できない
+        assert_eq!(Value::Number(1.0).partial_cmp(&Value::String("1".to_string())), None);
+        assert_eq!(Value::List(vec![]).partial_cmp(&Value::List(vec![Value::Nil])), None);
+        assert_eq!(Value::Nil.partial_cmp(&Value::Nil), Some(std::cmp::Ordering::Equal));
+        assert!(Value::Nil >= Value::Nil);
+    }
+
+    #[test]
+    fn test_typed_accessors() {
+        let mut table = SymbolTable::new();
+        table.register("N", Value::Number(3.0));
+        table.register("S", Value::String("あ".to_string()));
+        table.register("B", Value::Bool(true));
+        table.register("L", Value::List(vec![Value::Number(1.0)]));
+
+        assert_eq!(table.get_number("N"), Some(3.0));
+        assert_eq!(table.get_string("S"), Some("あ"));
+        assert_eq!(table.get_bool("B"), Some(true));
+        assert_eq!(table.get_list("L"), Some(&[Value::Number(1.0)][..]));
+
+        // 型違い・未定義は None
+        assert_eq!(table.get_number("S"), None);
+        assert_eq!(table.get_string("N"), None);
+        assert_eq!(table.get_bool("N"), None);
+        assert_eq!(table.get_list("B"), None);
+        assert_eq!(table.get_number("missing"), None);
+    }
+
+    #[test]
+    fn test_value_coercions() {
+        // 算術: 真偽値は 1/0、それ以外の非数値は変換しない
+        assert_eq!(Value::Number(2.5).as_number(), Some(2.5));
+        assert_eq!(Value::Bool(true).as_number(), Some(1.0));
+        assert_eq!(Value::Bool(false).as_number(), Some(0.0));
+        assert_eq!(Value::String("1".to_string()).as_number(), None);
+        assert_eq!(Value::Nil.as_number(), None);
+
+        // 条件: 数値は 0 以外、文字列は空でなければ真
+        assert!(Value::Number(-1.0).is_truthy());
+        assert!(!Value::Number(0.0).is_truthy());
+        assert!(Value::String("a".to_string()).is_truthy());
+        assert!(!Value::String(String::new()).is_truthy());
+        assert!(Value::Bool(true).is_truthy());
+        assert!(!Value::Nil.is_truthy());
+    }
+
+    #[test]
+    fn test_bond_display_and_json() {
+        let rel = crate::p2p::Relationship { level: 2, strength: 35, ..Default::default() };
+        assert_eq!(Value::Bond(rel.clone()).to_string(), "絆(レベル2, 強さ35)");
+
+        let json: serde_json::Value = serde_json::from_str(&rel.to_json()).unwrap();
+        assert_eq!(json["level"], 2);
+        assert_eq!(json["strength"], 35);
+    }
 }
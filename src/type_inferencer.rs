@@ -2,7 +2,7 @@
 //! コード全体をスキャンして変数の型と生存期間を予測する
 //! Eeyo: 次元解析（距離・時間の型安全性）
 
-use crate::parser::{Expr, Program, Statement};
+use crate::parser::{Condition, Expr, Program, Statement};
 use serde::{Deserialize, Serialize};
 
 /// 推論された型
@@ -15,6 +15,10 @@ pub enum InferredType {
     Distance { unit: String },  // "m", "km"
     Duration { unit: String },  // "秒", "分", "時間"
     Bond,                       // 絆 (Relationship)
+    /// 真偽値 (真/偽)
+    Bool,
+    /// 任意 (再帰アクションなど、静的に決まらない)
+    Any,
 }
 
 impl std::fmt::Display for InferredType {
@@ -26,6 +30,8 @@ impl std::fmt::Display for InferredType {
             InferredType::Distance { unit } => write!(f, "Distance({})", unit),
             InferredType::Duration { unit } => write!(f, "Duration({})", unit),
             InferredType::Bond => write!(f, "Bond"),
+            InferredType::Bool => write!(f, "Bool"),
+            InferredType::Any => write!(f, "Any"),
         }
     }
 }
@@ -54,10 +60,24 @@ pub struct VariableMetadata {
     pub reason: String,
 }
 
+/// アクションのシグネチャ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionSignature {
+    /// アクション名
+    pub name: String,
+    /// 結果 の型
+    pub return_type: InferredType,
+}
+
 /// 型推論の結果
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TypeInferenceResult {
     pub variables: Vec<VariableMetadata>,
+    #[serde(default)]
+    pub actions: Vec<ActionSignature>,
+    /// 型の矛盾などの警告 (行番号付き)
+    #[serde(default)]
+    pub diagnostics: Vec<String>,
 }
 
 impl TypeInferenceResult {
@@ -76,15 +96,31 @@ impl TypeInferenceResult {
             ));
             output.push_str(&format!("  Reason: {}\n", var.reason));
         }
+        for action in &self.actions {
+            output.push_str(&format!("Action \"{}\": returns {}\n", action.name, action.return_type));
+        }
+        for diagnostic in &self.diagnostics {
+            output.push_str(&format!("Warning: {}\n", diagnostic));
+        }
         output
     }
 }
 
-pub struct TypeInferencer;
+pub struct TypeInferencer {
+    /// アクション名 -> 戻り値の型 (呼び出し箇所の推論に使う)
+    action_types: std::cell::RefCell<std::collections::HashMap<String, InferredType>>,
+    /// 分岐内で条件から確定した型 (内側ほど後ろ)
+    narrowed: std::cell::RefCell<Vec<(String, InferredType)>>,
+    diagnostics: std::cell::RefCell<Vec<String>>,
+}
 
 impl TypeInferencer {
     pub fn new() -> Self {
-        Self
+        Self {
+            action_types: std::cell::RefCell::new(std::collections::HashMap::new()),
+            narrowed: std::cell::RefCell::new(Vec::new()),
+            diagnostics: std::cell::RefCell::new(Vec::new()),
+        }
     }
 
     /// プログラム全体から型を推論
@@ -92,13 +128,109 @@ impl TypeInferencer {
         let mut variables: std::collections::HashMap<String, VariableMetadata> = 
             std::collections::HashMap::new();
 
+        // 呼び出しより後に定義されたアクションも解決できるよう先に戻り値の型を集める
+        self.action_types.borrow_mut().clear();
+        for stmt in &program.statements {
+            if let Statement::ActionDefinition { name, body, .. } = stmt.unspanned() {
+                let return_type = self.infer_action_return_type(name, body);
+                self.action_types.borrow_mut().insert(name.clone(), return_type);
+            }
+        }
+        // 事前走査の警告は本走査で改めて出る
+        self.diagnostics.borrow_mut().clear();
+
         for (line_idx, stmt) in program.statements.iter().enumerate() {
             let line_num = line_idx + 1;
             self.process_statement(stmt, line_num, &mut variables);
         }
 
+        let mut actions: Vec<ActionSignature> = self.action_types.borrow().iter()
+            .map(|(name, return_type)| ActionSignature { name: name.clone(), return_type: return_type.clone() })
+            .collect();
+        actions.sort_by(|a, b| a.name.cmp(&b.name));
+
         TypeInferenceResult {
             variables: variables.into_values().collect(),
+            actions,
+            diagnostics: self.diagnostics.take(),
+        }
+    }
+
+    /// 条件から then 側で確定する型 (X equals 5 → X は Number)
+    fn narrowing_facts(&self, condition: &Condition) -> Vec<(String, InferredType)> {
+        let literal_type = |e: &Expr| match e {
+            Expr::Variable(_) => None,
+            _ => Some(self.infer_from_expr(e).0).filter(|t| *t != InferredType::Unknown),
+        };
+        match condition {
+            Condition::Equals(Expr::Variable(name), other) | Condition::Equals(other, Expr::Variable(name)) => {
+                literal_type(other).map(|t| (name.clone(), t)).into_iter().collect()
+            }
+            Condition::GreaterThan(Expr::Variable(name), _)
+            | Condition::LessThan(Expr::Variable(name), _)
+            | Condition::GreaterOrEqual(Expr::Variable(name), _) => vec![(name.clone(), InferredType::Number)],
+            Condition::And(left, right) => {
+                let mut facts = self.narrowing_facts(left);
+                facts.extend(self.narrowing_facts(right));
+                facts
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// 分岐内で確定した型 (なければ None)
+    fn narrowed_type(&self, name: &str) -> Option<InferredType> {
+        self.narrowed.borrow().iter().rev().find(|(n, _)| n == name).map(|(_, t)| t.clone())
+    }
+
+    fn report(&self, line_num: usize, message: String) {
+        self.diagnostics.borrow_mut().push(format!("line {}: {}", line_num, message));
+    }
+
+    /// アクション本体の 結果 から戻り値の型を推論する。再帰するアクションは Any
+    fn infer_action_return_type(&self, name: &str, body: &[Statement]) -> InferredType {
+        if body.iter().any(|s| statement_calls(s, name)) {
+            return InferredType::Any;
+        }
+
+        let mut locals = std::collections::HashMap::new();
+        for (idx, stmt) in body.iter().enumerate() {
+            self.process_statement(stmt, idx + 1, &mut locals);
+        }
+
+        let mut types = Vec::new();
+        self.collect_return_types(body, &locals, &mut types);
+        types.retain(|t| *t != InferredType::Unknown);
+        match types.split_first() {
+            None => InferredType::Unknown,
+            Some((first, rest)) if rest.iter().all(|t| t == first) => first.clone(),
+            Some(_) => InferredType::Any,
+        }
+    }
+
+    fn collect_return_types(
+        &self,
+        body: &[Statement],
+        locals: &std::collections::HashMap<String, VariableMetadata>,
+        types: &mut Vec<InferredType>,
+    ) {
+        for stmt in body {
+            match stmt.unspanned() {
+                Statement::ReturnStatement { value: Expr::Variable(var) } => {
+                    types.push(locals.get(var).map(|m| m.inferred_type.clone()).unwrap_or(InferredType::Unknown));
+                }
+                Statement::ReturnStatement { value } => types.push(self.infer_from_expr(value).0),
+                Statement::IfStatement { then_block, else_block, .. } => {
+                    self.collect_return_types(then_block, locals, types);
+                    if let Some(else_stmts) = else_block {
+                        self.collect_return_types(else_stmts, locals, types);
+                    }
+                }
+                Statement::RepeatStatement { body, .. } | Statement::WhileStatement { body, .. } => {
+                    self.collect_return_types(body, locals, types)
+                }
+                _ => {}
+            }
         }
     }
 
@@ -147,6 +279,10 @@ impl TypeInferencer {
             Statement::BinaryOp { target, operand, verb } => {
                 // 演算対象は数値型であるべき
                 if let Expr::Variable(name) = target {
+                    if matches!(verb.as_str(), "足す" | "引く" | "掛ける" | "割る")
+                        && self.narrowed_type(name) == Some(InferredType::String) {
+                        self.report(line_num, format!("'{}' is String in this branch but used in arithmetic ({})", name, verb));
+                    }
                     if let Some(var) = variables.get_mut(name) {
                         var.lifetime.end = line_num;
                         
@@ -168,18 +304,38 @@ impl TypeInferencer {
                     }
                 }
             }
-            Statement::UnaryOp { operand, verb: _ } | Statement::AsyncOp { operand, verb: _ } => {
+            Statement::Parallel { inner } => {
+                self.process_statement(inner, line_num, variables);
+            }
+            Statement::UnaryOp { operand, verb: _ }
+            | Statement::AsyncOp { operand, verb: _ }
+            | Statement::Await { target: operand }
+            | Statement::Cancel { target: operand }
+            | Statement::SetVisibility { target: operand, .. }
+            | Statement::SetEnabled { target: operand, .. } => {
                 if let Expr::Variable(name) = operand {
                     if let Some(var) = variables.get_mut(name) {
                         var.lifetime.end = line_num;
                     }
                 }
             }
-            Statement::IfStatement { condition: _, then_block, else_block } => {
-                // Process statements in then block
+            Statement::Bind { target, variable, .. } => {
+                // 束縛後も変数・コンポーネントは参照され続ける
+                for name in [Some(variable), match target { Expr::Variable(n) => Some(n), _ => None }].into_iter().flatten() {
+                    if let Some(var) = variables.get_mut(name) {
+                        var.lifetime.end = line_num;
+                    }
+                }
+            }
+            Statement::IfStatement { condition, then_block, else_block } => {
+                // Process statements in then block (条件で確定した型を適用)
+                let facts = self.narrowing_facts(condition);
+                let depth = self.narrowed.borrow().len();
+                self.narrowed.borrow_mut().extend(facts);
                 for (idx, inner_stmt) in then_block.iter().enumerate() {
                     self.process_statement(inner_stmt, line_num + idx, variables);
                 }
+                self.narrowed.borrow_mut().truncate(depth);
                 // Process statements in else block
                 if let Some(else_stmts) = else_block {
                     for (i, inner_stmt) in else_stmts.iter().enumerate() {
@@ -187,13 +343,19 @@ impl TypeInferencer {
                     }
                 }
             }
-            Statement::RepeatStatement { count: _, body } => {
+            Statement::RepeatStatement { count: _, body } | Statement::WhileStatement { condition: _, body } => {
                 // Process statements in loop body
                 for (idx, inner_stmt) in body.iter().enumerate() {
                     self.process_statement(inner_stmt, line_num + idx, variables);
                 }
             }
-            Statement::AiOp { result, input: _, verb: _, options: _ } => {
+            Statement::AiOp { result, input, verb, options: _ } => {
+                // AI操作の入力は文字列
+                if let Expr::Variable(name) = input {
+                    if let Some(narrowed) = self.narrowed_type(name).filter(|t| *t != InferredType::String) {
+                        self.report(line_num, format!("'{}' is {} in this branch but used as String ({})", name, narrowed, verb));
+                    }
+                }
                 // AI操作の結果は常にString
                 if let Expr::Variable(name) = result {
                     variables.insert(name.clone(), VariableMetadata {
@@ -205,10 +367,10 @@ impl TypeInferencer {
                     });
                 }
             }
-            Statement::ScreenOp { operand: _ } => {
-                // Screen出力は変数を更新しない
+            Statement::ScreenOp { operand: _ } | Statement::SaveScreen { path: _ } | Statement::Log { .. } => {
+                // Screen出力・記録は変数を更新しない
             }
-            Statement::EventHandler { target: _, event: _, body } => {
+            Statement::EventHandler { body, .. } => {
                 // Process statements in event handler body
                 for (idx, inner_stmt) in body.iter().enumerate() {
                     self.process_statement(inner_stmt, line_num + idx, variables);
@@ -287,6 +449,9 @@ impl TypeInferencer {
             Statement::BeaconBroadcast { .. } | Statement::Notify { .. } | Statement::TokuAccrue { .. } => {
                 // Eeyo用の空間ステートメント（型推論不要）
             }
+            Statement::Assert { .. } => {
+                // 比較のみで変数を定義しない
+            }
             // AGN 2.0: Social Layer statements
             Statement::VariableUpdate { target, value, verb } => {
                 // Determine target name (if simple variable)
@@ -326,6 +491,11 @@ impl TypeInferencer {
                      let _ = self.infer_from_expr(arg);
                  }
             }
+            Statement::Spanned { span, inner } => {
+                 // 実際のソース行を優先する
+                 self.process_statement(inner, span.line, variables);
+            }
+            Statement::Comment { .. } | Statement::Break | Statement::Continue => {}
         }
     }
 
@@ -336,6 +506,11 @@ impl TypeInferencer {
                 1.0,
                 "Assigned from number literal".to_string(),
             ),
+            Expr::Bool(_) => (
+                InferredType::Bool,
+                1.0,
+                "Assigned from boolean literal".to_string(),
+            ),
             Expr::String(_) => (
                 InferredType::String,
                 1.0,
@@ -357,6 +532,11 @@ impl TypeInferencer {
                 1.0,
                 format!("Duration literal with unit '{}'", unit),
             ),
+            Expr::Percent { .. } => (
+                InferredType::Number,
+                1.0,
+                "Assigned from percent literal".to_string(),
+            ),
             // AGN 2.0
             Expr::PropertyAccess { target: _, property: _ } => (
                 InferredType::Unknown,
@@ -368,16 +548,30 @@ impl TypeInferencer {
                 1.0,
                 "Relationship between users".to_string(),
             ),
-            Expr::Call { name: _, args } => {
+            Expr::Call { name, args } => {
                 for arg in args {
                     let _ = self.infer_from_expr(arg);
                 }
+                if let Some(return_type) = self.action_types.borrow().get(name) {
+                    return (
+                        return_type.clone(),
+                        0.9,
+                        format!("Return value of action '{}'", name),
+                    );
+                }
                 (
                     InferredType::Unknown,
                     0.5,
                     "Action/Rule call result".to_string(),
                 )
             }
+            Expr::Spread(inner) | Expr::Await(inner) => self.infer_from_expr(inner),
+            Expr::Record(fields) => {
+                for (_, value) in fields {
+                    let _ = self.infer_from_expr(value);
+                }
+                (InferredType::Unknown, 0.5, "Record literal".to_string())
+            }
         }
     }
 
@@ -430,6 +624,37 @@ impl TypeInferencer {
     }
 }
 
+/// ステートメントが指定のアクションを呼び出すか (入れ子のブロックも含む)
+fn statement_calls(stmt: &Statement, action: &str) -> bool {
+    let body_calls = |body: &[Statement]| body.iter().any(|s| statement_calls(s, action));
+    match stmt.unspanned() {
+        Statement::ActionCall { name, args } => name == action || args.iter().any(|a| expr_calls(a, action)),
+        Statement::Assignment { value, .. }
+        | Statement::ReturnStatement { value }
+        | Statement::VariableUpdate { value, .. } => expr_calls(value, action),
+        Statement::BinaryOp { operand, .. } | Statement::UnaryOp { operand, .. } => expr_calls(operand, action),
+        Statement::IfStatement { then_block, else_block, .. } => {
+            body_calls(then_block) || else_block.as_deref().is_some_and(body_calls)
+        }
+        Statement::RepeatStatement { body, .. } | Statement::WhileStatement { body, .. } | Statement::Block { body, .. } => {
+            body_calls(body)
+        }
+        Statement::Parallel { inner } => statement_calls(inner, action),
+        _ => false,
+    }
+}
+
+fn expr_calls(expr: &Expr, action: &str) -> bool {
+    match expr {
+        Expr::Call { name, args } => name == action || args.iter().any(|a| expr_calls(a, action)),
+        Expr::Spread(inner) | Expr::Await(inner) => expr_calls(inner, action),
+        Expr::PropertyAccess { target, .. } => expr_calls(target, action),
+        Expr::Bond(a, b) => expr_calls(a, action) || expr_calls(b, action),
+        Expr::Record(fields) => fields.iter().any(|(_, v)| expr_calls(v, action)),
+        _ => false,
+    }
+}
+
 impl Default for TypeInferencer {
     fn default() -> Self {
         Self::new()
@@ -473,6 +698,42 @@ mod tests {
         assert_eq!(result.variables[0].inferred_type, InferredType::String);
     }
 
+    #[test]
+    fn test_infer_action_return_type() {
+        let code = "アクション 二乗(N)\n  A は N だ\n  A に N を 掛ける\n  結果 を A とする\nおわり\nX は 二乗(3) だ";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let result = TypeInferencer::new().infer(&program);
+        let x = result.variables.iter().find(|v| v.name == "X").unwrap();
+        assert_eq!(x.inferred_type, InferredType::Number);
+        assert_eq!(result.actions[0].return_type, InferredType::Number);
+        assert!(result.to_human_readable().contains("Action \"二乗\": returns Number"));
+    }
+
+    #[test]
+    fn test_recursive_action_returns_any() {
+        let code = "アクション 階乗(N)\n  結果 を 階乗(N) とする\nおわり\nX は 階乗(3) だ";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let result = TypeInferencer::new().infer(&program);
+        let x = result.variables.iter().find(|v| v.name == "X").unwrap();
+        assert_eq!(x.inferred_type, InferredType::Any);
+    }
+
+    #[test]
+    fn test_branch_narrowing_reports_conflict() {
+        let code = "X は Y だ\nif X equals 5 then\n要旨 は X を 要約する\nend\n乙 は X を 要約する";
+        let mut lexer = Lexer::new(code);
+        let program = Parser::new(lexer.tokenize()).parse().unwrap();
+
+        let result = TypeInferencer::new().infer(&program);
+        // 分岐の外では X の型は確定しないので警告は1件のみ
+        assert_eq!(result.diagnostics.len(), 1);
+        assert!(result.diagnostics[0].contains("'X' is Number in this branch but used as String"));
+    }
+
     #[test]
     fn test_lifetime_tracking() {
         let code = "X は 10 だ\nX に 5 を 足す\nX を 表示する";
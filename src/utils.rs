@@ -27,3 +27,134 @@ pub async fn sleep(ms: u64) {
     // but good to have fallback.
     tokio::time::sleep(tokio::time::Duration::from_millis(ms)).await;
 }
+
+/// 制限時間付きで待つ。時間内に完了しなければ None
+#[cfg(target_arch = "wasm32")]
+pub async fn timeout<F: std::future::Future>(duration: std::time::Duration, future: F) -> Option<F::Output> {
+    tokio::select! {
+        output = future => Some(output),
+        _ = sleep(duration.as_millis() as u64) => None,
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn timeout<F: std::future::Future>(duration: std::time::Duration, future: F) -> Option<F::Output> {
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+/// 現在時刻 (ミリ秒)。イベントの間引きなど経過時間の計測用 (WASMでも動作)
+pub fn now_ms() -> f64 {
+    web_time::SystemTime::now()
+        .duration_since(web_time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// 時刻の取得元。タイマー・TTLなど経過時間に依存する処理に注入する
+pub trait Clock: Send + Sync {
+    /// 現在時刻 (ミリ秒)
+    fn now_ms(&self) -> f64;
+}
+
+/// 実時間の時計
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> f64 {
+        now_ms()
+    }
+}
+
+/// 手動で進める時計 (テスト用)
+#[derive(Debug, Default)]
+pub struct MockClock {
+    now: std::sync::Mutex<f64>,
+}
+
+impl MockClock {
+    pub fn new(start_ms: f64) -> Self {
+        Self { now: std::sync::Mutex::new(start_ms) }
+    }
+
+    /// 時刻を進める (ミリ秒)
+    pub fn advance(&self, ms: f64) {
+        *self.now.lock().unwrap() += ms;
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> f64 {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// 数値表示のロケール (桁区切り・小数点)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    /// 1,000.5
+    #[default]
+    Ja,
+    /// 1,000.5
+    En,
+    /// 1.000,5
+    De,
+    /// 区切りなし: 1000.5
+    Plain,
+}
+
+impl Locale {
+    fn separators(&self) -> (Option<char>, char) {
+        match self {
+            Locale::Ja | Locale::En => (Some(','), '.'),
+            Locale::De => (Some('.'), ','),
+            Locale::Plain => (None, '.'),
+        }
+    }
+}
+
+/// ロケールに従って数値を整形する (例: 1000 -> "1,000")
+pub fn format_number(value: f64, locale: Locale) -> String {
+    let (thousands, decimal) = locale.separators();
+    let raw = value.abs().to_string();
+    let (int_part, frac_part) = match raw.split_once('.') {
+        Some((i, f)) => (i.to_string(), Some(f.to_string())),
+        None => (raw, None),
+    };
+
+    let mut grouped = String::new();
+    for (i, c) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            if let Some(sep) = thousands {
+                grouped.push(sep);
+            }
+        }
+        grouped.push(c);
+    }
+
+    let mut out = String::new();
+    if value < 0.0 {
+        out.push('-');
+    }
+    out.push_str(&grouped);
+    if let Some(frac) = frac_part {
+        out.push(decimal);
+        out.push_str(&frac);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_number_locales() {
+        assert_eq!(format_number(1000.0, Locale::En), "1,000");
+        assert_eq!(format_number(1000.0, Locale::Ja), "1,000");
+        assert_eq!(format_number(1000.0, Locale::Plain), "1000");
+        assert_eq!(format_number(1234567.5, Locale::De), "1.234.567,5");
+        assert_eq!(format_number(-42.0, Locale::En), "-42");
+        assert_eq!(format_number(999.0, Locale::En), "999");
+    }
+}
@@ -177,7 +177,7 @@ wasm-bindgen = "0.2"
                     String::from("    // Complex BinaryOp not supported in Web transpiler yet\n")
                 }
             }
-            Statement::EventHandler { target, event, body } => {
+            Statement::EventHandler { target, event, body, .. } => {
                 let mut s = String::new();
                 let target_name = if let Expr::Variable(n) = target { n.clone() } else { format!("{:?}", target) };
                 s.push_str(&format!("    // Event: on {} {}\n", target_name, event));
@@ -195,6 +195,7 @@ wasm-bindgen = "0.2"
             Statement::ReturnStatement { .. } => {
                  String::from("    // Return statement\n")
             }
+            Statement::Spanned { inner, .. } => self.transpile_statement(inner),
             _ => format!("    // Unsupported statement in Wasm: {:?}\n", stmt),
         }
     }
@@ -203,14 +204,23 @@ wasm-bindgen = "0.2"
         match expr {
             Expr::Number(n) => format!("{:.1}", n),
             Expr::String(s) => format!("\"{}\"", s),
+            Expr::Bool(b) => b.to_string(),
             Expr::Variable(name) => name.clone(),
             // Eeyo: 空間・時間リテラル
             Expr::Distance { value, unit } => format!("\"{:.1}{}\"", value, unit),
             Expr::Duration { value, unit } => format!("\"{:.1}{}\"", value, unit),
+            Expr::Percent { value } => format!("{}", value / 100.0),
             // AGN 2.0: Property Access (Stub)
             Expr::PropertyAccess { .. } => String::from("\"[PropertyAccess Stub]\""),
             Expr::Bond(_, _) => String::from("\"[Bond Stub]\""),
             Expr::Call { name, .. } => format!("\"[Call Stub: {}]\"", name),
+            Expr::Spread(inner) | Expr::Await(inner) => self.transpile_expr_value(inner),
+            Expr::Record(fields) => {
+                let fields: Vec<String> = fields.iter()
+                    .map(|(k, v)| format!("\"{}\": {}", k, self.transpile_expr_value(v)))
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
         }
     }
 